@@ -0,0 +1,54 @@
+//! The table of symbols kapi exports to loaded modules
+//!
+//! Every `#[capi_fn]` and `#[cdata]` item linked into the image is recorded in
+//! the `kmod_symtab` section, so embedders can resolve module imports without
+//! listing the symbols by hand.
+
+use kmod_tools::KernelSymbol;
+
+// Defined by the linker around the `kmod_symtab` section
+unsafe extern "C" {
+    static __start_kmod_symtab: u8;
+    static __stop_kmod_symtab: u8;
+}
+
+/// All symbols exported with `#[capi_fn]` or `#[cdata]`.
+///
+/// The table is the `kmod_symtab` section itself, so it is a slice of
+/// [`KernelSymbol`] rather than of `(name, address)` tuples, whose layout is
+/// unspecified and could not be borrowed from the section without a copy.
+/// Each entry gives the same pair through [`KernelSymbol::name`] and
+/// [`KernelSymbol::addr`].
+pub fn export_table() -> &'static [KernelSymbol] {
+    let start = &raw const __start_kmod_symtab;
+    let stop = &raw const __stop_kmod_symtab;
+    let len = (stop as usize - start as usize) / core::mem::size_of::<KernelSymbol>();
+    unsafe { core::slice::from_raw_parts(start.cast(), len) }
+}
+
+/// Look up the address of an exported symbol, e.g. to implement
+/// `KernelModuleHelper::resolve_symbol`.
+pub fn lookup_symbol(name: &str) -> Option<usize> {
+    export_table()
+        .iter()
+        .find(|sym| sym.name() == name)
+        .map(KernelSymbol::addr)
+}
+
+#[cfg(all(test, feature = "kstr", feature = "kparameter"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_symbol() {
+        assert_eq!(
+            lookup_symbol("strlen"),
+            Some(crate::string::strlen as *const () as usize)
+        );
+        assert_eq!(
+            lookup_symbol("param_ops_int"),
+            Some(&raw const crate::param::param_ops_int as usize)
+        );
+        assert_eq!(lookup_symbol("no_such_symbol"), None);
+    }
+}
@@ -0,0 +1,268 @@
+//! Hex conversion and dump helpers for kernel modules
+//!
+//! References: <https://elixir.bootlin.com/linux/v6.6/source/lib/hexdump.c>
+
+use core::ffi::{c_char, c_int};
+
+use axerrno::LinuxError;
+use kmod::capi_fn;
+
+use crate::kstrtox::{is_xdigit, to_lower};
+
+/// hex_to_bin - convert a hex digit to its real value
+/// # Arguments
+/// - ch: ascii character represents hex digit
+/// # Returns
+/// The value of the hex digit, or -1 if @ch is not a hex digit
+#[capi_fn]
+pub unsafe extern "C" fn hex_to_bin(ch: c_char) -> c_int {
+    let c = ch as u8;
+    if !is_xdigit(c) {
+        return -1;
+    }
+    if c.is_ascii_digit() {
+        (c - b'0') as c_int
+    } else {
+        (to_lower(c) - b'a' + 10) as c_int
+    }
+}
+
+/// hex2bin - convert an ascii hexadecimal string to its binary representation
+/// # Arguments
+/// - dst: binary result
+/// - src: ascii hexadecimal string
+/// - count: result length
+/// # Returns
+/// 0 on success, -EINVAL in case of bad input
+#[capi_fn]
+pub unsafe extern "C" fn hex2bin(mut dst: *mut u8, mut src: *const c_char, count: usize) -> c_int {
+    for _ in 0..count {
+        let hi = hex_to_bin(*src);
+        if hi < 0 {
+            return -(LinuxError::EINVAL as c_int);
+        }
+        src = src.add(1);
+        let lo = hex_to_bin(*src);
+        if lo < 0 {
+            return -(LinuxError::EINVAL as c_int);
+        }
+        src = src.add(1);
+        *dst = ((hi << 4) | lo) as u8;
+        dst = dst.add(1);
+    }
+    0
+}
+
+const HEX_ASC: &[u8; 16] = b"0123456789abcdef";
+
+/// bin2hex - convert binary data to an ascii hexadecimal string
+/// # Arguments
+/// - dst: ascii hexadecimal result
+/// - src: binary data
+/// - count: binary data length
+/// # Returns
+/// A pointer just past the last character written. The result is not
+/// NUL-terminated.
+#[capi_fn]
+pub unsafe extern "C" fn bin2hex(
+    mut dst: *mut c_char,
+    src: *const core::ffi::c_void,
+    count: usize,
+) -> *mut c_char {
+    let src = core::slice::from_raw_parts(src as *const u8, count);
+    for &byte in src {
+        *dst = HEX_ASC[(byte >> 4) as usize] as c_char;
+        *dst.add(1) = HEX_ASC[(byte & 0xf) as usize] as c_char;
+        dst = dst.add(2);
+    }
+    dst
+}
+
+/// hex_dump_to_buffer - convert a blob of data to "hex ASCII" in memory
+/// # Arguments
+/// - buf: data blob to dump
+/// - len: number of bytes in the @buf
+/// - rowsize: number of bytes to print per line; must be 16 or 32
+/// - groupsize: number of bytes to print at a time (1, 2, 4, 8; default = 1)
+/// - linebuf: where to put the converted data
+/// - linebuflen: total size of @linebuf, including space for terminating NUL
+/// - ascii: include ASCII after the hex output
+/// # Returns
+/// The amount of bytes placed in @linebuf (excluding the terminating NUL),
+/// or, if @linebuf is too small, the amount it would have needed. At most
+/// one line (@rowsize bytes of @buf) is converted.
+///
+/// Example output using 1-byte groups, with @ascii:
+/// `40 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f  @ABCDEFGHIJKLMNO`
+#[capi_fn]
+pub unsafe extern "C" fn hex_dump_to_buffer(
+    buf: *const core::ffi::c_void,
+    mut len: usize,
+    mut rowsize: c_int,
+    mut groupsize: c_int,
+    linebuf: *mut c_char,
+    linebuflen: usize,
+    ascii: bool,
+) -> c_int {
+    use core::fmt::Write;
+
+    if rowsize != 16 && rowsize != 32 {
+        rowsize = 16;
+    }
+    let rowsize = rowsize as usize;
+    // limit to one line at a time
+    len = len.min(rowsize);
+    if groupsize <= 0 || !(groupsize as u32).is_power_of_two() || groupsize > 8 {
+        groupsize = 1;
+    }
+    let mut groupsize = groupsize as usize;
+    // no mixed size output
+    if !len.is_multiple_of(groupsize) {
+        groupsize = 1;
+    }
+    let ascii_column = rowsize * 2 + rowsize / groupsize + 1;
+    let data = core::slice::from_raw_parts(buf as *const u8, len);
+
+    let mut line = alloc::string::String::new();
+    for (j, group) in data.chunks_exact(groupsize).enumerate() {
+        let sep = if j != 0 { " " } else { "" };
+        let _ = match groupsize {
+            8 => write!(
+                line,
+                "{}{:016x}",
+                sep,
+                u64::from_ne_bytes(group.try_into().unwrap())
+            ),
+            4 => write!(
+                line,
+                "{}{:08x}",
+                sep,
+                u32::from_ne_bytes(group.try_into().unwrap())
+            ),
+            2 => write!(
+                line,
+                "{}{:04x}",
+                sep,
+                u16::from_ne_bytes(group.try_into().unwrap())
+            ),
+            _ => write!(line, "{}{:02x}", sep, group[0]),
+        };
+    }
+    if ascii {
+        while line.len() < ascii_column {
+            line.push(' ');
+        }
+        for &ch in data {
+            let printable = ch.is_ascii_graphic() || ch == b' ';
+            line.push(if printable { ch as char } else { '.' });
+        }
+    }
+
+    if linebuflen != 0 {
+        let n = line.len().min(linebuflen - 1);
+        core::ptr::copy_nonoverlapping(line.as_ptr(), linebuf as *mut u8, n);
+        *linebuf.add(n) = 0;
+    }
+    line.len() as c_int
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_to_bin() {
+        assert_eq!(unsafe { hex_to_bin(b'0' as c_char) }, 0);
+        assert_eq!(unsafe { hex_to_bin(b'9' as c_char) }, 9);
+        assert_eq!(unsafe { hex_to_bin(b'a' as c_char) }, 10);
+        assert_eq!(unsafe { hex_to_bin(b'F' as c_char) }, 15);
+        assert_eq!(unsafe { hex_to_bin(b'g' as c_char) }, -1);
+        assert_eq!(unsafe { hex_to_bin(b' ' as c_char) }, -1);
+    }
+
+    #[test]
+    fn test_hex2bin() {
+        let mut dst = [0u8; 4];
+        let ret = unsafe { hex2bin(dst.as_mut_ptr(), c"deadBEEF".as_ptr(), dst.len()) };
+        assert_eq!(ret, 0);
+        assert_eq!(dst, [0xde, 0xad, 0xbe, 0xef]);
+
+        let mut dst = [0u8; 2];
+        let ret = unsafe { hex2bin(dst.as_mut_ptr(), c"12x4".as_ptr(), dst.len()) };
+        assert_eq!(ret, -(LinuxError::EINVAL as c_int));
+    }
+
+    #[test]
+    fn test_bin2hex() {
+        let src = [0x01u8, 0xab, 0xcd, 0xef];
+        let mut dst = [0 as c_char; 8];
+        let end = unsafe { bin2hex(dst.as_mut_ptr(), src.as_ptr() as _, src.len()) };
+        assert_eq!(end, unsafe { dst.as_mut_ptr().add(8) });
+        assert_eq!(dst.map(|c| c as u8), *b"01abcdef");
+    }
+
+    #[test]
+    fn test_hex_dump_to_buffer() {
+        let data: [u8; 16] = core::array::from_fn(|i| b'@' + i as u8);
+        let mut line = [0 as c_char; 80];
+        let ret = unsafe {
+            hex_dump_to_buffer(
+                data.as_ptr() as _,
+                data.len(),
+                16,
+                1,
+                line.as_mut_ptr(),
+                line.len(),
+                true,
+            )
+        };
+        let s = unsafe { core::ffi::CStr::from_ptr(line.as_ptr()) };
+        assert_eq!(
+            s.to_str().unwrap(),
+            "40 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f  @ABCDEFGHIJKLMNO"
+        );
+        assert_eq!(ret as usize, s.to_bytes().len());
+    }
+
+    #[test]
+    fn test_hex_dump_to_buffer_groups() {
+        let data = [0x00u8, 0x01, 0x02, 0x03, 0x0a, 0x7f, 0x41, 0x42];
+        let mut line = [0 as c_char; 80];
+        let ret = unsafe {
+            hex_dump_to_buffer(
+                data.as_ptr() as _,
+                data.len(),
+                16,
+                4,
+                line.as_mut_ptr(),
+                line.len(),
+                false,
+            )
+        };
+        let s = unsafe { core::ffi::CStr::from_ptr(line.as_ptr()) };
+        let expected = if cfg!(target_endian = "little") {
+            "03020100 42417f0a"
+        } else {
+            "00010203 0a7f4142"
+        };
+        assert_eq!(s.to_str().unwrap(), expected);
+        assert_eq!(ret, 17);
+
+        // Too small: truncated, but reports the full length
+        let mut small = [0 as c_char; 4];
+        let ret = unsafe {
+            hex_dump_to_buffer(
+                data.as_ptr() as _,
+                data.len(),
+                16,
+                4,
+                small.as_mut_ptr(),
+                4,
+                false,
+            )
+        };
+        assert_eq!(ret, 17);
+        let s = unsafe { core::ffi::CStr::from_ptr(small.as_ptr()) };
+        assert_eq!(s.to_bytes().len(), 3);
+    }
+}
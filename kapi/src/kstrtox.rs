@@ -9,13 +9,13 @@ const INT_MAX: usize = i32::MAX as usize;
 
 /// Helper: convert character to lowercase
 #[inline]
-fn to_lower(c: u8) -> u8 {
+pub(crate) fn to_lower(c: u8) -> u8 {
     if c.is_ascii_uppercase() { c + 32 } else { c }
 }
 
 /// Helper: check if character is a hex digit
 #[inline]
-fn is_xdigit(c: u8) -> bool {
+pub(crate) fn is_xdigit(c: u8) -> bool {
     c.is_ascii_digit() || (b'a'..=b'f').contains(&c) || (b'A'..=b'F').contains(&c)
 }
 
@@ -33,9 +33,11 @@ pub unsafe extern "C" fn _parse_integer_fixup_radix(
     if *base == 0 {
         let first = *s as u8;
         if first == b'0' {
-            let second = *s.add(1) as u8;
-            if to_lower(second) == b'x' && is_xdigit(*s.add(2) as u8) {
+            let second = to_lower(*s.add(1) as u8);
+            if second == b'x' && is_xdigit(*s.add(2) as u8) {
                 *base = 16;
+            } else if second == b'b' && matches!(*s.add(2) as u8, b'0' | b'1') {
+                *base = 2;
             } else {
                 *base = 8;
             }
@@ -46,6 +48,9 @@ pub unsafe extern "C" fn _parse_integer_fixup_radix(
     if *base == 16 && *s as u8 == b'0' && to_lower(*s.add(1) as u8) == b'x' {
         s = s.add(2);
     }
+    if *base == 2 && *s as u8 == b'0' && to_lower(*s.add(1) as u8) == b'b' {
+        s = s.add(2);
+    }
     s
 }
 
@@ -150,8 +155,9 @@ fn kstrtoull_internal(s: *const core::ffi::c_char, base: u32, res: *mut u64) ->
 /// - base: The number base to use. The maximum supported base is 16. If base is
 ///   given as 0, then the base of the string is automatically detected with the
 ///   conventional semantics - If it begins with 0x the number will be parsed as a
-///   hexadecimal (case insensitive), if it otherwise begins with 0, it will be
-///   parsed as an octal number. Otherwise it will be parsed as a decimal.
+///   hexadecimal (case insensitive), if it begins with 0b as a binary number,
+///   if it otherwise begins with 0, it will be parsed as an octal number.
+///   Otherwise it will be parsed as a decimal.
 /// - res: Where to write the result of the conversion on success.
 ///
 /// # Returns
@@ -354,41 +360,44 @@ pub unsafe extern "C" fn kstrtos8(s: *const core::ffi::c_char, base: u32, res: *
 /// - res: result
 /// # Returns
 /// 0 if successful, -EINVAL otherwise
-/// This routine returns 0 iff the first character is one of 'YyTt1NnFf0', or
-/// [oO][NnFf] for "on" and "off". Otherwise it will return -EINVAL.  Value
-/// pointed to by res is updated upon finding a match
+/// This routine returns 0 iff the whole string is one of "y", "yes", "t",
+/// "true", "1", "n", "no", "f", "false", "0", "on" or "off" (case-insensitive),
+/// optionally followed by a single newline. Otherwise it will return -EINVAL.
+/// Value pointed to by res is updated upon finding a match
 #[capi_fn]
 #[inline(never)]
 pub unsafe extern "C" fn kstrtobool(s: *const core::ffi::c_char, res: *mut bool) -> c_int {
     if s.is_null() || res.is_null() {
         return -(LinuxError::EINVAL as c_int);
     }
-    let first_char = *s as u8;
-
-    match first_char {
-        b'y' | b'Y' | b't' | b'T' | b'1' => {
-            *res = true;
-            0
-        }
-        b'n' | b'N' | b'f' | b'F' | b'0' => {
-            *res = false;
+    let bytes = core::ffi::CStr::from_ptr(s).to_bytes();
+    // Like kstrtoull, tolerate a single trailing newline
+    let token = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    const WORDS: [(&[u8], bool); 12] = [
+        (b"y", true),
+        (b"yes", true),
+        (b"t", true),
+        (b"true", true),
+        (b"1", true),
+        (b"on", true),
+        (b"n", false),
+        (b"no", false),
+        (b"f", false),
+        (b"false", false),
+        (b"0", false),
+        (b"off", false),
+    ];
+    let value = WORDS
+        .iter()
+        .find(|(word, _)| token.eq_ignore_ascii_case(word))
+        .map(|&(_, value)| value);
+
+    match value {
+        Some(value) => {
+            *res = value;
             0
         }
-        b'o' | b'O' => {
-            let second_char = *s.add(1) as u8;
-            match second_char {
-                b'n' | b'N' => {
-                    *res = true;
-                    0
-                }
-                b'f' | b'F' => {
-                    *res = false;
-                    0
-                }
-                _ => -(LinuxError::EINVAL as c_int),
-            }
-        }
-        _ => -(LinuxError::EINVAL as c_int),
+        None => -(LinuxError::EINVAL as c_int),
     }
 }
 
@@ -434,6 +443,32 @@ mod tests {
             );
         }
 
+        // The whole token must match
+        let test_cases = [
+            (c"yes", true),
+            (c"True", true),
+            (c"y\n", true),
+            (c"no\n", false),
+            (c"false", false),
+        ];
+        for (input, expected) in test_cases.iter() {
+            let mut result: bool = !expected;
+            let ret_code = unsafe { kstrtobool(input.as_ptr(), &mut result as *mut bool) };
+            assert_eq!(ret_code, 0, "Input: {:?}", input);
+            assert_eq!(result, *expected, "Input: {:?}", input);
+        }
+        let invalid_inputs = [c"ynope", c"yes_but_no", c"onx", c"y\n\n", c"1 "];
+        for input in invalid_inputs.iter() {
+            let mut result: bool = false;
+            let ret_code = unsafe { kstrtobool(input.as_ptr(), &mut result as *mut bool) };
+            assert_eq!(
+                ret_code,
+                -(super::LinuxError::EINVAL as c_int),
+                "Input: {:?}",
+                input
+            );
+        }
+
         // Test null pointer inputs
         let mut result: bool = false;
         let ret_code = unsafe { kstrtobool(core::ptr::null(), &mut result as *mut bool) };
@@ -464,6 +499,24 @@ mod tests {
         assert_eq!(ret, 0);
         assert_eq!(result, 511);
 
+        // Test binary with prefix, detected or explicit
+        let ret = unsafe { kstrtoull(c"0b1010".as_ptr(), 0, &mut result) };
+        assert_eq!(ret, 0);
+        assert_eq!(result, 10);
+        let ret = unsafe { kstrtoull(c"0B11".as_ptr(), 2, &mut result) };
+        assert_eq!(ret, 0);
+        assert_eq!(result, 3);
+
+        // Without binary digits after it, 0b is not a prefix
+        let ret = unsafe { kstrtoull(c"0b2".as_ptr(), 0, &mut result) };
+        assert!(ret < 0);
+        // An explicit base keeps its own rules
+        let ret = unsafe { kstrtoull(c"0b0".as_ptr(), 8, &mut result) };
+        assert!(ret < 0);
+        let ret = unsafe { kstrtoull(c"0b1".as_ptr(), 16, &mut result) };
+        assert_eq!(ret, 0);
+        assert_eq!(result, 0xb1);
+
         // Test with leading plus sign
         let ret = unsafe { kstrtoull(c"+456".as_ptr(), 10, &mut result) };
         assert_eq!(ret, 0);
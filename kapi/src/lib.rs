@@ -10,13 +10,26 @@ type Result<T> = LinuxResult<T>;
 #[allow(dead_code)]
 type ModuleErr = LinuxError;
 
+#[cfg(any(feature = "kstr", feature = "kparameter"))]
+mod export;
+#[cfg(feature = "kstr")]
+pub mod hexdump;
 #[cfg(feature = "kstr")]
 pub mod kstrtox;
 #[cfg(feature = "kmem")]
 pub mod mm;
+#[cfg(feature = "kstr")]
+pub mod nospec;
 #[cfg(feature = "kparameter")]
 pub mod param;
 #[cfg(feature = "kstr")]
 pub mod string;
 #[cfg(feature = "kstr")]
 pub mod string_helper;
+#[cfg(feature = "kstr")]
+pub mod stringhash;
+#[cfg(feature = "kstr")]
+pub mod vsprintf;
+
+#[cfg(any(feature = "kstr", feature = "kparameter"))]
+pub use export::{export_table, lookup_symbol};
@@ -1 +1,52 @@
+use core::ffi::c_uint;
+
+use kmod::capi_fn;
+
 pub mod util;
+pub mod vmalloc;
+
+/// log2 of the page size: 14, as LoongArch kernels default to 16K pages
+#[cfg(target_arch = "loongarch64")]
+pub const PAGE_SHIFT: u32 = 14;
+/// log2 of the page size: 12, the 4K default of the other architectures
+#[cfg(not(target_arch = "loongarch64"))]
+pub const PAGE_SHIFT: u32 = 12;
+/// Size of a page in bytes
+pub const PAGE_SIZE: usize = 1 << PAGE_SHIFT;
+
+/// get_order - Determine the allocation order of a memory size
+///
+/// # Arguments
+/// - size: The size for which to get the order
+///
+/// # Returns
+/// the order of the smallest power-of-two number of pages that holds @size,
+/// e.g. 0 for up to %PAGE_SIZE bytes and 1 for up to twice that.
+/// The result is undefined if the size is 0.
+#[capi_fn]
+pub unsafe extern "C" fn get_order(size: usize) -> c_uint {
+    let pages = size.wrapping_sub(1) >> PAGE_SHIFT;
+    usize::BITS - pages.leading_zeros()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_order() {
+        assert_eq!(PAGE_SIZE, 1 << PAGE_SHIFT);
+        assert_eq!(unsafe { get_order(1) }, 0);
+        assert_eq!(unsafe { get_order(PAGE_SIZE - 1) }, 0);
+        assert_eq!(unsafe { get_order(PAGE_SIZE) }, 0);
+        assert_eq!(unsafe { get_order(PAGE_SIZE + 1) }, 1);
+        assert_eq!(unsafe { get_order(2 * PAGE_SIZE) }, 1);
+        assert_eq!(unsafe { get_order(2 * PAGE_SIZE + 1) }, 2);
+        assert_eq!(unsafe { get_order(3 * PAGE_SIZE) }, 2);
+        assert_eq!(unsafe { get_order(4 * PAGE_SIZE) }, 2);
+        assert_eq!(unsafe { get_order(5 * PAGE_SIZE) }, 3);
+        assert_eq!(unsafe { get_order(1024 * PAGE_SIZE) }, 10);
+        assert_eq!(unsafe { get_order(1024 * PAGE_SIZE + 1) }, 11);
+        assert_eq!(unsafe { get_order(usize::MAX) }, usize::BITS - PAGE_SHIFT);
+    }
+}
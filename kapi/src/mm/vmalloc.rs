@@ -0,0 +1,201 @@
+//! `vmalloc` and `kvmalloc` over the global allocator
+//!
+//! Every buffer is preceded by a header recording the layout it was
+//! allocated with, so `vfree` and `kvfree` only need the pointer. The
+//! `v*` family returns page-aligned buffers.
+
+use alloc::alloc::{Layout, alloc, alloc_zeroed, dealloc};
+use core::{
+    ffi::{c_int, c_void},
+    mem::size_of,
+    ptr::null_mut,
+};
+
+use kmod::capi_fn;
+
+use super::PAGE_SIZE;
+
+/// `__GFP_ZERO`, return zeroed memory
+pub const GFP_ZERO: u32 = 0x100;
+
+/// Stored right before the returned pointer
+#[repr(C)]
+struct Header {
+    /// Size of the whole allocation, header included
+    size: usize,
+    /// Alignment of the allocation, which is also the offset of the buffer
+    align: usize,
+}
+
+const MIN_ALIGN: usize = size_of::<Header>();
+
+/// Allocate `size` bytes aligned to `align`, which must be a power of two
+/// no smaller than [`MIN_ALIGN`]. Returns NULL if `size` is 0 or the
+/// allocation fails.
+fn alloc_with_header(size: usize, align: usize, zero: bool) -> *mut c_void {
+    if size == 0 {
+        return null_mut();
+    }
+    let Some(layout) = size
+        .checked_add(align)
+        .and_then(|total| Layout::from_size_align(total, align).ok())
+    else {
+        return null_mut();
+    };
+    let base = unsafe {
+        if zero {
+            alloc_zeroed(layout)
+        } else {
+            alloc(layout)
+        }
+    };
+    if base.is_null() {
+        return null_mut();
+    }
+    unsafe {
+        let buf = base.add(align);
+        buf.cast::<Header>().sub(1).write(Header {
+            size: layout.size(),
+            align,
+        });
+        buf.cast()
+    }
+}
+
+/// Free a buffer returned by [`alloc_with_header`]
+unsafe fn free_with_header(addr: *const c_void) {
+    if addr.is_null() {
+        return;
+    }
+    unsafe {
+        let header = addr.cast::<Header>().sub(1).read();
+        let base = addr.cast::<u8>().sub(header.align).cast_mut();
+        dealloc(
+            base,
+            Layout::from_size_align_unchecked(header.size, header.align),
+        );
+    }
+}
+
+/// vmalloc - allocate virtually contiguous memory
+/// # Arguments
+/// - size: allocation size
+/// # Returns
+/// pointer to the page-aligned allocated memory or %NULL on error.
+/// Use vfree() to free.
+#[capi_fn]
+pub unsafe extern "C" fn vmalloc(size: usize) -> *mut c_void {
+    alloc_with_header(size, PAGE_SIZE, false)
+}
+
+/// vzalloc - allocate virtually contiguous memory with zero fill
+/// # Arguments
+/// - size: allocation size
+/// # Returns
+/// pointer to the page-aligned, zeroed memory or %NULL on error.
+/// Use vfree() to free.
+#[capi_fn]
+pub unsafe extern "C" fn vzalloc(size: usize) -> *mut c_void {
+    alloc_with_header(size, PAGE_SIZE, true)
+}
+
+/// vfree - Release memory allocated by vmalloc()
+/// # Arguments
+/// - addr: Memory base address
+/// # Note
+/// If @addr is NULL, no operation is performed.
+#[capi_fn]
+pub unsafe extern "C" fn vfree(addr: *const c_void) {
+    unsafe { free_with_header(addr) }
+}
+
+/// kvmalloc_node - attempt to allocate physically contiguous memory, but upon
+/// failure, fall back to non-contiguous (vmalloc) allocation.
+///
+/// `kvmalloc()` and `kvzalloc()` are inline wrappers around this function, so
+/// it is the symbol modules import.
+/// # Arguments
+/// - size: size of the request.
+/// - flags: GFP mask for the allocation, only `__GFP_ZERO` is honoured
+/// - node: numa node to allocate from, ignored
+/// # Returns
+/// pointer to the allocated memory or %NULL in case of failure.
+/// Use kvfree() to free.
+#[capi_fn]
+pub unsafe extern "C" fn kvmalloc_node(size: usize, flags: u32, _node: c_int) -> *mut c_void {
+    let zero = flags & GFP_ZERO != 0;
+    let buf = alloc_with_header(size, MIN_ALIGN, zero);
+    if !buf.is_null() || size == 0 {
+        return buf;
+    }
+    alloc_with_header(size, PAGE_SIZE, zero)
+}
+
+/// kvfree() - Free memory.
+/// # Arguments
+/// - addr: Pointer to allocated memory.
+/// # Note
+/// kvfree frees memory allocated by vmalloc(), vzalloc() or kvmalloc().
+/// If @addr is NULL, no operation is performed.
+#[capi_fn]
+pub unsafe extern "C" fn kvfree(addr: *const c_void) {
+    unsafe { free_with_header(addr) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vmalloc_round_trip() {
+        unsafe {
+            let buf = vmalloc(3 * PAGE_SIZE + 1).cast::<u8>();
+            assert!(!buf.is_null());
+            assert_eq!(buf as usize % PAGE_SIZE, 0);
+            buf.write_bytes(0xa5, 3 * PAGE_SIZE + 1);
+            assert_eq!(*buf.add(3 * PAGE_SIZE), 0xa5);
+            vfree(buf.cast());
+
+            assert!(vmalloc(0).is_null());
+            assert!(vmalloc(usize::MAX).is_null());
+            vfree(null_mut());
+        }
+    }
+
+    #[test]
+    fn test_vzalloc_is_zeroed() {
+        unsafe {
+            // Dirty some memory first so a reused block would show
+            let buf = vmalloc(PAGE_SIZE).cast::<u8>();
+            buf.write_bytes(0xff, PAGE_SIZE);
+            vfree(buf.cast());
+
+            let buf = vzalloc(PAGE_SIZE).cast::<u8>();
+            assert!(!buf.is_null());
+            assert_eq!(buf as usize % PAGE_SIZE, 0);
+            let bytes = core::slice::from_raw_parts(buf, PAGE_SIZE);
+            assert!(bytes.iter().all(|&b| b == 0));
+            vfree(buf.cast());
+        }
+    }
+
+    #[test]
+    fn test_kvmalloc_round_trip() {
+        unsafe {
+            let buf = kvmalloc_node(100, 0, -1).cast::<u8>();
+            assert!(!buf.is_null());
+            assert_eq!(buf as usize % MIN_ALIGN, 0);
+            buf.write_bytes(0x5a, 100);
+            kvfree(buf.cast());
+
+            let buf = kvmalloc_node(2 * PAGE_SIZE, GFP_ZERO, -1).cast::<u8>();
+            let bytes = core::slice::from_raw_parts(buf, 2 * PAGE_SIZE);
+            assert!(bytes.iter().all(|&b| b == 0));
+            kvfree(buf.cast());
+
+            // kvfree also takes vmalloc() memory
+            kvfree(vmalloc(10));
+            kvfree(null_mut());
+        }
+    }
+}
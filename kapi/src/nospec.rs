@@ -0,0 +1,76 @@
+//! Bounds clamping that holds under speculative execution
+//!
+//! In the kernel these are inline helpers, but modules built against headers
+//! that out-of-line them import the symbols, so both are exported here.
+//!
+//! References: <https://elixir.bootlin.com/linux/v6.6/source/include/linux/nospec.h>
+
+use core::hint::black_box;
+
+use kmod::capi_fn;
+
+/// array_index_mask_nospec() - generate a mask that is ~0UL when the
+/// bounds check succeeds and 0 otherwise
+/// # Arguments
+/// - index: array element index
+/// - size: number of elements in array
+/// # Returns
+/// `usize::MAX` if `index < size`, 0 otherwise. Computed without branches, so
+/// a mispredicted bounds check cannot speculatively use an out-of-range index.
+/// # Note
+/// Like the kernel version, both @index and @size must be at most `isize::MAX`.
+#[capi_fn]
+pub unsafe extern "C" fn array_index_mask_nospec(index: usize, size: usize) -> usize {
+    // Keep the compiler from proving the range and emitting a branch, the
+    // same job OPTIMIZER_HIDE_VAR() does in the kernel
+    let index = black_box(index);
+    (!(index | size.wrapping_sub(1).wrapping_sub(index)) as isize >> (usize::BITS - 1)) as usize
+}
+
+/// array_index_nospec - sanitize an array index after a bounds check
+/// # Arguments
+/// - index: array element index
+/// - size: number of elements in array
+/// # Returns
+/// @index if it is in range, 0 otherwise
+#[capi_fn]
+pub unsafe extern "C" fn array_index_nospec(index: usize, size: usize) -> usize {
+    index & array_index_mask_nospec(index, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_array_index_mask_nospec() {
+        let cases = [
+            (0, 1, usize::MAX),
+            (0, 16, usize::MAX),
+            (15, 16, usize::MAX),
+            (16, 16, 0),
+            (17, 16, 0),
+            (0, 0, 0),
+            (isize::MAX as usize - 1, isize::MAX as usize, usize::MAX),
+            (isize::MAX as usize, isize::MAX as usize, 0),
+            (isize::MAX as usize, 1, 0),
+        ];
+        for (index, size, mask) in cases {
+            assert_eq!(
+                unsafe { array_index_mask_nospec(index, size) },
+                mask,
+                "index {index} size {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_array_index_nospec() {
+        unsafe {
+            assert_eq!(array_index_nospec(3, 8), 3);
+            assert_eq!(array_index_nospec(7, 8), 7);
+            assert_eq!(array_index_nospec(8, 8), 0);
+            assert_eq!(array_index_nospec(1000, 8), 0);
+        }
+    }
+}
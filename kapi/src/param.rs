@@ -15,27 +15,96 @@ pub enum ParamOpsFlags {
     KERNEL_PARAM_OPS_FL_NOARG = 1 << 0,
 }
 
+/// Size of the buffer the `get` operations write into, like a sysfs page
+pub const PARAM_GET_BUF_SIZE: usize = 4096;
+
 pub trait KernelParamValue: Sized {
     fn parse(s: &str) -> Result<Self>;
-    fn format(self, buf: *mut u8) -> Result<usize>;
+    /// Write the value into `buf`, truncated to fit, and return the number
+    /// of bytes written
+    fn format(self, buf: &mut [u8]) -> Result<usize>;
+}
+
+/// Copy as much of `bytes` into `buf` as fits while leaving room for a
+/// terminating NUL, like `scnprintf`. Returns the number of bytes copied.
+fn write_truncated(buf: &mut [u8], bytes: &[u8]) -> usize {
+    let Some(room) = buf.len().checked_sub(1) else {
+        return 0;
+    };
+    let len = bytes.len().min(room);
+    buf[..len].copy_from_slice(&bytes[..len]);
+    buf[len] = 0;
+    len
+}
+
+/// Format the value `kp` points to into the `get` buffer
+fn common_get<T: KernelParamValue + Copy>(
+    buffer: *mut c_char,
+    kp: *const kmod_tools::kernel_param,
+) -> c_int {
+    let arg_ptr = unsafe { kp.as_ref().unwrap().__bindgen_anon_1.arg };
+    let v = unsafe { *(arg_ptr as *const T) };
+    let buf = unsafe { core::slice::from_raw_parts_mut(buffer as *mut u8, PARAM_GET_BUF_SIZE) };
+    v.format(buf).unwrap_or(0) as c_int
+}
+
+/// Split an optional leading `+` or `-` off `s`, returning whether it was `-`
+fn split_sign(s: &str) -> (bool, &str) {
+    match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    }
 }
 
 fn parse_base<T>(s: &str) -> Result<T>
 where
     T: TryFrom<i128>,
 {
-    let s = s.trim();
+    // The radix prefix comes after the sign, e.g. "-0x10"
+    let (negative, s) = split_sign(s.trim());
 
-    let v = if s.starts_with("0x") || s.starts_with("0X") {
-        i128::from_str_radix(&s[2..], 16)
+    let (radix, digits) = if s.starts_with("0x") || s.starts_with("0X") {
+        (16, &s[2..])
     } else if s.starts_with('0') && s.len() > 1 {
-        i128::from_str_radix(&s[1..], 8)
+        (8, &s[1..])
     } else {
-        s.parse::<i128>()
+        (10, s)
+    };
+    // `from_str_radix` would accept a second sign
+    if digits.starts_with(['+', '-']) {
+        return Err(ModuleErr::EINVAL);
     }
-    .map_err(|_| ModuleErr::EINVAL)?;
+    let v = i128::from_str_radix(digits, radix).map_err(|_| ModuleErr::EINVAL)?;
+
+    T::try_from(if negative { -v } else { v }).map_err(|_| ModuleErr::EINVAL)
+}
+
+/// Like [`parse_base`], but stop at the first character that is not a digit
+/// of the radix instead of rejecting it, as `simple_strtol` does. At least
+/// one digit is still required, so `"42x"` is 42 but `"x"` is an error.
+fn parse_lenient<T>(s: &str) -> Result<T>
+where
+    T: TryFrom<i128>,
+{
+    let (negative, s) = split_sign(s.trim_start());
+
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .filter(|rest| rest.starts_with(|c: char| c.is_ascii_hexdigit()));
+    let (radix, digits) = match hex {
+        Some(rest) => (16, rest),
+        // Keep the leading 0, so that "08" is 0 followed by garbage
+        None if s.starts_with('0') => (8, s),
+        None => (10, s),
+    };
+    let len = digits
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(digits.len());
+    let v = i128::from_str_radix(&digits[..len], radix).map_err(|_| ModuleErr::EINVAL)?;
 
-    T::try_from(v).map_err(|_| ModuleErr::EINVAL)
+    T::try_from(if negative { -v } else { v }).map_err(|_| ModuleErr::EINVAL)
 }
 
 fn common_parse<T: KernelParamValue>(val: *const c_char) -> Result<T> {
@@ -49,7 +118,17 @@ fn common_set<T: KernelParamValue>(
     val: *const c_char,
     kp: *const kmod_tools::kernel_param,
 ) -> c_int {
-    let v = match common_parse::<T>(val) {
+    common_set_with(val, kp, T::parse)
+}
+
+/// [`common_set`] with a parser other than [`KernelParamValue::parse`]
+fn common_set_with<T>(
+    val: *const c_char,
+    kp: *const kmod_tools::kernel_param,
+    parse: fn(&str) -> Result<T>,
+) -> c_int {
+    let s = unsafe { CStr::from_ptr(val) }.to_str();
+    let v = match s.map_err(|_| ModuleErr::EINVAL).and_then(parse) {
         Ok(v) => v,
         Err(_) => return -(ModuleErr::EINVAL as c_int),
     };
@@ -62,6 +141,9 @@ fn common_set<T: KernelParamValue>(
 
 /// Macro to define standard kernel parameter operations for a given type.
 ///
+/// Besides `param_ops_<name>`, this defines `param_ops_<name>_lenient`, which
+/// accepts trailing characters after the number (see [`parse_lenient`]).
+///
 /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/params.c#L218>
 macro_rules! impl_macro {
     ($name: ident, $type: ident, $format:expr) => {
@@ -76,13 +158,9 @@ macro_rules! impl_macro {
                 Ok($name(v))
             }
 
-            fn format(self, buf: *mut u8) -> Result<usize> {
+            fn format(self, buf: &mut [u8]) -> Result<usize> {
                 let s = alloc::format!($format, self.0);
-                let bytes = s.as_bytes();
-                unsafe {
-                    core::ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
-                }
-                Ok(bytes.len())
+                Ok(write_truncated(buf, s.as_bytes()))
             }
         }
         paste! {
@@ -99,10 +177,7 @@ macro_rules! impl_macro {
                 buffer: *mut c_char,
                 kp: *const kmod_tools::kernel_param,
             ) -> c_int {
-                let arg_ptr = unsafe { kp.as_ref().unwrap().__bindgen_anon_1.arg };
-                let v = unsafe { *(arg_ptr as *const $name) };
-                let len = v.format(buffer as *mut u8).unwrap_or(0);
-                len as c_int
+                common_get::<$name>(buffer, kp)
             }
 
             #[cdata]
@@ -112,6 +187,22 @@ macro_rules! impl_macro {
                 flags: 0,
                 free: None,
             };
+
+            #[capi_fn]
+            unsafe extern "C" fn [<param_set_$name _lenient>](
+                val: *const c_char,
+                kp: *const kmod_tools::kernel_param,
+            ) -> c_int {
+                common_set_with(val, kp, |s| parse_lenient::<$type>(s).map($name))
+            }
+
+            #[cdata]
+            pub static [<param_ops_$name _lenient>]: kmod_tools::kernel_param_ops = kmod_tools::kernel_param_ops {
+                set: Some([<param_set_$name _lenient>]),
+                get: Some([<param_get_$name>]),
+                flags: 0,
+                free: None,
+            };
         }
     };
 }
@@ -167,14 +258,10 @@ impl KernelParamValue for charp {
         Ok(charp(ptr))
     }
 
-    fn format(self, buf: *mut u8) -> Result<usize> {
-        unsafe {
-            let c_str = CStr::from_ptr(self.0);
-            let s = alloc::format!("{}\n", c_str.to_str().map_err(|_| ModuleErr::EINVAL)?);
-            let bytes = s.as_bytes();
-            core::ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
-            Ok(bytes.len())
-        }
+    fn format(self, buf: &mut [u8]) -> Result<usize> {
+        let c_str = unsafe { CStr::from_ptr(self.0) };
+        let s = alloc::format!("{}\n", c_str.to_str().map_err(|_| ModuleErr::EINVAL)?);
+        Ok(write_truncated(buf, s.as_bytes()))
     }
 }
 
@@ -205,14 +292,14 @@ unsafe extern "C" fn param_get_charp(
     buffer: *mut c_char,
     kp: *const kmod_tools::kernel_param,
 ) -> c_int {
-    let arg_ptr = unsafe { kp.as_ref().unwrap().__bindgen_anon_1.arg };
-    let v = unsafe { *(arg_ptr as *const charp) };
-    let len = v.format(buffer as _).unwrap_or(0);
-    len as c_int
+    common_get::<charp>(buffer, kp)
 }
 
 unsafe extern "C" fn param_free_charp(arg: *mut c_void) {
-    maybe_kfree_parameter(*(arg as *mut *mut c_char));
+    let slot = arg as *mut *mut c_char;
+    maybe_kfree_parameter(*slot);
+    // Leave the slot empty so a later set or free does not see a dangling pointer
+    *slot = core::ptr::null_mut();
 }
 
 #[cdata]
@@ -224,24 +311,31 @@ pub static param_ops_charp: kmod_tools::kernel_param_ops = kmod_tools::kernel_pa
 };
 
 impl KernelParamValue for bool {
-    // One of =[yYnN01]
+    // One of =[yYnN01], or "yes"/"no"/"on"/"off" (case-insensitive)
     fn parse(s: &str) -> Result<Self> {
         let s = s.trim();
         // No equals means "set"...
-        match s {
-            "y" | "Y" | "1" | "" => Ok(true),
-            "n" | "N" | "0" => Ok(false),
-            _ => Err(ModuleErr::EINVAL),
+        if s.is_empty() {
+            return Ok(true);
+        }
+        if ["y", "1", "yes", "on"]
+            .iter()
+            .any(|t| s.eq_ignore_ascii_case(t))
+        {
+            Ok(true)
+        } else if ["n", "0", "no", "off"]
+            .iter()
+            .any(|f| s.eq_ignore_ascii_case(f))
+        {
+            Ok(false)
+        } else {
+            Err(ModuleErr::EINVAL)
         }
     }
 
-    fn format(self, buf: *mut u8) -> Result<usize> {
+    fn format(self, buf: &mut [u8]) -> Result<usize> {
         let s = if self { b"1\n" } else { b"0\n" };
-        let bytes = s;
-        unsafe {
-            core::ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
-        }
-        Ok(bytes.len())
+        Ok(write_truncated(buf, s))
     }
 }
 
@@ -261,10 +355,7 @@ unsafe extern "C" fn param_get_bool(
     buffer: *mut c_char,
     kp: *const kmod_tools::kernel_param,
 ) -> c_int {
-    let arg_ptr = unsafe { kp.as_ref().unwrap().__bindgen_anon_1.arg };
-    let v = unsafe { *(arg_ptr as *const bool) };
-    let len = v.format(buffer as _).unwrap_or(0);
-    len as c_int
+    common_get::<bool>(buffer, kp)
 }
 
 #[cdata]
@@ -275,6 +366,93 @@ pub static param_ops_bool: kmod_tools::kernel_param_ops = kmod_tools::kernel_par
     free: None,
 };
 
+/// A boolean stored as the logical negation of its input, e.g. `nomodeset`.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+struct invbool(bool);
+
+impl KernelParamValue for invbool {
+    fn parse(s: &str) -> Result<Self> {
+        bool::parse(s).map(|v| invbool(!v))
+    }
+
+    // Report the value as it was written, like the kernel's param_get_invbool.
+    fn format(self, buf: &mut [u8]) -> Result<usize> {
+        (!self.0).format(buf)
+    }
+}
+
+unsafe extern "C" fn param_set_invbool(
+    val: *const c_char,
+    kp: *const kmod_tools::kernel_param,
+) -> c_int {
+    let val = if val.is_null() {
+        c"".as_ptr() // No argument means "set"
+    } else {
+        val
+    };
+    common_set::<invbool>(val, kp)
+}
+
+unsafe extern "C" fn param_get_invbool(
+    buffer: *mut c_char,
+    kp: *const kmod_tools::kernel_param,
+) -> c_int {
+    common_get::<invbool>(buffer, kp)
+}
+
+#[cdata]
+pub static param_ops_invbool: kmod_tools::kernel_param_ops = kmod_tools::kernel_param_ops {
+    set: Some(param_set_invbool),
+    get: Some(param_get_invbool),
+    flags: ParamOpsFlags::KERNEL_PARAM_OPS_FL_NOARG as u32,
+    free: None,
+};
+
+/// A boolean stored in an `int` slot as 0/1.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+struct bint(c_int);
+
+impl KernelParamValue for bint {
+    fn parse(s: &str) -> Result<Self> {
+        bool::parse(s).map(|v| bint(v as c_int))
+    }
+
+    fn format(self, buf: &mut [u8]) -> Result<usize> {
+        (self.0 != 0).format(buf)
+    }
+}
+
+unsafe extern "C" fn param_set_bint(
+    val: *const c_char,
+    kp: *const kmod_tools::kernel_param,
+) -> c_int {
+    let val = if val.is_null() {
+        c"".as_ptr() // No argument means "set"
+    } else {
+        val
+    };
+    common_set::<bint>(val, kp)
+}
+
+unsafe extern "C" fn param_get_bint(
+    buffer: *mut c_char,
+    kp: *const kmod_tools::kernel_param,
+) -> c_int {
+    common_get::<bint>(buffer, kp)
+}
+
+#[cdata]
+pub static param_ops_bint: kmod_tools::kernel_param_ops = kmod_tools::kernel_param_ops {
+    set: Some(param_set_bint),
+    get: Some(param_get_bint),
+    flags: ParamOpsFlags::KERNEL_PARAM_OPS_FL_NOARG as u32,
+    free: None,
+};
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -288,7 +466,7 @@ mod tests {
         assert_eq!(parsed, expected);
 
         let mut buf = [0u8; 64];
-        let len = parsed.format(buf.as_mut_ptr()).expect("Failed to format");
+        let len = parsed.format(&mut buf).expect("Failed to format");
         let formatted = core::str::from_utf8(&buf[..len]).expect("Invalid UTF-8");
         assert_eq!(formatted, excepted_str);
     }
@@ -396,6 +574,43 @@ mod tests {
         test_param("0Xdeadbeef", hexint(0xDEADBEEF), "0xdeadbeef\n");
     }
 
+    #[test]
+    fn test_signed_prefixed_param() {
+        test_param("-0x10", int(-16), "-16\n");
+        test_param("-0777", int(-511), "-511\n");
+        test_param("+0x10", int(16), "16\n");
+        test_param("-0x8000", short(-32768), "-32768\n");
+        assert_eq!(int::parse("-0x-10"), Err(ModuleErr::EINVAL));
+        assert_eq!(int::parse("--10"), Err(ModuleErr::EINVAL));
+        assert_eq!(uint::parse("-0x10"), Err(ModuleErr::EINVAL));
+    }
+
+    #[test]
+    fn test_lenient_param() {
+        assert_eq!(int::parse("42x"), Err(ModuleErr::EINVAL));
+        assert_eq!(parse_lenient::<c_int>("42x"), Ok(42));
+        assert_eq!(parse_lenient::<c_int>(" -0x1fz"), Ok(-31));
+        assert_eq!(parse_lenient::<c_int>("0x"), Ok(0));
+        assert_eq!(parse_lenient::<c_int>("019"), Ok(1));
+        assert_eq!(parse_lenient::<c_int>("x42"), Err(ModuleErr::EINVAL));
+        assert_eq!(parse_lenient::<c_uchar>("256k"), Err(ModuleErr::EINVAL));
+    }
+
+    #[test]
+    fn test_lenient_ops() {
+        let mut value: c_int = 0;
+        let mut kp = kmod_tools::kernel_param::default();
+        kp.__bindgen_anon_1.arg = &mut value as *mut c_int as *mut c_void;
+
+        let ret = unsafe { param_set_int(c"42x".as_ptr(), &kp) };
+        assert_eq!(ret, -(ModuleErr::EINVAL as c_int));
+        assert_eq!(value, 0);
+        let set = param_ops_int_lenient.set.unwrap();
+        let ret = unsafe { set(c"42x".as_ptr(), &kp) };
+        assert_eq!(ret, 0);
+        assert_eq!(value, 42);
+    }
+
     #[test]
     fn test_charp_param() {
         let original_str = "Hello, Kernel Param!";
@@ -413,4 +628,102 @@ mod tests {
         test_param("N", false, "0\n");
         test_param("0", false, "0\n");
     }
+
+    #[test]
+    fn test_bool_param_words() {
+        test_param("yes", true, "1\n");
+        test_param("YES", true, "1\n");
+        test_param("on", true, "1\n");
+        test_param("On", true, "1\n");
+        test_param("no", false, "0\n");
+        test_param("No", false, "0\n");
+        test_param("off", false, "0\n");
+        test_param("OFF", false, "0\n");
+        // sysfs writes carry a trailing newline
+        test_param("on\n", true, "1\n");
+        assert_eq!(bool::parse("maybe"), Err(ModuleErr::EINVAL));
+    }
+
+    #[test]
+    fn test_invbool_param() {
+        test_param("y", invbool(false), "1\n");
+        test_param("", invbool(false), "1\n");
+        test_param("n", invbool(true), "0\n");
+        test_param("off", invbool(true), "0\n");
+        assert_eq!(invbool::parse("maybe"), Err(ModuleErr::EINVAL));
+    }
+
+    #[test]
+    fn test_invbool_ops() {
+        let mut value = false;
+        let mut kp = kmod_tools::kernel_param::default();
+        kp.__bindgen_anon_1.arg = &mut value as *mut bool as *mut c_void;
+
+        let ret = unsafe { param_set_invbool(core::ptr::null(), &kp) };
+        assert_eq!(ret, 0);
+        assert!(!value);
+        let ret = unsafe { param_set_invbool(c"n".as_ptr(), &kp) };
+        assert_eq!(ret, 0);
+        assert!(value);
+
+        let mut buf = [0u8; PARAM_GET_BUF_SIZE];
+        let len = unsafe { param_get_invbool(buf.as_mut_ptr() as *mut c_char, &kp) };
+        assert_eq!(&buf[..len as usize], b"0\n");
+    }
+
+    #[test]
+    fn test_bint_param() {
+        test_param("1", bint(1), "1\n");
+        test_param("y", bint(1), "1\n");
+        test_param("0", bint(0), "0\n");
+        test_param("n", bint(0), "0\n");
+        assert_eq!(bint::parse("2"), Err(ModuleErr::EINVAL));
+    }
+
+    #[test]
+    fn test_bint_ops() {
+        let mut value: c_int = 42;
+        let mut kp = kmod_tools::kernel_param::default();
+        kp.__bindgen_anon_1.arg = &mut value as *mut c_int as *mut c_void;
+
+        let ret = unsafe { param_set_bint(c"n".as_ptr(), &kp) };
+        assert_eq!(ret, 0);
+        assert_eq!(value, 0);
+        let ret = unsafe { param_set_bint(core::ptr::null(), &kp) };
+        assert_eq!(ret, 0);
+        assert_eq!(value, 1);
+
+        let mut buf = [0u8; PARAM_GET_BUF_SIZE];
+        let len = unsafe { param_get_bint(buf.as_mut_ptr() as *mut c_char, &kp) };
+        assert_eq!(&buf[..len as usize], b"1\n");
+    }
+
+    #[test]
+    fn test_get_truncates_to_buffer() {
+        let s = alloc::ffi::CString::new("x".repeat(2 * PARAM_GET_BUF_SIZE)).unwrap();
+        let mut value = s.into_raw();
+        let mut kp = kmod_tools::kernel_param::default();
+        kp.__bindgen_anon_1.arg = &mut value as *mut *mut c_char as *mut c_void;
+
+        let mut buf = alloc::vec![0xffu8; PARAM_GET_BUF_SIZE];
+        let len = unsafe { param_get_charp(buf.as_mut_ptr() as *mut c_char, &kp) };
+        assert_eq!(len as usize, PARAM_GET_BUF_SIZE - 1);
+        assert!(buf[..PARAM_GET_BUF_SIZE - 1].iter().all(|&b| b == b'x'));
+        assert_eq!(buf[PARAM_GET_BUF_SIZE - 1], 0);
+        unsafe { param_free_charp(kp.__bindgen_anon_1.arg) };
+
+        let mut small = [0xffu8; 4];
+        assert_eq!(int(-12345).format(&mut small), Ok(3));
+        assert_eq!(&small, b"-12\0");
+        assert_eq!(true.format(&mut []), Ok(0));
+    }
+
+    #[test]
+    fn test_param_ops_aligned() {
+        let align = core::mem::align_of::<kmod_tools::kernel_param_ops>();
+        for ops in [&param_ops_int, &param_ops_charp, &param_ops_bool] {
+            let addr = ops as *const kmod_tools::kernel_param_ops as usize;
+            assert!(addr.is_multiple_of(align));
+        }
+    }
 }
@@ -6,6 +6,7 @@
 
 use core::ffi::{c_char, c_int, c_void};
 
+use axerrno::LinuxError;
 use kmod::capi_fn;
 
 /// Case insensitive, length-limited string comparison
@@ -101,6 +102,9 @@ pub unsafe extern "C" fn strcpy(dest: *mut c_char, src: *const c_char) -> *mut c
 /// * `dest` - Destination string buffer
 /// * `src` - Source string to copy from
 /// * `count` - Maximum number of characters to copy
+///
+/// If `src` is shorter than `count`, the rest of `dest` is zero-padded. If it
+/// is not, `dest` is not NUL-terminated.
 #[capi_fn]
 pub unsafe extern "C" fn strncpy(dest: *mut c_char, src: *const c_char, n: usize) -> *mut c_char {
     let mut tmp = dest;
@@ -108,31 +112,85 @@ pub unsafe extern "C" fn strncpy(dest: *mut c_char, src: *const c_char, n: usize
     let mut count = n;
 
     while count > 0 {
-        let c = *s;
-        *tmp = c;
-        if c == 0 {
-            break;
+        *tmp = *s;
+        if *s != 0 {
+            s = s.add(1);
         }
         tmp = tmp.add(1);
-        s = s.add(1);
         count -= 1;
     }
 
     dest
 }
 
+/// Copy a C-string into a sized buffer
+///
+/// # Arguments
+/// * `dest` - Where to copy the string to
+/// * `src` - Where to copy the string from
+/// * `count` - Size of destination buffer
+///
+/// The result is always NUL-terminated unless `count` is 0. Returns the
+/// number of characters copied (not including the trailing NUL), or -E2BIG
+/// if `count` is 0 or `src` was truncated.
+#[capi_fn]
+pub unsafe extern "C" fn strscpy(dest: *mut c_char, src: *const c_char, count: usize) -> isize {
+    if count == 0 {
+        return -(LinuxError::E2BIG as isize);
+    }
+
+    for i in 0..count {
+        let c = *src.add(i);
+        *dest.add(i) = c;
+        if c == 0 {
+            return i as isize;
+        }
+    }
+
+    // Hit the buffer length without finding a NUL; force NUL-termination.
+    *dest.add(count - 1) = 0;
+    -(LinuxError::E2BIG as isize)
+}
+
+/// Copy a C-string into a sized buffer and zero-pad the rest of it
+///
+/// # Arguments
+/// * `dest` - Where to copy the string to
+/// * `src` - Where to copy the string from
+/// * `count` - Size of destination buffer
+///
+/// Like [`strscpy`], but the bytes of `dest` after the trailing NUL are
+/// zeroed, e.g. before the buffer is copied to userspace.
+///
+/// See <https://elixir.bootlin.com/linux/v6.6/source/lib/string_helpers.c#L843>
+#[capi_fn]
+pub unsafe extern "C" fn strscpy_pad(dest: *mut c_char, src: *const c_char, count: usize) -> isize {
+    let written = strscpy(dest, src, count);
+    if written < 0 || written as usize == count - 1 {
+        return written;
+    }
+    let written = written as usize;
+    core::ptr::write_bytes(dest.add(written + 1), 0, count - written - 1);
+    written as isize
+}
+
 /// Safe string copy with size limit
 ///
 /// # Arguments
 /// * `dest` - Destination string buffer
 /// * `src` - Source string to copy from
 /// * `size` - Maximum size of destination buffer
+///
+/// Copies at most `size - 1` bytes and NUL-terminates `dest`; nothing is
+/// written if `size` is 0. Returns `strlen(src)`, so a result `>= size`
+/// means the copy was truncated.
 #[capi_fn]
 pub unsafe extern "C" fn strlcpy(dest: *mut c_char, src: *const c_char, size: usize) -> usize {
     let len = strlen(src);
 
     if size > 0 {
-        let copy_len = if len >= size { size - 1 } else { len };
+        // Never read past the source's NUL terminator
+        let copy_len = len.min(size - 1);
         core::ptr::copy_nonoverlapping(src as *const u8, dest as *mut u8, copy_len);
         *dest.add(copy_len) = 0;
     }
@@ -225,6 +283,9 @@ pub unsafe extern "C" fn strlcat(dest: *mut c_char, src: *const c_char, size: us
 /// # Arguments
 /// * `cs` - One string
 /// * `ct` - Another string
+///
+/// Returns the difference of the first mismatching bytes, compared as
+/// `unsigned char`.
 #[capi_fn]
 pub unsafe extern "C" fn strcmp(s1: *const c_char, s2: *const c_char) -> c_int {
     let mut p1 = s1;
@@ -235,7 +296,7 @@ pub unsafe extern "C" fn strcmp(s1: *const c_char, s2: *const c_char) -> c_int {
         let c2 = *p2 as u8;
 
         if c1 != c2 {
-            return if c1 < c2 { -1 } else { 1 };
+            return c1 as c_int - c2 as c_int;
         }
 
         if c1 == 0 {
@@ -255,6 +316,9 @@ pub unsafe extern "C" fn strcmp(s1: *const c_char, s2: *const c_char) -> c_int {
 /// * `cs` - One string
 /// * `ct` - Another string
 /// * `count` - The maximum number of bytes to compare
+///
+/// Returns the difference of the first mismatching bytes, compared as
+/// `unsigned char`.
 #[capi_fn]
 pub unsafe extern "C" fn strncmp(s1: *const c_char, s2: *const c_char, n: usize) -> c_int {
     let mut count = n;
@@ -266,7 +330,7 @@ pub unsafe extern "C" fn strncmp(s1: *const c_char, s2: *const c_char, n: usize)
         let c2 = *p2 as u8;
 
         if c1 != c2 {
-            return if c1 < c2 { -1 } else { 1 };
+            return c1 as c_int - c2 as c_int;
         }
 
         if c1 == 0 {
@@ -584,7 +648,7 @@ pub unsafe extern "C" fn strsep(s: *mut *mut c_char, delim: *const c_char) -> *m
 ///
 /// Do not use memset() to access IO space, use memset_io() instead.
 #[capi_fn]
-pub unsafe extern "C" fn memset(s: *mut c_void, c: c_int, n: usize) -> *mut c_char {
+pub unsafe extern "C" fn memset(s: *mut c_void, c: c_int, n: usize) -> *mut c_void {
     let xs = s as *mut u8;
     let byte = c as u8;
 
@@ -592,7 +656,7 @@ pub unsafe extern "C" fn memset(s: *mut c_void, c: c_int, n: usize) -> *mut c_ch
         *xs.add(i) = byte;
     }
 
-    s as *mut c_char
+    s
 }
 
 /// Fill a memory area with a uint16_t
@@ -606,14 +670,14 @@ pub unsafe extern "C" fn memset(s: *mut c_void, c: c_int, n: usize) -> *mut c_ch
 /// of a byte.  Remember that @count is the number of uint16_ts to
 /// store, not the number of bytes.
 #[capi_fn]
-pub unsafe extern "C" fn memset16(s: *mut u16, c: u16, n: usize) -> *mut c_char {
+pub unsafe extern "C" fn memset16(s: *mut u16, c: u16, n: usize) -> *mut c_void {
     let xs = s;
 
     for i in 0..n {
         *xs.add(i) = c;
     }
 
-    s as *mut c_char
+    s as *mut c_void
 }
 
 /// Fill a memory area with a uint32_t
@@ -627,14 +691,14 @@ pub unsafe extern "C" fn memset16(s: *mut u16, c: u16, n: usize) -> *mut c_char
 /// of a byte.  Remember that @count is the number of uint32_ts to
 /// store, not the number of bytes.
 #[capi_fn]
-pub unsafe extern "C" fn memset32(s: *mut u32, c: u32, n: usize) -> *mut c_char {
+pub unsafe extern "C" fn memset32(s: *mut u32, c: u32, n: usize) -> *mut c_void {
     let xs = s;
 
     for i in 0..n {
         *xs.add(i) = c;
     }
 
-    s as *mut c_char
+    s as *mut c_void
 }
 
 /// Fill a memory area with a uint64_t
@@ -648,14 +712,14 @@ pub unsafe extern "C" fn memset32(s: *mut u32, c: u32, n: usize) -> *mut c_char
 /// of a byte.  Remember that @count is the number of uint64_ts to
 /// store, not the number of bytes.
 #[capi_fn]
-pub unsafe extern "C" fn memset64(s: *mut u64, c: u64, n: usize) -> *mut c_char {
+pub unsafe extern "C" fn memset64(s: *mut u64, c: u64, n: usize) -> *mut c_void {
     let xs = s;
 
     for i in 0..n {
         *xs.add(i) = c;
     }
 
-    s as *mut c_char
+    s as *mut c_void
 }
 
 /// Copy one area of memory to another
@@ -718,12 +782,38 @@ pub unsafe extern "C" fn memmove(dest: *mut c_void, src: *const c_void, n: usize
 /// * `cs` - One area of memory
 /// * `ct` - Another area of memory
 /// * `count` - The size of the area.
+///
+/// When both areas share an alignment, the aligned middle is compared a word
+/// at a time, and only the head, the tail and the first mismatching word are
+/// compared byte by byte.
 #[capi_fn]
 pub unsafe extern "C" fn memcmp(s1: *const c_void, s2: *const c_void, n: usize) -> c_int {
+    const WORD: usize = core::mem::size_of::<usize>();
     let su1 = s1 as *const u8;
     let su2 = s2 as *const u8;
 
-    for i in 0..n {
+    let mut start = 0;
+    if (su1 as usize ^ su2 as usize).is_multiple_of(WORD) {
+        let head = su1.align_offset(WORD).min(n);
+        let res = memcmp_bytes(su1, su2, 0, head);
+        if res != 0 {
+            return res;
+        }
+        start = head;
+        // A mismatching word is left to the byte loop below for the sign
+        while n - start >= WORD
+            && su1.add(start).cast::<usize>().read() == su2.add(start).cast::<usize>().read()
+        {
+            start += WORD;
+        }
+    }
+
+    memcmp_bytes(su1, su2, start, n)
+}
+
+/// Compare bytes `start..end` of two areas of memory, like [`memcmp`]
+unsafe fn memcmp_bytes(su1: *const u8, su2: *const u8, start: usize, end: usize) -> c_int {
+    for i in start..end {
         let c1 = *su1.add(i);
         let c2 = *su2.add(i);
         if c1 != c2 {
@@ -928,6 +1018,50 @@ mod tests {
             )
         };
         assert_eq!(&dest[0..5], b"hello");
+        // Longer source: exactly `n` bytes, no NUL terminator
+        assert_eq!(dest[5], 0);
+        dest.fill(0xff);
+        unsafe {
+            strncpy(
+                dest.as_mut_ptr() as *mut c_char,
+                src.as_ptr() as *const c_char,
+                5,
+            )
+        };
+        assert_eq!(&dest[..6], b"hello\xff");
+
+        // Shorter source: the tail up to `n` is zeroed
+        let src = b"hi\0";
+        dest.fill(0xff);
+        unsafe {
+            strncpy(
+                dest.as_mut_ptr() as *mut c_char,
+                src.as_ptr() as *const c_char,
+                8,
+            )
+        };
+        assert_eq!(&dest, b"hi\0\0\0\0\0\0\xff\xff");
+    }
+
+    #[test]
+    fn test_strscpy_pad() {
+        use super::{strscpy, strscpy_pad};
+        let mut dest = [0xffu8; 8];
+        let copied = unsafe { strscpy_pad(dest.as_mut_ptr() as *mut c_char, c"hi".as_ptr(), 8) };
+        assert_eq!(copied, 2);
+        assert_eq!(&dest, b"hi\0\0\0\0\0\0");
+
+        // Truncated, but still NUL-terminated
+        dest.fill(0xff);
+        let copied =
+            unsafe { strscpy_pad(dest.as_mut_ptr() as *mut c_char, c"hello world".as_ptr(), 6) };
+        assert_eq!(copied, -(axerrno::LinuxError::E2BIG as isize));
+        assert_eq!(&dest[..7], b"hello\0\xff");
+
+        dest.fill(0xff);
+        let copied = unsafe { strscpy(dest.as_mut_ptr() as *mut c_char, c"hi".as_ptr(), 8) };
+        assert_eq!(copied, 2);
+        assert_eq!(&dest[..4], b"hi\0\xff");
     }
 
     #[test]
@@ -1020,7 +1154,17 @@ mod tests {
 
         let c = b"abd\0";
         let result = unsafe { strcmp(a.as_ptr() as *const c_char, c.as_ptr() as *const c_char) };
-        assert!(result < 0);
+        assert_eq!(result, -1);
+        let result = unsafe { strcmp(c.as_ptr() as *const c_char, a.as_ptr() as *const c_char) };
+        assert_eq!(result, 1);
+
+        // Bytes above 0x7f compare as unsigned
+        let d = b"ab\xe9\0";
+        let result = unsafe { strcmp(d.as_ptr() as *const c_char, a.as_ptr() as *const c_char) };
+        assert_eq!(result, 0xe9 - b'c' as c_int);
+        let e = b"ab\0";
+        let result = unsafe { strcmp(a.as_ptr() as *const c_char, e.as_ptr() as *const c_char) };
+        assert_eq!(result, b'c' as c_int);
     }
 
     #[test]
@@ -1033,14 +1177,23 @@ mod tests {
         assert_eq!(result, 0);
         let result =
             unsafe { strncmp(a.as_ptr() as *const c_char, b.as_ptr() as *const c_char, 3) };
-        assert!(result < 0);
+        assert_eq!(result, -1);
+        let c = b"abz\0";
+        let result =
+            unsafe { strncmp(c.as_ptr() as *const c_char, a.as_ptr() as *const c_char, 3) };
+        assert_eq!(result, b'z' as c_int - b'c' as c_int);
+        let d = b"\x80\0";
+        let result =
+            unsafe { strncmp(d.as_ptr() as *const c_char, a.as_ptr() as *const c_char, 1) };
+        assert_eq!(result, 0x80 - b'a' as c_int);
     }
 
     #[test]
     fn test_memset() {
         use super::memset;
         let mut buf = [0u8; 10];
-        unsafe { memset(buf.as_mut_ptr() as *mut c_void, 0x41, 10) };
+        let ret: *mut c_void = unsafe { memset(buf.as_mut_ptr() as *mut c_void, 0x41, 10) };
+        assert_eq!(ret, buf.as_mut_ptr() as *mut c_void);
         assert_eq!(&buf, &[0x41u8; 10]);
     }
 
@@ -1072,6 +1225,48 @@ mod tests {
         assert!(result < 0);
     }
 
+    #[test]
+    fn test_memcmp_words() {
+        use super::memcmp;
+
+        #[repr(align(8))]
+        struct Buf([u8; 64]);
+
+        let a = Buf(core::array::from_fn(|i| i as u8));
+        for (off1, off2) in [(0, 0), (3, 3), (0, 1), (5, 2)] {
+            for len in [0, 1, 7, 8, 9, 16, 31, 40] {
+                let x = &a.0[off1..off1 + len];
+                // Equal areas
+                let mut b = Buf([0; 64]);
+                b.0[off2..off2 + len].copy_from_slice(x);
+                let y = &b.0[off2..off2 + len];
+                let cmp = |y: &[u8]| unsafe {
+                    memcmp(
+                        x.as_ptr() as *const c_void,
+                        y.as_ptr() as *const c_void,
+                        len,
+                    )
+                };
+                assert_eq!(cmp(y), 0);
+
+                // A mismatch at every offset, in both directions, with bytes
+                // earlier in a word that would decide a wrong word order
+                for at in 0..len {
+                    for delta in [1u8, 0x80] {
+                        b.0[off2..off2 + len].copy_from_slice(x);
+                        b.0[off2 + at] = x[at].wrapping_add(delta);
+                        if at + 1 < len {
+                            b.0[off2 + at + 1] = x[at + 1].wrapping_sub(delta);
+                        }
+                        let y = &b.0[off2..off2 + len];
+                        let expected = x.cmp(y) as c_int;
+                        assert_eq!(cmp(y), expected, "off {off1}/{off2} len {len} at {at}");
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_strlcpy() {
         use super::strlcpy;
@@ -1086,6 +1281,22 @@ mod tests {
         };
         assert_eq!(len, 5);
         assert_eq!(&dest[0..5], b"hello");
+
+        // Short source, large size: only the string and its NUL are written
+        let mut dest = [0xffu8; 16];
+        let len = unsafe { strlcpy(dest.as_mut_ptr() as *mut c_char, c"hi".as_ptr(), 16) };
+        assert_eq!(len, 2);
+        assert_eq!(&dest[..4], b"hi\0\xff");
+
+        // Truncated copy still reports the full source length
+        let len = unsafe { strlcpy(dest.as_mut_ptr() as *mut c_char, c"hello".as_ptr(), 3) };
+        assert_eq!(len, 5);
+        assert_eq!(&dest[..4], b"he\0\xff");
+
+        // Zero size writes nothing
+        let len = unsafe { strlcpy(dest.as_mut_ptr() as *mut c_char, c"xyz".as_ptr(), 0) };
+        assert_eq!(len, 3);
+        assert_eq!(&dest[..4], b"he\0\xff");
     }
 
     #[test]
@@ -1199,15 +1410,17 @@ mod tests {
     #[test]
     fn test_memmove() {
         use super::memmove;
+        // Derive both ends from one pointer so the overlapping accesses
+        // share its provenance
         let mut buf = *b"hello world";
-        unsafe {
-            memmove(
-                (buf.as_mut_ptr() as usize + 3) as *mut c_void,
-                buf.as_ptr() as *const c_void,
-                5,
-            )
-        };
-        assert_eq!(&buf[3..8], *b"hello");
+        let p = buf.as_mut_ptr();
+        unsafe { memmove(p.add(3) as *mut c_void, p as *const c_void, 5) };
+        assert_eq!(&buf, b"helhellorld");
+
+        let mut buf = *b"hello world";
+        let p = buf.as_mut_ptr();
+        unsafe { memmove(p as *mut c_void, p.add(3) as *const c_void, 5) };
+        assert_eq!(&buf, b"lo wo world");
     }
 
     #[test]
@@ -1229,13 +1442,22 @@ mod tests {
     #[test]
     fn test_strsep() {
         use super::strsep;
-        let test_data = b"a,b,c\0";
-        let mut buf = test_data.to_vec();
+        let mut buf = *b"a,b;;c\0";
         let mut str_ptr = buf.as_mut_ptr() as *mut c_char;
-        let delim = b",\0";
-        let token = unsafe { strsep(&mut str_ptr, delim.as_ptr() as *const c_char) };
-        assert!(!token.is_null());
-        assert_eq!(unsafe { *token }, 'a' as c_char);
+        let mut tokens = alloc::vec::Vec::new();
+        loop {
+            let token = unsafe { strsep(&mut str_ptr, c",;".as_ptr()) };
+            if token.is_null() {
+                break;
+            }
+            tokens.push(
+                unsafe { core::ffi::CStr::from_ptr(token) }
+                    .to_bytes()
+                    .to_vec(),
+            );
+        }
+        assert_eq!(tokens, [&b"a"[..], b"b", b"", b"c"]);
+        assert!(str_ptr.is_null());
     }
 
     #[test]
@@ -1251,7 +1473,8 @@ mod tests {
     fn test_memset16() {
         use super::memset16;
         let mut buf = [0u16; 5];
-        unsafe { memset16(buf.as_mut_ptr(), 0x1234, 5) };
+        let ret: *mut c_void = unsafe { memset16(buf.as_mut_ptr(), 0x1234, 5) };
+        assert_eq!(ret, buf.as_mut_ptr() as *mut c_void);
         assert_eq!(&buf, &[0x1234u16; 5]);
     }
 
@@ -1259,7 +1482,8 @@ mod tests {
     fn test_memset32() {
         use super::memset32;
         let mut buf = [0u32; 5];
-        unsafe { memset32(buf.as_mut_ptr(), 0x12345678, 5) };
+        let ret: *mut c_void = unsafe { memset32(buf.as_mut_ptr(), 0x12345678, 5) };
+        assert_eq!(ret, buf.as_mut_ptr() as *mut c_void);
         assert_eq!(&buf, &[0x12345678u32; 5]);
     }
 
@@ -1267,7 +1491,110 @@ mod tests {
     fn test_memset64() {
         use super::memset64;
         let mut buf = [0u64; 5];
-        unsafe { memset64(buf.as_mut_ptr(), 0x123456789abcdef0, 5) };
+        let ret: *mut c_void = unsafe { memset64(buf.as_mut_ptr(), 0x123456789abcdef0, 5) };
+        assert_eq!(ret, buf.as_mut_ptr() as *mut c_void);
         assert_eq!(&buf, &[0x123456789abcdef0u64; 5]);
     }
+
+    /// Differential tests against the slice operations of the standard
+    /// library, over small random strings so that matches are frequent
+    mod differential {
+        use alloc::vec::Vec;
+        use core::{
+            cmp::Ordering,
+            ffi::{c_char, c_int, c_void},
+        };
+
+        use crate::string::*;
+
+        const ALPHABET: &[u8] = b"abc\xe9";
+        const ROUNDS: usize = 2000;
+
+        /// xorshift64, seeded for reproducibility
+        struct Rng(u64);
+
+        impl Rng {
+            fn next(&mut self) -> u64 {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                self.0
+            }
+
+            fn below(&mut self, n: usize) -> usize {
+                (self.next() % n as u64) as usize
+            }
+
+            /// A NUL-terminated string of up to `max_len` bytes
+            fn string(&mut self, max_len: usize) -> Vec<u8> {
+                let len = self.below(max_len + 1);
+                let mut s: Vec<u8> = (0..len)
+                    .map(|_| ALPHABET[self.below(ALPHABET.len())])
+                    .collect();
+                s.push(0);
+                s
+            }
+        }
+
+        fn sign(ord: Ordering) -> c_int {
+            ord as c_int
+        }
+
+        fn ptr(s: &[u8]) -> *const c_char {
+            s.as_ptr() as *const c_char
+        }
+
+        fn offset(s: &[u8], p: *mut c_char) -> Option<usize> {
+            (!p.is_null()).then(|| p as usize - s.as_ptr() as usize)
+        }
+
+        #[test]
+        fn test_string_fns_against_std() {
+            let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+            for _ in 0..ROUNDS {
+                let a = rng.string(8);
+                let b = rng.string(8);
+                let (sa, sb) = (&a[..a.len() - 1], &b[..b.len() - 1]);
+
+                let n = rng.below(a.len().min(b.len()) + 1);
+                let ret =
+                    unsafe { memcmp(a.as_ptr() as *const c_void, b.as_ptr() as *const c_void, n) };
+                assert_eq!(
+                    ret.signum(),
+                    sign(a[..n].cmp(&b[..n])),
+                    "memcmp {a:?} {b:?} {n}"
+                );
+
+                let n = rng.below(10);
+                let ret = unsafe { strncmp(ptr(&a), ptr(&b), n) };
+                let expected = a[..n.min(a.len())].cmp(&b[..n.min(b.len())]);
+                assert_eq!(ret.signum(), sign(expected), "strncmp {a:?} {b:?} {n}");
+
+                let c = *[0].iter().chain(ALPHABET).nth(rng.below(5)).unwrap();
+                let ret = unsafe { strchr(ptr(&a), c as c_int) };
+                let expected = a.iter().position(|&x| x == c);
+                assert_eq!(offset(&a, ret), expected, "strchr {a:?} {c}");
+
+                let ret = unsafe { strstr(ptr(&a), ptr(&b)) };
+                let expected = if sb.is_empty() {
+                    Some(0)
+                } else {
+                    sa.windows(sb.len()).position(|w| w == sb)
+                };
+                assert_eq!(offset(&a, ret), expected, "strstr {a:?} {b:?}");
+
+                let ret = unsafe { strspn(ptr(&a), ptr(&b)) };
+                let expected = sa.iter().take_while(|x| sb.contains(x)).count();
+                assert_eq!(ret, expected, "strspn {a:?} {b:?}");
+
+                let ret = unsafe { strcspn(ptr(&a), ptr(&b)) };
+                let expected = sa.iter().take_while(|x| !sb.contains(x)).count();
+                assert_eq!(ret, expected, "strcspn {a:?} {b:?}");
+
+                let ret = unsafe { strpbrk(ptr(&a), ptr(&b)) };
+                let expected = sa.iter().position(|x| sb.contains(x));
+                assert_eq!(offset(&a, ret), expected, "strpbrk {a:?} {b:?}");
+            }
+        }
+    }
 }
@@ -2,7 +2,7 @@
 //!
 //! References: <https://elixir.bootlin.com/linux/v6.6/source/lib/string_helpers.c>
 
-use core::ffi::c_char;
+use core::ffi::{c_char, c_int};
 
 use kmod::capi_fn;
 
@@ -25,15 +25,13 @@ pub unsafe extern "C" fn skip_spaces(s: *const c_char) -> *mut c_char {
 /// character in @s.
 #[capi_fn]
 pub unsafe extern "C" fn strstrip(s: *mut c_char) -> *mut c_char {
-    let size = crate::string::strlen(s);
-    if size == 0 {
-        return s;
+    // Walk an index back rather than a pointer, which would step before `s`
+    // on an all-whitespace string
+    let mut end = crate::string::strlen(s);
+    while end > 0 && (*s.add(end - 1) as u8).is_ascii_whitespace() {
+        end -= 1;
     }
-    let mut end = s.add(size - 1);
-    while end >= s && (*end as u8).is_ascii_whitespace() {
-        end = end.sub(1);
-    }
-    *end.add(1) = 0;
+    *s.add(end) = 0;
     skip_spaces(s)
 }
 
@@ -42,6 +40,104 @@ pub unsafe extern "C" fn strim(s: *mut c_char) -> *mut c_char {
     strstrip(s)
 }
 
+/// Units for [`string_get_size`]: powers of 1000 ("kB", "MB", ...)
+pub const STRING_UNITS_10: c_int = 0;
+/// Units for [`string_get_size`]: powers of 1024 ("KiB", "MiB", ...)
+pub const STRING_UNITS_2: c_int = 1;
+
+/// Get the size in the specified units.
+///
+/// @size: The size to be converted in blocks
+/// @blk_size: Size of the block (use 1 for size in bytes)
+/// @units: units to use (powers of 1000 or 1024)
+/// @buf: buffer to format to
+/// @len: length of buffer
+///
+/// This function returns a string formatted to 3 significant figures
+/// giving the size in the required units. @buf should have room for
+/// at least 9 bytes and will always be zero terminated.
+#[capi_fn]
+pub unsafe extern "C" fn string_get_size(
+    mut size: u64,
+    mut blk_size: u64,
+    units: c_int,
+    buf: *mut c_char,
+    len: c_int,
+) {
+    const UNITS_10: [&str; 9] = ["B", "kB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB"];
+    const UNITS_2: [&str; 9] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
+    const ROUNDING: [u32; 3] = [500, 50, 5];
+
+    let (units_str, divisor) = if units == STRING_UNITS_2 {
+        (&UNITS_2, 1024)
+    } else {
+        (&UNITS_10, 1000)
+    };
+    let mut i = 0;
+    let mut remainder: u32 = 0;
+    let mut tmp = alloc::string::String::new();
+
+    if blk_size == 0 {
+        size = 0;
+    }
+    if size != 0 {
+        // Napier's algorithm: reduce both coefficients to just under 32 bits
+        // so that multiplying them won't overflow 64 bits.
+        while blk_size >> 32 != 0 {
+            blk_size /= divisor;
+            i += 1;
+        }
+        while size >> 32 != 0 {
+            size /= divisor;
+            i += 1;
+        }
+        size *= blk_size;
+
+        // and logarithmically reduce it until it's just under the divisor
+        while size >= divisor {
+            remainder = (size % divisor) as u32;
+            size /= divisor;
+            i += 1;
+        }
+
+        // work out in j how many digits of precision we need from the remainder
+        let mut sf_cap = size as u32;
+        let mut j = 0;
+        while sf_cap * 10 < 1000 {
+            sf_cap *= 10;
+            j += 1;
+        }
+
+        if units == STRING_UNITS_2 {
+            // express the remainder as a decimal of 1 << 10
+            remainder *= 1000;
+            remainder >>= 10;
+        }
+
+        // add a 5 to the digit below what will be printed to ensure
+        // an arithmetical round up and carry it through to size
+        remainder += ROUNDING[j];
+        if remainder >= 1000 {
+            remainder -= 1000;
+            size += 1;
+        }
+
+        if j != 0 {
+            tmp = alloc::format!(".{:03}", remainder);
+            tmp.truncate(j + 1);
+        }
+    }
+
+    let unit = units_str.get(i).copied().unwrap_or("UNK");
+    let s = alloc::format!("{}{} {}", size as u32, tmp, unit);
+    if len <= 0 {
+        return;
+    }
+    let n = s.len().min(len as usize - 1);
+    core::ptr::copy_nonoverlapping(s.as_ptr(), buf as *mut u8, n);
+    *buf.add(n) = 0;
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::ffi::CString;
@@ -64,4 +160,50 @@ mod tests {
         let result_str = unsafe { CStr::from_ptr(result) };
         assert_eq!(result_str.to_str().unwrap(), "Hello, World!");
     }
+
+    #[test]
+    fn test_strstrip_all_whitespace() {
+        let mut buf = *b"  \t \n\0";
+        let result = unsafe { strstrip(buf.as_mut_ptr() as *mut c_char) };
+        assert_eq!(unsafe { CStr::from_ptr(result) }, c"");
+        assert_eq!(buf[0], 0);
+
+        let mut buf = *b"\0";
+        let result = unsafe { strim(buf.as_mut_ptr() as *mut c_char) };
+        assert_eq!(result, buf.as_mut_ptr() as *mut c_char);
+        assert_eq!(unsafe { CStr::from_ptr(result) }, c"");
+    }
+
+    fn get_size(size: u64, blk_size: u64, units: c_int) -> alloc::string::String {
+        let mut buf = [0 as c_char; 16];
+        unsafe { string_get_size(size, blk_size, units, buf.as_mut_ptr(), buf.len() as c_int) };
+        let s = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        s.to_str().unwrap().into()
+    }
+
+    #[test]
+    fn test_string_get_size() {
+        assert_eq!(get_size(1024, 1, STRING_UNITS_2), "1.00 KiB");
+        assert_eq!(get_size(1500, 1, STRING_UNITS_10), "1.50 kB");
+        assert_eq!(get_size(0, 512, STRING_UNITS_2), "0 B");
+        assert_eq!(get_size(999, 1, STRING_UNITS_10), "999 B");
+        assert_eq!(get_size(2048, 512, STRING_UNITS_2), "1.00 MiB");
+        assert_eq!(get_size(123456789, 1, STRING_UNITS_10), "123 MB");
+    }
+
+    #[test]
+    fn test_string_get_size_truncates() {
+        let mut buf = [0x7f as c_char; 4];
+        unsafe {
+            string_get_size(
+                1024,
+                1,
+                STRING_UNITS_2,
+                buf.as_mut_ptr(),
+                buf.len() as c_int,
+            )
+        };
+        let s = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(s, c"1.0");
+    }
 }
@@ -0,0 +1,124 @@
+//! Hashing of names, as used by the dcache and other lookup tables
+//!
+//! Matches the 64-bit word-at-a-time algorithm of Linux v6.6
+//! (`CONFIG_DCACHE_WORD_ACCESS` on a little-endian machine), so hashes agree
+//! with the ones an x86_64 or arm64 kernel of that version computes.
+//!
+//! References: <https://elixir.bootlin.com/linux/v6.6/source/fs/namei.c#L2113>
+
+use core::ffi::{c_char, c_uint, c_void};
+
+use kmod::capi_fn;
+
+const GOLDEN_RATIO_64: u64 = 0x61c8_8646_80b5_83eb;
+
+const WORD: usize = size_of::<u64>();
+
+/// Mix one word `a` into the state `(x, y)`
+#[inline]
+fn hash_mix(x: &mut u64, y: &mut u64, a: u64) {
+    *x ^= a;
+    *y ^= *x;
+    *x = x.rotate_left(12);
+    *x = x.wrapping_add(*y);
+    *y = y.rotate_left(45);
+    *y = y.wrapping_mul(9);
+}
+
+/// Fold the state into the final 32-bit hash
+#[inline]
+fn fold_hash(x: u64, y: u64) -> u32 {
+    let y = (y ^ x.wrapping_mul(GOLDEN_RATIO_64)).wrapping_mul(GOLDEN_RATIO_64);
+    (y >> 32) as u32
+}
+
+/// Hash `name` with `salt`, reading the final partial word zero-padded
+fn hash_bytes(salt: usize, name: &[u8]) -> u32 {
+    let (mut x, mut y) = (0u64, salt as u64);
+    let mut words = name.chunks_exact(WORD);
+    for word in &mut words {
+        hash_mix(&mut x, &mut y, u64::from_le_bytes(word.try_into().unwrap()));
+    }
+    let rest = words.remainder();
+    if !rest.is_empty() {
+        let mut last = [0u8; WORD];
+        last[..rest.len()].copy_from_slice(rest);
+        x ^= u64::from_le_bytes(last);
+    }
+    fold_hash(x, y)
+}
+
+/// full_name_hash - Hash a string of known length
+/// # Arguments
+/// - salt: The hash salt, usually the parent dentry
+/// - name: The string to hash
+/// - len: The length of @name
+/// # Returns
+/// The 32-bit hash of @name
+#[capi_fn]
+pub unsafe extern "C" fn full_name_hash(
+    salt: *const c_void,
+    name: *const c_char,
+    len: c_uint,
+) -> c_uint {
+    if len == 0 {
+        return hash_bytes(salt as usize, &[]);
+    }
+    let name = core::slice::from_raw_parts(name as *const u8, len as usize);
+    hash_bytes(salt as usize, name)
+}
+
+/// hashlen_string - Hash a NUL-terminated string
+/// # Arguments
+/// - salt: The hash salt, usually the parent dentry
+/// - name: The string to hash
+/// # Returns
+/// The hash of @name in the low 32 bits and its length in the high 32 bits,
+/// like `hashlen_create()`
+#[capi_fn]
+pub unsafe extern "C" fn hashlen_string(salt: *const c_void, name: *const c_char) -> u64 {
+    let name = core::ffi::CStr::from_ptr(name).to_bytes();
+    let hash = hash_bytes(salt as usize, name);
+    ((name.len() as u64) << 32) | hash as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::null;
+
+    use super::*;
+
+    fn hash(salt: usize, name: &core::ffi::CStr) -> u32 {
+        let len = name.to_bytes().len() as c_uint;
+        unsafe { full_name_hash(salt as *const c_void, name.as_ptr(), len) }
+    }
+
+    #[test]
+    fn test_full_name_hash() {
+        // Computed with the v6.6 fs/namei.c implementation
+        assert_eq!(hash(0, c""), 0);
+        assert_eq!(hash(0, c"a"), 0x98d5_1a30);
+        assert_eq!(hash(0, c"hello"), 0xc495_851d);
+        assert_eq!(hash(0, c"12345678"), 0xc953_179b);
+        assert_eq!(hash(0, c"hello world!"), 0x8bc8_062a);
+        assert_eq!(hash(0, c"lost+found"), 0xf20a_a046);
+
+        assert_eq!(hash(0x1234, c""), 0xf62c_3b5e);
+        assert_eq!(hash(0x1234, c"hello"), 0xed76_4c09);
+        assert_eq!(hash(0x1234, c"12345678"), 0xd9bc_da65);
+        assert_eq!(hash(0x1234, c"lost+found"), 0xdd82_cb3e);
+
+        // Only the first `len` bytes count
+        let prefix = unsafe { full_name_hash(null(), c"hello world!".as_ptr(), 5) };
+        assert_eq!(prefix, hash(0, c"hello"));
+    }
+
+    #[test]
+    fn test_hashlen_string() {
+        for name in [c"", c"a", c"12345678", c"lost+found"] {
+            let hashlen = unsafe { hashlen_string(null(), name.as_ptr()) };
+            assert_eq!(hashlen as u32, hash(0, name));
+            assert_eq!((hashlen >> 32) as usize, name.to_bytes().len());
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! A small printf-style formatter for kernel modules
+//!
+//! Supports the conversions commonly used by module code: `%d %i %u %x %X %s
+//! %p %c %%`, the `-` and `0` flags, a field width and the `hh h l ll z`
+//! length modifiers.
+//!
+//! References: <https://elixir.bootlin.com/linux/v6.6/source/lib/vsprintf.c>
+
+use core::ffi::{CStr, c_char, c_int, c_void};
+
+use kmod::capi_fn;
+
+/// An argument for [`kvsnprintf`]
+#[derive(Debug, Clone, Copy)]
+pub enum FmtArg<'a> {
+    Int(i64),
+    UInt(u64),
+    Str(&'a str),
+    Char(u8),
+    Ptr(*const c_void),
+}
+
+/// An argument as the formatter sees it. `Raw` is only built by the C
+/// entry points, which take on the caller's promise that a `%s` argument is
+/// a valid C string.
+#[derive(Clone, Copy)]
+enum Arg<'a> {
+    Int(i64),
+    UInt(u64),
+    Str(&'a str),
+    Char(u8),
+    Ptr(*const c_void),
+    /// An untyped argument passed from C, interpreted according to the
+    /// conversion and length modifier that consumes it
+    Raw(usize),
+}
+
+impl<'a> From<FmtArg<'a>> for Arg<'a> {
+    fn from(arg: FmtArg<'a>) -> Self {
+        match arg {
+            FmtArg::Int(v) => Arg::Int(v),
+            FmtArg::UInt(v) => Arg::UInt(v),
+            FmtArg::Str(s) => Arg::Str(s),
+            FmtArg::Char(c) => Arg::Char(c),
+            FmtArg::Ptr(p) => Arg::Ptr(p),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Length {
+    Char,
+    Short,
+    Int,
+    Long,
+}
+
+/// Writes into a fixed buffer, counting what would have been written past
+/// its end.
+struct Output<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Output<'_> {
+    fn push(&mut self, b: u8) {
+        if let Some(slot) = self.buf.get_mut(self.len) {
+            *slot = b;
+        }
+        self.len += 1;
+    }
+
+    fn pad(&mut self, s: &[u8], width: usize, left: bool, zero: bool) {
+        let fill = width.saturating_sub(s.len());
+        if left {
+            s.iter().for_each(|&b| self.push(b));
+            (0..fill).for_each(|_| self.push(b' '));
+        } else if zero {
+            // Keep the sign in front of the zeros
+            let (sign, digits) = match s.first() {
+                Some(b'-') => (&s[..1], &s[1..]),
+                _ => (&s[..0], s),
+            };
+            sign.iter().for_each(|&b| self.push(b));
+            (0..fill).for_each(|_| self.push(b'0'));
+            digits.iter().for_each(|&b| self.push(b));
+        } else {
+            (0..fill).for_each(|_| self.push(b' '));
+            s.iter().for_each(|&b| self.push(b));
+        }
+    }
+}
+
+/// Format `args` according to `fmt` into `buf`.
+///
+/// The output is always NUL-terminated if `buf` is not empty. Returns the
+/// number of characters (excluding the NUL) which would have been generated
+/// for a large enough buffer, like `vsnprintf`. A conversion with no
+/// argument left or with a mismatched argument prints nothing.
+pub fn kvsnprintf(buf: &mut [u8], fmt: &str, args: &[FmtArg]) -> usize {
+    // Safety: typed arguments never reach the C string dereference
+    unsafe { kvsnprintf_bytes(buf, fmt.as_bytes(), args.iter().map(|&arg| arg.into())) }
+}
+
+/// # Safety
+/// Every [`Arg::Raw`] consumed by `%s` must be 0 or point to a NUL-terminated
+/// string.
+unsafe fn kvsnprintf_bytes<'a>(
+    buf: &mut [u8],
+    fmt: &[u8],
+    mut args: impl Iterator<Item = Arg<'a>>,
+) -> usize {
+    let cap = buf.len();
+    let mut out = Output {
+        buf: match cap {
+            0 => &mut [],
+            _ => &mut buf[..cap - 1],
+        },
+        len: 0,
+    };
+    let mut i = 0;
+    while i < fmt.len() {
+        let c = fmt[i];
+        i += 1;
+        if c != b'%' {
+            out.push(c);
+            continue;
+        }
+
+        let (mut left, mut zero) = (false, false);
+        while let Some(&flag) = fmt.get(i) {
+            match flag {
+                b'-' => left = true,
+                b'0' => zero = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        let mut width = 0;
+        while let Some(d) = fmt.get(i).filter(|d| d.is_ascii_digit()) {
+            width = width * 10 + (d - b'0') as usize;
+            i += 1;
+        }
+        let mut length = Length::Int;
+        while let Some(&m) = fmt.get(i) {
+            length = match (m, length) {
+                (b'h', Length::Short) => Length::Char,
+                (b'h', _) => Length::Short,
+                (b'l' | b'z', _) => Length::Long,
+                _ => break,
+            };
+            i += 1;
+        }
+        let Some(&conv) = fmt.get(i) else {
+            break;
+        };
+        i += 1;
+
+        let mut num = [0u8; 24];
+        match conv {
+            b'%' => out.push(b'%'),
+            b'd' | b'i' => {
+                if let Some(v) = args.next().and_then(|a| signed(a, length)) {
+                    let s = fmt_num(&mut num, v.unsigned_abs(), 10, false, v < 0);
+                    out.pad(s, width, left, zero);
+                }
+            }
+            b'u' | b'x' | b'X' => {
+                if let Some(v) = args.next().and_then(|a| unsigned(a, length)) {
+                    let base = if conv == b'u' { 10 } else { 16 };
+                    let s = fmt_num(&mut num, v, base, conv == b'X', false);
+                    out.pad(s, width, left, zero);
+                }
+            }
+            b'p' => {
+                let v = match args.next() {
+                    Some(Arg::Ptr(p)) => Some(p as usize),
+                    Some(Arg::Raw(v)) => Some(v),
+                    _ => None,
+                };
+                if let Some(v) = v {
+                    let s = fmt_num(&mut num, v as u64, 16, false, false);
+                    let width = width.max(2 * core::mem::size_of::<usize>());
+                    out.pad(s, width, left, !left);
+                }
+            }
+            b'c' => {
+                let ch = match args.next() {
+                    Some(Arg::Char(c)) => Some(c),
+                    Some(Arg::Raw(v)) => Some(v as u8),
+                    _ => None,
+                };
+                if let Some(ch) = ch {
+                    out.pad(&[ch], width, left, false);
+                }
+            }
+            b's' => {
+                let s: Option<&[u8]> = match args.next() {
+                    Some(Arg::Str(s)) => Some(s.as_bytes()),
+                    Some(Arg::Raw(0)) => Some(b"(null)"),
+                    Some(Arg::Raw(p)) => Some(unsafe { CStr::from_ptr(p as _) }.to_bytes()),
+                    _ => None,
+                };
+                if let Some(s) = s {
+                    out.pad(s, width, left, false);
+                }
+            }
+            _ => {
+                // Unknown conversion: print it verbatim
+                out.push(b'%');
+                out.push(conv);
+            }
+        }
+    }
+
+    let len = out.len;
+    if cap != 0 {
+        buf[len.min(cap - 1)] = 0;
+    }
+    len
+}
+
+fn signed(arg: Arg, length: Length) -> Option<i64> {
+    let v = match arg {
+        Arg::Int(v) => return Some(v),
+        Arg::UInt(v) => return Some(v as i64),
+        Arg::Char(v) => return Some(v as i64),
+        Arg::Raw(v) => v,
+        Arg::Str(_) | Arg::Ptr(_) => return None,
+    };
+    Some(match length {
+        Length::Char => v as i8 as i64,
+        Length::Short => v as i16 as i64,
+        Length::Int => v as i32 as i64,
+        Length::Long => v as i64,
+    })
+}
+
+fn unsigned(arg: Arg, length: Length) -> Option<u64> {
+    let v = match arg {
+        Arg::Int(v) => v as u64,
+        Arg::UInt(v) => v,
+        Arg::Char(v) => v as u64,
+        Arg::Raw(v) => v as u64,
+        Arg::Str(_) | Arg::Ptr(_) => return None,
+    };
+    Some(match length {
+        Length::Char => v as u8 as u64,
+        Length::Short => v as u16 as u64,
+        Length::Int => v as u32 as u64,
+        Length::Long => v,
+    })
+}
+
+fn fmt_num(buf: &mut [u8; 24], mut v: u64, base: u64, upper: bool, negative: bool) -> &[u8] {
+    let digits: &[u8; 16] = if upper {
+        b"0123456789ABCDEF"
+    } else {
+        b"0123456789abcdef"
+    };
+    let mut pos = buf.len();
+    loop {
+        pos -= 1;
+        buf[pos] = digits[(v % base) as usize];
+        v /= base;
+        if v == 0 {
+            break;
+        }
+    }
+    if negative {
+        pos -= 1;
+        buf[pos] = b'-';
+    }
+    &buf[pos..]
+}
+
+/// snprintf - Format a string and place it in a buffer
+/// # Arguments
+/// - buf: The buffer to place the result into
+/// - size: The size of the buffer, including the trailing null space
+/// - fmt: The format string to use
+/// - a0..a5: Arguments for the format string
+/// # Returns
+/// The number of characters which would be generated for the given input,
+/// excluding the trailing null.
+///
+/// Stable Rust cannot define C-variadic functions, so this takes a fixed
+/// number of integer-sized arguments instead. For integer and pointer
+/// arguments the variadic calling convention of the supported architectures
+/// passes them the same way, so C callers with up to six arguments work
+/// unchanged. Floating point arguments are not supported.
+#[capi_fn]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn snprintf(
+    buf: *mut c_char,
+    size: usize,
+    fmt: *const c_char,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+    a5: usize,
+) -> c_int {
+    let fmt = CStr::from_ptr(fmt).to_bytes();
+    let args = [a0, a1, a2, a3, a4, a5].map(Arg::Raw);
+    let buf: &mut [u8] = if buf.is_null() || size == 0 {
+        &mut []
+    } else {
+        core::slice::from_raw_parts_mut(buf as *mut u8, size)
+    };
+    kvsnprintf_bytes(buf, fmt, args.into_iter()) as c_int
+}
+
+/// scnprintf - Format a string and place it in a buffer
+/// # Arguments
+/// - buf: The buffer to place the result into
+/// - size: The size of the buffer, including the trailing null space
+/// - fmt: The format string to use
+/// - a0..a5: Arguments for the format string, see [`snprintf`]
+/// # Returns
+/// The number of characters written into @buf not including the trailing
+/// '\0'. If @size is == 0 the function returns 0.
+///
+/// See <https://elixir.bootlin.com/linux/v6.6/source/lib/vsprintf.c#L2966>
+#[capi_fn]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn scnprintf(
+    buf: *mut c_char,
+    size: usize,
+    fmt: *const c_char,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+    a5: usize,
+) -> c_int {
+    let len = snprintf(buf, size, fmt, a0, a1, a2, a3, a4, a5) as usize;
+    if len < size {
+        len as c_int
+    } else {
+        size.saturating_sub(1) as c_int
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(fmt: &str, args: &[FmtArg]) -> alloc::string::String {
+        let mut buf = [0u8; 64];
+        let len = kvsnprintf(&mut buf, fmt, args);
+        let s = core::str::from_utf8(&buf[..len]).unwrap();
+        s.into()
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(format("%d", &[FmtArg::Int(-42)]), "-42");
+        assert_eq!(format("%i|%u", &[FmtArg::Int(7), FmtArg::UInt(7)]), "7|7");
+        assert_eq!(
+            format("%x %X", &[FmtArg::UInt(0xbeef), FmtArg::UInt(0xbeef)]),
+            "beef BEEF"
+        );
+        assert_eq!(
+            format("%5d|%-5d|", &[FmtArg::Int(42), FmtArg::Int(42)]),
+            "   42|42   |"
+        );
+        assert_eq!(
+            format("%05d %08x", &[FmtArg::Int(-42), FmtArg::UInt(0xff)]),
+            "-0042 000000ff"
+        );
+        assert_eq!(
+            format("%lu", &[FmtArg::UInt(u64::MAX)]),
+            "18446744073709551615"
+        );
+    }
+
+    #[test]
+    fn test_strings_and_chars() {
+        assert_eq!(format("%s!", &[FmtArg::Str("hi")]), "hi!");
+        assert_eq!(
+            format("[%4s][%-4s]", &[FmtArg::Str("a"), FmtArg::Str("b")]),
+            "[   a][b   ]"
+        );
+        assert_eq!(
+            format("%c%c", &[FmtArg::Char(b'o'), FmtArg::Char(b'k')]),
+            "ok"
+        );
+        assert_eq!(format("100%%", &[]), "100%");
+    }
+
+    #[test]
+    fn test_str_needs_str_arg() {
+        // Safe callers have no way to hand %s an address to dereference
+        let p = core::ptr::dangling::<c_void>();
+        assert_eq!(format("[%s]", &[FmtArg::Ptr(p)]), "[]");
+        assert_eq!(format("[%s]", &[FmtArg::UInt(1)]), "[]");
+        assert_eq!(format("[%s]", &[FmtArg::Int(0)]), "[]");
+    }
+
+    #[test]
+    fn test_pointer() {
+        let p = 0x1234usize as *const c_void;
+        let expected = alloc::format!("{:01$x}", 0x1234, 2 * core::mem::size_of::<usize>());
+        assert_eq!(format("%p", &[FmtArg::Ptr(p)]), expected);
+    }
+
+    #[test]
+    fn test_truncation() {
+        let mut buf = [0xffu8; 4];
+        let len = kvsnprintf(&mut buf, "%d", &[FmtArg::Int(123456)]);
+        assert_eq!(len, 6);
+        assert_eq!(&buf, b"123\0");
+        assert_eq!(kvsnprintf(&mut [], "abc", &[]), 3);
+    }
+
+    #[test]
+    fn test_snprintf_raw_args() {
+        let mut buf = [0 as c_char; 64];
+        let len = unsafe {
+            snprintf(
+                buf.as_mut_ptr(),
+                buf.len(),
+                c"%s=%d %hhu %lx %c".as_ptr(),
+                c"val".as_ptr() as usize,
+                -5i32 as u32 as usize,
+                0x1ff,
+                0xdead_beef_cafe,
+                b'z' as usize,
+                0,
+            )
+        };
+        let s = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(s, c"val=-5 255 deadbeefcafe z");
+        assert_eq!(len as usize, s.to_bytes().len());
+
+        let len = unsafe {
+            snprintf(
+                buf.as_mut_ptr(),
+                buf.len(),
+                c"%s".as_ptr(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+            )
+        };
+        assert_eq!(len, 6);
+        assert_eq!(unsafe { CStr::from_ptr(buf.as_ptr()) }, c"(null)");
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn print_truncated(
+        f: unsafe extern "C" fn(
+            *mut c_char,
+            usize,
+            *const c_char,
+            usize,
+            usize,
+            usize,
+            usize,
+            usize,
+            usize,
+        ) -> c_int,
+        buf: &mut [c_char],
+    ) -> c_int {
+        let name = c"truncated".as_ptr() as usize;
+        unsafe {
+            f(
+                buf.as_mut_ptr(),
+                buf.len(),
+                c"%s-%d".as_ptr(),
+                name,
+                42,
+                0,
+                0,
+                0,
+                0,
+            )
+        }
+    }
+
+    #[test]
+    fn test_scnprintf_truncation() {
+        // snprintf returns what would have been written, scnprintf what was
+        let mut buf = [0 as c_char; 8];
+        assert_eq!(print_truncated(snprintf, &mut buf), 12);
+        assert_eq!(print_truncated(scnprintf, &mut buf), 7);
+        assert_eq!(unsafe { CStr::from_ptr(buf.as_ptr()) }, c"truncat");
+
+        // Both agree when the output fits
+        let mut buf = [0 as c_char; 16];
+        assert_eq!(print_truncated(snprintf, &mut buf), 12);
+        assert_eq!(print_truncated(scnprintf, &mut buf), 12);
+
+        assert_eq!(print_truncated(snprintf, &mut []), 12);
+        assert_eq!(print_truncated(scnprintf, &mut []), 0);
+    }
+}
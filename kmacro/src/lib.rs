@@ -1,4 +1,6 @@
 //! Macro definitions for kernel module functions.
+use std::ffi::CString;
+
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::{
@@ -49,8 +51,35 @@ pub fn exit_fn(_attr: TokenStream, item: TokenStream) -> TokenStream {
     .into()
 }
 
+/// Attribute macro to place a function or static in the given section.
+/// Statics are also marked `used` so they are kept even if unreferenced.
+/// # Example:
+/// ```ignore
+/// #[section(".ref.text")]
+/// fn helper() { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn section(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let section_name = parse_macro_input!(attr as LitStr);
+    match parse_macro_input!(item as syn::Item) {
+        syn::Item::Fn(func) => quote! {
+            #[unsafe(link_section = #section_name)]
+            #func
+        },
+        syn::Item::Static(data) => quote! {
+            #[used]
+            #[unsafe(link_section = #section_name)]
+            #data
+        },
+        item => syn::Error::new_spanned(item, "#[section] only supports functions and statics")
+            .to_compile_error(),
+    }
+    .into()
+}
+
 /// Attribute macro to mark a C API function. It places the function in the
-/// `.text` section and applies `no_mangle`.
+/// `.text` section, applies `no_mangle` and records it in the `kmod_symtab`
+/// section of exported symbols.
 /// # Example:
 /// ```ignore
 /// #[capi_fn]
@@ -61,6 +90,7 @@ pub fn capi_fn(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let func = parse_macro_input!(item as syn::ItemFn);
     let func_name = &func.sig.ident;
     let anchor_name = format_ident!("__kmod_export_anchor_{}", func_name);
+    let symbol = export_symbol(func_name, quote! { #func_name as *const () });
     let section_name = format!(".kmod_export.{}", func_name);
     let unsafety = &func.sig.unsafety;
     let abi = &func.sig.abi;
@@ -93,12 +123,18 @@ pub fn capi_fn(_attr: TokenStream, item: TokenStream) -> TokenStream {
         #[unsafe(link_section = #section_name)]
         #[allow(non_upper_case_globals)]
         static #anchor_name: #fn_ptr_type = #func_name;
+
+        #symbol
     }
     .into()
 }
 
 /// Attribute macro to mark a C static data item. It places the item in the
-/// `.data` section and applies `no_mangle` and `used`.
+/// `.data` section, applies `no_mangle` and `used` and records it in the
+/// `kmod_symtab` section of exported symbols.
+///
+/// No alignment attribute is emitted: a `static` is always aligned to the
+/// natural alignment of its type, even when placed in a custom section.
 /// # Example:
 /// ```ignore
 /// #[cdata]
@@ -107,20 +143,94 @@ pub fn capi_fn(_attr: TokenStream, item: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn cdata(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let data = parse_macro_input!(item as syn::ItemStatic);
+    let data_name = &data.ident;
+    let symbol = export_symbol(data_name, quote! { &raw const #data_name as *const () });
     quote! {
         #[unsafe(no_mangle)]
         #[used]
         #[unsafe(link_section = ".kmod_export.data")]
         #data
+
+        #symbol
     }
     .into()
 }
 
+/// Emit a `KernelSymbol` for `name` into the `kmod_symtab` section. The section
+/// name is a C identifier so the linker provides `__start_kmod_symtab` and
+/// `__stop_kmod_symtab` to walk it.
+fn export_symbol(name: &Ident, addr: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let symbol_name = format_ident!("__kmod_symbol_{}", name);
+    let name_str = name.to_string();
+    quote! {
+        #[used]
+        #[unsafe(link_section = "kmod_symtab")]
+        #[allow(non_upper_case_globals)]
+        static #symbol_name: kmod_tools::KernelSymbol = kmod_tools::KernelSymbol::new(#name_str, #addr);
+    }
+}
+
 struct ModuleArgs {
     name: Option<LitStr>,
     version: Option<LitStr>,
     license: Option<LitStr>,
     description: Option<LitStr>,
+    params: Vec<ModuleParam>,
+}
+
+/// A module parameter declared in `module!`:
+/// `name: type { default: expr, permissions: expr, description: "..." }`.
+struct ModuleParam {
+    name: Ident,
+    ty: Ident,
+    default: syn::Expr,
+    permissions: Option<syn::Expr>,
+    description: LitStr,
+}
+
+impl Parse for ModuleParam {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty: Ident = input.parse()?;
+        let content;
+        syn::braced!(content in input);
+
+        let mut default = None;
+        let mut permissions = None;
+        let mut description = None;
+        while !content.is_empty() {
+            let key: Ident = content.parse()?;
+            content.parse::<Token![:]>()?;
+
+            match key.to_string().as_str() {
+                "default" => default = Some(content.parse()?),
+                "permissions" => permissions = Some(content.parse()?),
+                "description" => description = Some(content.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("Unknown parameter field: {}", key),
+                    ));
+                }
+            }
+
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+        }
+
+        let default = default.ok_or_else(|| syn::Error::new(name.span(), "default is required"))?;
+        let description =
+            description.ok_or_else(|| syn::Error::new(name.span(), "description is required"))?;
+        Ok(ModuleParam {
+            name,
+            ty,
+            default,
+            permissions,
+            description,
+        })
+    }
 }
 
 impl Parse for ModuleArgs {
@@ -129,6 +239,7 @@ impl Parse for ModuleArgs {
         let mut version = None;
         let mut license = None;
         let mut description = None;
+        let mut params = Vec::new();
         while !input.is_empty() {
             let key: Ident = input.parse()?;
             input.parse::<Token![:]>()?;
@@ -150,6 +261,14 @@ impl Parse for ModuleArgs {
                     let value: LitStr = input.parse()?;
                     description = Some(value);
                 }
+                "params" => {
+                    let content;
+                    syn::braced!(content in input);
+                    params = content
+                        .parse_terminated(ModuleParam::parse, Token![,])?
+                        .into_iter()
+                        .collect();
+                }
                 _ => {
                     return Err(syn::Error::new(
                         key.span(),
@@ -168,10 +287,89 @@ impl Parse for ModuleArgs {
             version,
             license,
             description,
+            params,
         })
     }
 }
 
+/// The C type backing a parameter of the given kernel parameter type, which
+/// is handled by the matching `param_ops_<type>`.
+fn param_ctype(ty: &Ident) -> Option<proc_macro2::TokenStream> {
+    let ctype = match ty.to_string().as_str() {
+        "byte" => quote! { core::ffi::c_uchar },
+        "short" => quote! { core::ffi::c_short },
+        "ushort" => quote! { core::ffi::c_ushort },
+        "int" | "bint" => quote! { core::ffi::c_int },
+        "uint" | "hexint" => quote! { core::ffi::c_uint },
+        "long" => quote! { core::ffi::c_long },
+        "ulong" => quote! { core::ffi::c_ulong },
+        "ullong" => quote! { core::ffi::c_ulonglong },
+        "bool" | "invbool" => quote! { bool },
+        _ => return None,
+    };
+    Some(ctype)
+}
+
+/// Build a NUL-terminated `key=value` entry for the `.modinfo` section.
+fn modinfo_entry(key: &str, value: &str) -> Vec<u8> {
+    let mut entry = format!("{}={}", key, value).into_bytes();
+    entry.push(0);
+    entry
+}
+
+/// Generate the storage, the `__param` entry and the `parmtype=`/`parm=`
+/// modinfo strings for one parameter, so they always agree on its name.
+///
+/// See <https://elixir.bootlin.com/linux/v6.6/source/include/linux/moduleparam.h#L126>
+fn module_param(param: &ModuleParam) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &param.name;
+    let ty = &param.ty;
+    let ctype = param_ctype(ty)
+        .ok_or_else(|| syn::Error::new(ty.span(), format!("Unsupported parameter type: {}", ty)))?;
+    let default = &param.default;
+    let permissions = match &param.permissions {
+        Some(permissions) => quote! { #permissions },
+        None => quote! { 0 },
+    };
+    let ops = format_ident!("param_ops_{}", ty);
+    let c_name = CString::new(name.to_string()).unwrap();
+    let c_name = syn::LitCStr::new(&c_name, name.span());
+
+    let parmtype = modinfo_entry("parmtype", &format!("{}:{}", name, ty));
+    let parm = modinfo_entry("parm", &format!("{}:{}", name, param.description.value()));
+    let parmtype_len = parmtype.len();
+    let parm_len = parm.len();
+
+    Ok(quote! {
+        #[allow(non_upper_case_globals)]
+        static mut #name: #ctype = #default;
+
+        const _: () = {
+            unsafe extern "C" {
+                static #ops: kmod_tools::kernel_param_ops;
+            }
+            #[used]
+            #[unsafe(link_section = "__param")]
+            static PARAM: kmod_tools::KernelParam = kmod_tools::KernelParam::new(
+                #c_name,
+                unsafe { &#ops },
+                &raw mut #name as *mut core::ffi::c_void,
+                #permissions,
+                -1,
+            );
+            #[used]
+            #[unsafe(link_section = ".modinfo")]
+            static PARMTYPE: [u8; #parmtype_len] = [#(#parmtype),*];
+            #[used]
+            #[unsafe(link_section = ".modinfo")]
+            static PARM: [u8; #parm_len] = [#(#parm),*];
+        };
+    })
+}
+
+/// Size of the `name` array of `struct module`, including the NUL
+const MODULE_NAME_LEN: usize = 56;
+
 /// Macro to declare module metadata in the `.modinfo` section.
 ///
 /// # Example:
@@ -193,14 +391,60 @@ impl Parse for ModuleArgs {
 ///     version: "1.0.0"
 /// }
 /// ```
+///
+/// Module parameters are declared under `params`. Each one becomes a
+/// `static mut` of the matching C type, a `kernel_param` in the `__param`
+/// section and `parmtype=`/`parm=` entries in `.modinfo`. The type is one of
+/// the kernel parameter types (`int`, `bool`, `ulong`, ...) and selects the
+/// `param_ops_<type>` used to set it; `permissions` defaults to 0:
+/// ```ignore
+/// module! {
+///     name: "hello",
+///     version: "1.0.0",
+///     license: "GPL",
+///     description: "A simple hello world kernel module",
+///     params: {
+///         count: int {
+///             default: 1,
+///             permissions: 0o644,
+///             description: "Number of greetings",
+///         },
+///     },
+/// }
+/// ```
+///
+/// `name` is also stored in `struct module`, so it is limited to 55 bytes
+/// (`MODULE_NAME_LEN` including the NUL); longer names are a compile error.
 #[proc_macro]
 pub fn module(item: TokenStream) -> TokenStream {
     let args = parse_macro_input!(item as ModuleArgs);
 
     let name = args.name.expect("name is required");
+    if name.value().len() >= MODULE_NAME_LEN {
+        return syn::Error::new(
+            name.span(),
+            format!(
+                "module name is {} bytes, but MODULE_NAME_LEN ({}) allows at most {}",
+                name.value().len(),
+                MODULE_NAME_LEN,
+                MODULE_NAME_LEN - 1
+            ),
+        )
+        .to_compile_error()
+        .into();
+    }
     let version = args.version.expect("version is required");
     let license = args.license.expect("license is required");
     let description = args.description.expect("description is required");
+    let params = match args
+        .params
+        .iter()
+        .map(module_param)
+        .collect::<syn::Result<Vec<_>>>()
+    {
+        Ok(params) => params,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
     // Build complete byte arrays for each modinfo entry
     let mut name_array = b"name=".to_vec();
@@ -239,7 +483,8 @@ pub fn module(item: TokenStream) -> TokenStream {
         static MODULE_DESCRIPTION: [u8; #description_len] = [#(#description_array),*];
         #[used]
         #[unsafe(link_section = ".gnu.linkonce.this_module")]
-        static __this_module: kmod_tools::Module = kmod_tools::Module::new(Some(init_module), Some(cleanup_module));
+        static __this_module: kmod_tools::Module = kmod_tools::Module::new(Some(init_module), Some(cleanup_module)).with_name(#name);
+        #(#params)*
     }
     .into()
 }
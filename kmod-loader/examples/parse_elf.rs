@@ -6,7 +6,7 @@ use std::{
 };
 
 use goblin::elf::Elf;
-use kmod_loader::arch::ArchRelocationType;
+use kmod_loader::ArchRelocationType;
 
 pub struct ElfParser<'a> {
     elf: Elf<'a>,
@@ -5,7 +5,10 @@ use int_enum::IntEnum;
 
 use crate::{
     BIT, BIT_U64, ModuleErr, Result,
-    arch::{Ptr, aarch64::insn::*, get_rela_sym_idx, get_rela_type},
+    arch::{
+        Ptr, aarch64::insn::*, get_rela_sym_idx, get_rela_type, reject_tls_relocation,
+        relative_load_bias, signed_imm_check, unsigned_imm_check,
+    },
     loader::*,
 };
 
@@ -97,23 +100,19 @@ const AARCH64_INSN_ADD_IMM_64: u32 = 0x9100_0000;
 const AARCH64_INSN_BR: u32 = 0xd61f_0000;
 const AARCH64_REG_IP0: u32 = 16;
 
-const fn signed_imm_check(value: i128, bits: u32) -> bool {
-    value >= -(1i128 << (bits - 1)) && value < (1i128 << (bits - 1))
-}
-
 fn emit_plt_entry(address: u64, plt_entry_addr: u64) -> Result<PltEntry> {
-    let target_page = (address & !0xfff) as i64 as i128;
-    let plt_page = (plt_entry_addr & !0xfff) as i64 as i128;
-    let page_delta = (target_page - plt_page) >> 12;
+    let target_page = (address & !0xfff) as i64;
+    let plt_page = (plt_entry_addr & !0xfff) as i64;
+    let page_delta = target_page.checked_sub(plt_page).map(|delta| delta >> 12);
 
-    if !signed_imm_check(page_delta, 21) {
+    let Some(page_delta) = page_delta.filter(|&delta| signed_imm_check(delta, 21)) else {
         log::error!(
             "AArch64 PLT target {:#x} is out of ADRP range from PLT entry {:#x}",
             address,
             plt_entry_addr
         );
         return Err(ModuleErr::ENOEXEC);
-    }
+    };
 
     let imm = (page_delta as u32) & ((1 << 21) - 1);
     let immlo = imm & 0x3;
@@ -160,6 +159,50 @@ fn is_forbidden_offset_for_adrp(address: u64) -> bool {
     ((address & 0xfff) >= 0xff8) && false
 }
 
+/// See [`crate::ModuleLoader::supported_relocations`]; in match order
+pub const SUPPORTED_RELOCATIONS: &[u32] = &[
+    Arm64RelTy::R_ARM_NONE as u32,
+    Arm64RelTy::R_AARCH64_NONE as u32,
+    Arm64RelTy::R_AARCH64_ABS64 as u32,
+    Arm64RelTy::R_AARCH64_RELATIVE as u32,
+    Arm64RelTy::R_AARCH64_ABS32 as u32,
+    Arm64RelTy::R_AARCH64_ABS16 as u32,
+    Arm64RelTy::R_AARCH64_PREL64 as u32,
+    Arm64RelTy::R_AARCH64_PREL32 as u32,
+    Arm64RelTy::R_AARCH64_PREL16 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_UABS_G0_NC as u32,
+    Arm64RelTy::R_AARCH64_MOVW_UABS_G0 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_UABS_G1_NC as u32,
+    Arm64RelTy::R_AARCH64_MOVW_UABS_G1 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_UABS_G2_NC as u32,
+    Arm64RelTy::R_AARCH64_MOVW_UABS_G2 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_UABS_G3 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_SABS_G0 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_SABS_G1 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_SABS_G2 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_PREL_G0_NC as u32,
+    Arm64RelTy::R_AARCH64_MOVW_PREL_G0 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_PREL_G1_NC as u32,
+    Arm64RelTy::R_AARCH64_MOVW_PREL_G1 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_PREL_G2_NC as u32,
+    Arm64RelTy::R_AARCH64_MOVW_PREL_G2 as u32,
+    Arm64RelTy::R_AARCH64_MOVW_PREL_G3 as u32,
+    Arm64RelTy::R_AARCH64_LD_PREL_LO19 as u32,
+    Arm64RelTy::R_AARCH64_ADR_PREL_LO21 as u32,
+    Arm64RelTy::R_AARCH64_ADR_PREL_PG_HI21_NC as u32,
+    Arm64RelTy::R_AARCH64_ADR_PREL_PG_HI21 as u32,
+    Arm64RelTy::R_AARCH64_ADD_ABS_LO12_NC as u32,
+    Arm64RelTy::R_AARCH64_LDST8_ABS_LO12_NC as u32,
+    Arm64RelTy::R_AARCH64_LDST16_ABS_LO12_NC as u32,
+    Arm64RelTy::R_AARCH64_LDST32_ABS_LO12_NC as u32,
+    Arm64RelTy::R_AARCH64_LDST64_ABS_LO12_NC as u32,
+    Arm64RelTy::R_AARCH64_LDST128_ABS_LO12_NC as u32,
+    Arm64RelTy::R_AARCH64_TSTBR14 as u32,
+    Arm64RelTy::R_AARCH64_CONDBR19 as u32,
+    Arm64RelTy::R_AARCH64_JUMP26 as u32,
+    Arm64RelTy::R_AARCH64_CALL26 as u32,
+];
+
 impl ArchRelocationType {
     /// See <https://elixir.bootlin.com/linux/v6.6/source/arch/arm64/kernel/module.c#L177>
     fn reloc_data(
@@ -169,7 +212,8 @@ impl ArchRelocationType {
         address: u64,
         len: usize,
     ) -> Result<bool> {
-        let s_addr = do_reloc(op, location, address) as i64;
+        let value = do_reloc(op, location, address);
+        let s_addr = value as i64;
         /*
          * The ELF psABI for AArch64 documents the 16-bit and 32-bit place
          * relative and absolute relocations as having a range of [-2^15, 2^16)
@@ -180,34 +224,33 @@ impl ArchRelocationType {
          * interpretation of 32-bit relative references, so let's use that
          * for all R_AARCH64_PRELxx relocations. This means our upper
          * bound for overflow detection should be Sxx_MAX rather than Uxx_MAX.
+         *
+         * R_AARCH64_ABSxx are checked as unsigned: the value must survive a
+         * round trip through uxx, so negative values are out of range.
          */
         match len {
             16 => {
-                location.write::<i16>(s_addr as i16);
+                location.write_unaligned::<i16>(s_addr as i16);
                 match op {
-                    Aarch64RelocOp::RELOC_OP_ABS => Ok(s_addr < 0 || s_addr > u16::MAX as i64),
-                    Aarch64RelocOp::RELOC_OP_PREL => {
-                        Ok(s_addr < i16::MIN as i64 || s_addr > i16::MAX as i64)
-                    }
+                    Aarch64RelocOp::RELOC_OP_ABS => Ok(!unsigned_imm_check(value, 16)),
+                    Aarch64RelocOp::RELOC_OP_PREL => Ok(!signed_imm_check(s_addr, 16)),
                     _ => {
                         unreachable!("Unsupported operation for AArch64 16-bit relocation")
                     }
                 }
             }
             32 => {
-                location.write::<i32>(s_addr as i32);
+                location.write_unaligned::<i32>(s_addr as i32);
                 match op {
-                    Aarch64RelocOp::RELOC_OP_ABS => Ok(s_addr < 0 || s_addr > u32::MAX as i64),
-                    Aarch64RelocOp::RELOC_OP_PREL => {
-                        Ok(s_addr < i32::MIN as i64 || s_addr > i32::MAX as i64)
-                    }
+                    Aarch64RelocOp::RELOC_OP_ABS => Ok(!unsigned_imm_check(value, 32)),
+                    Aarch64RelocOp::RELOC_OP_PREL => Ok(!signed_imm_check(s_addr, 32)),
                     _ => {
                         unreachable!("Unsupported operation for AArch64 32-bit relocation")
                     }
                 }
             }
             64 => {
-                location.write::<u64>(s_addr as u64);
+                location.write_unaligned::<u64>(s_addr as u64);
                 Ok(false)
             }
             _ => unreachable!("Unsupported length for AArch64 relocation"),
@@ -332,6 +375,18 @@ impl ArchRelocationType {
         }
     }
 
+    /// Access size in bytes of the `LDST*_ABS_LO12_NC` relocations, whose
+    /// immediate is the low 12 bits of the address scaled down by it
+    fn ldst_access_size(&self) -> Option<u64> {
+        match self {
+            Arm64RelTy::R_AARCH64_LDST16_ABS_LO12_NC => Some(2),
+            Arm64RelTy::R_AARCH64_LDST32_ABS_LO12_NC => Some(4),
+            Arm64RelTy::R_AARCH64_LDST64_ABS_LO12_NC => Some(8),
+            Arm64RelTy::R_AARCH64_LDST128_ABS_LO12_NC => Some(16),
+            _ => None,
+        }
+    }
+
     fn apply_relocation(
         &self,
         module: &mut ModuleOwner<impl KernelModuleHelper>,
@@ -339,13 +394,26 @@ impl ArchRelocationType {
         location: u64,
         address: u64,
     ) -> Result<()> {
+        // The scaled immediate would silently drop the low bits of a
+        // misaligned target, so the access would hit the wrong address.
+        if let Some(size) = self.ldst_access_size()
+            && address & (size - 1) != 0
+        {
+            log::error!(
+                "Relocation type {:?} target {:#x} is not {}-byte aligned",
+                self,
+                address,
+                size
+            );
+            return Err(ModuleErr::ENOEXEC);
+        }
         // Check for overflow by default.
         let mut check_overflow = true;
         let location = Ptr(location);
         let ovf = match self {
             Arm64RelTy::R_ARM_NONE | Arm64RelTy::R_AARCH64_NONE => false,
             // Data relocations.
-            Arm64RelTy::R_AARCH64_ABS64 => {
+            Arm64RelTy::R_AARCH64_ABS64 | Arm64RelTy::R_AARCH64_RELATIVE => {
                 check_overflow = false;
                 self.reloc_data(Aarch64RelocOp::RELOC_OP_ABS, location, address, 64)?
             }
@@ -621,6 +689,13 @@ impl ArchRelocationType {
     }
 }
 
+/// Whether `rel_type` is one of the thread-local storage relocations: the
+/// static `R_AARCH64_TLSGD_*` to `R_AARCH64_TLSLD_LDST128_DTPREL_LO12_NC`
+/// block and the dynamic `R_AARCH64_TLS_DTPMOD` to `R_AARCH64_TLSDESC`
+const fn is_tls_relocation(rel_type: u32) -> bool {
+    matches!(rel_type, 512..=573 | 1028..=1031)
+}
+
 pub struct ArchRelocate;
 
 #[allow(unused_assignments)]
@@ -633,7 +708,7 @@ impl ArchRelocate {
         load_info: &ModuleLoadInfo,
         module: &mut ModuleOwner<H>,
     ) -> Result<()> {
-        for rela in rela_list {
+        for (idx, rela) in rela_list.iter().enumerate() {
             let rel_type = get_rela_type(rela.r_info);
             let sym_idx = get_rela_sym_idx(rela.r_info);
 
@@ -641,16 +716,26 @@ impl ArchRelocate {
             let location = sechdrs[rel_section.sh_info as usize].sh_addr + rela.r_offset;
             let (sym, sym_name) = &load_info.syms[sym_idx];
 
-            let reloc_type = Arm64RelTy::try_from(rel_type).map_err(|_| {
+            if is_tls_relocation(rel_type) {
+                reject_tls_relocation(module, rel_section, idx, rel_type)?;
+                continue;
+            }
+            let Ok(reloc_type) = Arm64RelTy::try_from(rel_type) else {
                 log::error!(
-                    "[{:?}]: Invalid relocation type: {}",
+                    "[{:?}]: Invalid relocation type {} at index {}",
                     module.name(),
-                    rel_type
+                    rel_type,
+                    idx
                 );
-                ModuleErr::ENOEXEC
-            })?;
+                module.reloc_failed(rel_section, idx, rel_type, ModuleErr::ENOEXEC)?;
+                continue;
+            };
             // val corresponds to (S + A) in the AArch64 ELF document.
-            let target_addr = sym.st_value.wrapping_add(rela.r_addend as u64);
+            let mut target_addr = sym.st_value.wrapping_add(rela.r_addend as u64);
+            if matches!(reloc_type, Arm64RelTy::R_AARCH64_RELATIVE) {
+                target_addr =
+                    relative_load_bias(sechdrs, rel_section).wrapping_add(rela.r_addend as u64);
+            }
 
             // Perform the static relocation.
             log::info!(
@@ -664,8 +749,14 @@ impl ArchRelocate {
             let res = reloc_type.apply_relocation(module, sechdrs, location, target_addr);
             match res {
                 Err(e) => {
-                    log::error!("[{:?}]: ({}) {:?}", module.name(), sym_name, e);
-                    return Err(e);
+                    log::error!(
+                        "[{:?}]: relocation {} against '{}' failed: {:?}",
+                        module.name(),
+                        idx,
+                        sym_name,
+                        e
+                    );
+                    module.reloc_failed(rel_section, idx, rel_type, e)?;
                 }
                 Ok(_) => { /* Successfully applied relocation */ }
             }
@@ -739,3 +830,83 @@ fn count_plts(rela_sec: &RelocSection) -> usize {
         })
         .count()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abs32_overflow() {
+        let mut word = 0u32;
+        let location = Ptr(&raw mut word as u64);
+        let abs32 = |address| {
+            Arm64RelTy::R_AARCH64_ABS32
+                .reloc_data(Aarch64RelocOp::RELOC_OP_ABS, location, address, 32)
+                .unwrap()
+        };
+        assert!(!abs32(0x8000_0000));
+        assert_eq!(word, 0x8000_0000);
+        assert!(!abs32(u32::MAX as u64));
+        assert!(abs32(0x1_0000_0000));
+        assert!(abs32(-1i64 as u64));
+    }
+
+    #[test]
+    fn test_abs16_overflow() {
+        let mut half = 0u16;
+        let location = Ptr(&raw mut half as u64);
+        let abs16 = |address| {
+            Arm64RelTy::R_AARCH64_ABS16
+                .reloc_data(Aarch64RelocOp::RELOC_OP_ABS, location, address, 16)
+                .unwrap()
+        };
+        assert!(!abs16(0x8000));
+        assert!(abs16(0x1_0000));
+        assert!(abs16(-1i64 as u64));
+    }
+
+    #[test]
+    fn test_ldst_misaligned_target() {
+        // ldr x0, [x1]
+        let mut insn = 0xf940_0020u32;
+        let location = &raw mut insn as u64;
+        let mut owner = crate::ModuleOwner::<crate::TestHelper>::empty();
+        let ldst64 = Arm64RelTy::R_AARCH64_LDST64_ABS_LO12_NC;
+
+        assert_eq!(
+            ldst64.apply_relocation(&mut owner, &[], location, 0x1004),
+            Err(ModuleErr::ENOEXEC)
+        );
+        assert_eq!(insn, 0xf940_0020);
+
+        ldst64
+            .apply_relocation(&mut owner, &[], location, 0x1008)
+            .unwrap();
+        // imm12 = 0x008 >> 3
+        assert_eq!(insn, 0xf940_0020 | (1 << 10));
+
+        assert_eq!(
+            Arm64RelTy::R_AARCH64_LDST128_ABS_LO12_NC.apply_relocation(
+                &mut owner,
+                &[],
+                location,
+                0x1008
+            ),
+            Err(ModuleErr::ENOEXEC)
+        );
+    }
+
+    #[test]
+    fn test_relative() {
+        let (word, addr) =
+            crate::arch::tests::relocate_word(Arm64RelTy::R_AARCH64_RELATIVE as u32, 0x40);
+        assert_eq!(word, addr + 0x40);
+    }
+
+    #[test]
+    fn test_tls_unsupported() {
+        // R_AARCH64_TLS_TPREL64
+        let err = crate::arch::tests::try_relocate_word(1030, 0).err();
+        assert_eq!(err, Some(ModuleErr::EOPNOTSUPP));
+    }
+}
@@ -146,16 +146,6 @@ type LaRelTy = ArchRelocationType;
 
 const RELA_STACK_DEPTH: usize = 16;
 
-const fn signed_imm_check(value: i64, bits: u32) -> bool {
-    let limit = 1i64 << (bits - 1);
-    value >= -limit && value < limit
-}
-
-const fn unsigned_imm_check(value: u64, bits: u32) -> bool {
-    let limit = 1u64 << bits;
-    value < limit
-}
-
 fn rela_stack_push(
     rela_stack: &mut [i64; RELA_STACK_DEPTH],
     rela_stack_top: &mut usize,
@@ -197,6 +187,48 @@ fn rela_stack_pop(
     Ok(value)
 }
 
+/// See [`crate::ModuleLoader::supported_relocations`]; in match order
+pub const SUPPORTED_RELOCATIONS: &[u32] = &[
+    LaRelTy::R_LARCH_B26 as u32,
+    LaRelTy::R_LARCH_GOT_PC_HI20 as u32,
+    LaRelTy::R_LARCH_GOT_PC_LO12 as u32,
+    LaRelTy::R_LARCH_SOP_PUSH_PLT_PCREL as u32,
+    LaRelTy::R_LARCH_NONE as u32,
+    LaRelTy::R_LARCH_32 as u32,
+    LaRelTy::R_LARCH_64 as u32,
+    LaRelTy::R_LARCH_RELATIVE as u32,
+    LaRelTy::R_LARCH_MARK_LA as u32,
+    LaRelTy::R_LARCH_MARK_PCREL as u32,
+    LaRelTy::R_LARCH_SOP_PUSH_PCREL as u32,
+    LaRelTy::R_LARCH_SOP_PUSH_ABSOLUTE as u32,
+    LaRelTy::R_LARCH_SOP_PUSH_DUP as u32,
+    LaRelTy::R_LARCH_SOP_SUB as u32,
+    LaRelTy::R_LARCH_SOP_SL as u32,
+    LaRelTy::R_LARCH_SOP_SR as u32,
+    LaRelTy::R_LARCH_SOP_ADD as u32,
+    LaRelTy::R_LARCH_SOP_AND as u32,
+    LaRelTy::R_LARCH_SOP_IF_ELSE as u32,
+    LaRelTy::R_LARCH_SOP_POP_32_S_10_5 as u32,
+    LaRelTy::R_LARCH_SOP_POP_32_U_10_12 as u32,
+    LaRelTy::R_LARCH_SOP_POP_32_S_10_12 as u32,
+    LaRelTy::R_LARCH_SOP_POP_32_S_10_16 as u32,
+    LaRelTy::R_LARCH_SOP_POP_32_S_10_16_S2 as u32,
+    LaRelTy::R_LARCH_SOP_POP_32_S_5_20 as u32,
+    LaRelTy::R_LARCH_SOP_POP_32_S_0_5_10_16_S2 as u32,
+    LaRelTy::R_LARCH_SOP_POP_32_S_0_10_10_16_S2 as u32,
+    LaRelTy::R_LARCH_SOP_POP_32_U as u32,
+    LaRelTy::R_LARCH_ADD32 as u32,
+    LaRelTy::R_LARCH_ADD64 as u32,
+    LaRelTy::R_LARCH_SUB32 as u32,
+    LaRelTy::R_LARCH_SUB64 as u32,
+    LaRelTy::R_LARCH_PCALA_HI20 as u32,
+    LaRelTy::R_LARCH_PCALA_LO12 as u32,
+    LaRelTy::R_LARCH_PCALA64_LO20 as u32,
+    LaRelTy::R_LARCH_PCALA64_HI12 as u32,
+    LaRelTy::R_LARCH_32_PCREL as u32,
+    LaRelTy::R_LARCH_64_PCREL as u32,
+];
+
 impl ArchRelocationType {
     /// See <https://elixir.bootlin.com/linux/v6.6/source/arch/loongarch/kernel/module.c#L278>
     fn apply_r_larch_b26(
@@ -309,14 +341,14 @@ impl ArchRelocationType {
     /// See <https://elixir.bootlin.com/linux/v6.6/source/arch/loongarch/kernel/module.c#L370>
     fn apply_r_larch_32_pcrel(&self, location: Ptr, address: u64) -> Result<()> {
         let offset = address as i64 - location.0 as i64;
-        location.write::<u32>(offset as u32);
+        location.write_unaligned::<u32>(offset as u32);
         Ok(())
     }
 
     /// See <https://elixir.bootlin.com/linux/v6.6/source/arch/loongarch/kernel/module.c#L379>
     fn apply_r_larch_64_pcrel(&self, location: Ptr, address: u64) -> Result<()> {
         let offset = address as i64 - location.0 as i64;
-        location.write::<u64>(offset as u64);
+        location.write_unaligned::<u64>(offset as u64);
         Ok(())
     }
 
@@ -583,12 +615,13 @@ impl ArchRelocationType {
                 if !unsigned_imm_check(opr1 as u64, 32) {
                     return Err(overflow());
                 }
-                location.write::<u32>(opr1 as u32);
+                location.write_unaligned::<u32>(opr1 as u32);
                 Ok(())
             }
 
             _ => {
-                unimplemented!("Relocation type {:?} not implemented yet", self);
+                log::error!("Relocation type {:?} not implemented yet", self);
+                Err(ModuleErr::ENOEXEC)
             }
         }
     }
@@ -597,27 +630,27 @@ impl ArchRelocationType {
     fn apply_r_larch_add_sub(&self, location: Ptr, address: u64) -> Result<()> {
         match *self {
             LaRelTy::R_LARCH_ADD32 => {
-                let original = location.read::<i32>();
+                let original = location.read_unaligned::<i32>();
                 let result = original.wrapping_add(address as i32);
-                location.write(result);
+                location.write_unaligned(result);
                 Ok(())
             }
             LaRelTy::R_LARCH_ADD64 => {
-                let original = location.read::<i64>();
+                let original = location.read_unaligned::<i64>();
                 let result = original.wrapping_add(address as i64);
-                location.write(result);
+                location.write_unaligned(result);
                 Ok(())
             }
             LaRelTy::R_LARCH_SUB32 => {
-                let original = location.read::<i32>();
+                let original = location.read_unaligned::<i32>();
                 let result = original.wrapping_sub(address as i32);
-                location.write(result);
+                location.write_unaligned(result);
                 Ok(())
             }
             LaRelTy::R_LARCH_SUB64 => {
-                let original = location.read::<i64>();
+                let original = location.read_unaligned::<i64>();
                 let result = original.wrapping_sub(address as i64);
-                location.write(result);
+                location.write_unaligned(result);
                 Ok(())
             }
             _ => {
@@ -632,12 +665,12 @@ impl ArchRelocationType {
     }
 
     fn apply_r_larch_32(&self, location: Ptr, address: u64) -> Result<()> {
-        location.write::<u32>(address as u32);
+        location.write_unaligned::<u32>(address as u32);
         Ok(())
     }
 
     fn apply_r_larch_64(&self, location: Ptr, address: u64) -> Result<()> {
-        location.write::<u64>(address);
+        location.write_unaligned::<u64>(address);
         Ok(())
     }
 
@@ -674,7 +707,9 @@ impl ArchRelocationType {
 
             LaRelTy::R_LARCH_NONE => self.apply_r_larch_none(location, address),
             LaRelTy::R_LARCH_32 => self.apply_r_larch_32(location, address),
-            LaRelTy::R_LARCH_64 => self.apply_r_larch_64(location, address),
+            LaRelTy::R_LARCH_64 | LaRelTy::R_LARCH_RELATIVE => {
+                self.apply_r_larch_64(location, address)
+            }
             LaRelTy::R_LARCH_MARK_LA | LaRelTy::R_LARCH_MARK_PCREL => {
                 self.apply_r_larch_none(location, address)
             }
@@ -730,12 +765,18 @@ impl ArchRelocationType {
             LaRelTy::R_LARCH_32_PCREL => self.apply_r_larch_32_pcrel(location, address),
             LaRelTy::R_LARCH_64_PCREL => self.apply_r_larch_64_pcrel(location, address),
             _ => {
-                unimplemented!("Relocation type {:?} not implemented yet", self);
+                log::error!("Relocation type {:?} not implemented yet", self);
+                Err(ModuleErr::ENOEXEC)
             }
         }
     }
 }
 
+/// Whether `rel_type` is one of the thread-local storage relocations
+const fn is_tls_relocation(rel_type: u32) -> bool {
+    matches!(rel_type, 6..=11 | 13 | 14 | 26..=28 | 83..=98 | 111..=126)
+}
+
 pub struct ArchRelocate;
 
 impl ArchRelocate {
@@ -750,7 +791,7 @@ impl ArchRelocate {
         let mut rela_stack = [0i64; RELA_STACK_DEPTH];
         let mut rela_stack_top = 0;
 
-        for rela in rela_list {
+        for (idx, rela) in rela_list.iter().enumerate() {
             let rel_type = get_rela_type(rela.r_info);
             let sym_idx = get_rela_sym_idx(rela.r_info);
 
@@ -766,16 +807,26 @@ impl ArchRelocate {
             //     return -ENOENT;
             // }
 
-            let reloc_type = ArchRelocationType::try_from(rel_type).map_err(|_| {
+            if is_tls_relocation(rel_type) {
+                reject_tls_relocation(module, rel_section, idx, rel_type)?;
+                continue;
+            }
+            let Ok(reloc_type) = ArchRelocationType::try_from(rel_type) else {
                 log::error!(
-                    "[{:?}]: Invalid relocation type: {}",
+                    "[{:?}]: Invalid relocation type {} at index {}",
                     module.name(),
-                    rel_type
+                    rel_type,
+                    idx
                 );
-                ModuleErr::ENOEXEC
-            })?;
+                module.reloc_failed(rel_section, idx, rel_type, ModuleErr::ENOEXEC)?;
+                continue;
+            };
 
-            let target_addr = sym.st_value.wrapping_add(rela.r_addend as u64);
+            let mut target_addr = sym.st_value.wrapping_add(rela.r_addend as u64);
+            if matches!(reloc_type, LaRelTy::R_LARCH_RELATIVE) {
+                target_addr =
+                    relative_load_bias(sechdrs, rel_section).wrapping_add(rela.r_addend as u64);
+            }
             log::trace!(
                 "Applying relocation: type = {:?}, location = {:#x}, target_addr = {:#x}",
                 reloc_type,
@@ -793,8 +844,14 @@ impl ArchRelocate {
 
             match res {
                 Err(e) => {
-                    log::error!("[{:?}]: ({}) {:?}", module.name(), sym_name, e);
-                    return Err(e);
+                    log::error!(
+                        "[{:?}]: relocation {} against '{}' failed: {:?}",
+                        module.name(),
+                        idx,
+                        sym_name,
+                        e
+                    );
+                    module.reloc_failed(rel_section, idx, rel_type, e)?;
                 }
                 Ok(_) => { /* Successfully applied relocation */ }
             }
@@ -879,3 +936,45 @@ fn count_max_entries(rela_sec: &RelocSection) -> (usize, usize) {
     }
     (plt_entries, got_entries)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative() {
+        let (word, addr) =
+            crate::arch::tests::relocate_word(LaRelTy::R_LARCH_RELATIVE as u32, 0x40);
+        assert_eq!(word, addr + 0x40);
+    }
+
+    #[test]
+    fn test_tls_unsupported() {
+        let err =
+            crate::arch::tests::try_relocate_word(LaRelTy::R_LARCH_TLS_LE_HI20 as u32, 0).err();
+        assert_eq!(err, Some(ModuleErr::EOPNOTSUPP));
+    }
+
+    #[test]
+    fn test_unimplemented_reported() {
+        use crate::{
+            arch::tests::{install_best_effort, try_relocate_word, try_relocate_word_in},
+            testing::HookHelper,
+        };
+
+        for ty in [LaRelTy::R_LARCH_B16, LaRelTy::R_LARCH_ABS_HI20] {
+            let ty = ty as u32;
+            assert_eq!(try_relocate_word(ty, 0).err(), Some(ModuleErr::ENOEXEC));
+
+            install_best_effort();
+            let mut owner = crate::ModuleOwner::<HookHelper>::empty();
+            try_relocate_word_in(ty, 0, &mut owner).unwrap();
+            let errors = owner
+                .reloc_errors()
+                .iter()
+                .map(|err| (err.section, err.index, err.rel_type))
+                .collect::<alloc::vec::Vec<_>>();
+            assert_eq!(errors, [(1, 0, ty)]);
+        }
+    }
+}
@@ -33,6 +33,67 @@ pub const fn sign_extend64(value: u64, index: u32) -> i64 {
     ((value << shift) as i64) >> shift
 }
 
+/// Whether `value` fits in a `bits`-bit two's complement immediate, i.e. lies
+/// in `[-2^(bits-1), 2^(bits-1))`
+const fn signed_imm_check(value: i64, bits: u32) -> bool {
+    if bits >= 64 {
+        return true;
+    }
+    let limit = 1i64 << (bits - 1);
+    value >= -limit && value < limit
+}
+
+/// Whether `value` fits in a `bits`-bit unsigned immediate, i.e. lies in
+/// `[0, 2^bits)`
+const fn unsigned_imm_check(value: u64, bits: u32) -> bool {
+    bits >= 64 || value < (1u64 << bits)
+}
+
+/// Whether relocations of type `ty` can be applied on this architecture
+pub(crate) fn is_relocation_supported(ty: u32) -> bool {
+    SUPPORTED_RELOCATIONS.contains(&ty)
+}
+
+/// Load bias for the `*_RELATIVE` relocations in `rel_section`, which have no
+/// symbol and resolve to `load_bias + addend`. Addends of a relocatable
+/// object are relative to the section being relocated, so the bias is the
+/// address that section was placed at. Layout replaces the `sh_addr` of each
+/// allocated section with that address before any relocation is applied, so
+/// whatever address the object was linked at is ignored.
+fn relative_load_bias(
+    sechdrs: &[goblin::elf::SectionHeader],
+    rel_section: &goblin::elf::SectionHeader,
+) -> u64 {
+    sechdrs[rel_section.sh_info as usize].sh_addr
+}
+
+/// Refuse the thread-local storage relocation at `idx` of `rel_section`.
+/// Modules have no TLS block of their own (per-CPU data is addressed
+/// differently), so these fail with `EOPNOTSUPP` on every architecture,
+/// unless relocations are best-effort.
+fn reject_tls_relocation<H: crate::KernelModuleHelper>(
+    module: &mut crate::ModuleOwner<H>,
+    rel_section: &goblin::elf::SectionHeader,
+    idx: usize,
+    rel_type: u32,
+) -> crate::Result<()> {
+    match ArchRelocationType::try_from(rel_type) {
+        Ok(ty) => log::error!(
+            "[{:?}]: TLS relocation {:?} at index {} is not supported",
+            module.name(),
+            ty,
+            idx
+        ),
+        Err(_) => log::error!(
+            "[{:?}]: TLS relocation type {} at index {} is not supported",
+            module.name(),
+            rel_type,
+            idx
+        ),
+    }
+    module.reloc_failed(rel_section, idx, rel_type, crate::ModuleErr::EOPNOTSUPP)
+}
+
 /// Extracts the relocation type from the r_info field of an Elf64_Rela
 const fn get_rela_type(r_info: u64) -> u32 {
     (r_info & 0xffffffff) as u32
@@ -50,21 +111,52 @@ impl Ptr {
         self.0 as *mut T
     }
 
-    /// Writes a value of type T to the pointer location
-    pub fn write<T>(&self, value: T) {
+    fn debug_assert_aligned<T>(&self) {
+        debug_assert!(
+            self.0.is_multiple_of(align_of::<T>() as u64),
+            "{:#x} is not aligned for {}",
+            self.0,
+            core::any::type_name::<T>()
+        );
+    }
+
+    /// Writes a value of type T to the pointer location, which must be
+    /// aligned for T
+    pub fn write<T: Copy>(&self, value: T) {
+        self.debug_assert_aligned::<T>();
         unsafe {
             let ptr = self.as_ptr::<T>();
             ptr.write(value);
         }
     }
 
-    pub fn read<T>(&self) -> T {
+    /// Reads a value of type T from the pointer location, which must be
+    /// aligned for T
+    pub fn read<T: Copy>(&self) -> T {
+        self.debug_assert_aligned::<T>();
         unsafe {
             let ptr = self.as_ptr::<T>();
             ptr.read()
         }
     }
 
+    /// Writes a value of type T to a possibly unaligned location, e.g. a
+    /// data word or an instruction immediate
+    pub fn write_unaligned<T: Copy>(&self, value: T) {
+        unsafe {
+            let ptr = self.as_ptr::<T>();
+            ptr.write_unaligned(value);
+        }
+    }
+
+    /// Reads a value of type T from a possibly unaligned location
+    pub fn read_unaligned<T: Copy>(&self) -> T {
+        unsafe {
+            let ptr = self.as_ptr::<T>();
+            ptr.read_unaligned()
+        }
+    }
+
     pub fn add(&self, offset: usize) -> Ptr {
         Ptr(self.0 + offset as u64)
     }
@@ -401,3 +493,174 @@ mod common {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+
+    use goblin::elf::{SectionHeader, sym::Sym};
+
+    use super::*;
+    use crate::{
+        KernelModuleHelper, ModuleErr,
+        loader::ModuleLoadInfo,
+        testing::{HookHelper, Hooks, TestHelper},
+    };
+
+    /// Apply a single relocation of type `ty` without a symbol to a zeroed
+    /// word in a section of its own, returning the word and its address
+    pub(super) fn relocate_word(ty: u32, addend: i64) -> (u64, u64) {
+        try_relocate_word(ty, addend).unwrap()
+    }
+
+    /// Like [`relocate_word`], returning the error of a failed relocation
+    pub(super) fn try_relocate_word(ty: u32, addend: i64) -> crate::Result<(u64, u64)> {
+        try_relocate_word_in(ty, addend, &mut crate::ModuleOwner::<TestHelper>::empty())
+    }
+
+    /// Like [`try_relocate_word`], recording failures in `owner`
+    pub(super) fn try_relocate_word_in<H: KernelModuleHelper>(
+        ty: u32,
+        addend: i64,
+        owner: &mut crate::ModuleOwner<H>,
+    ) -> crate::Result<(u64, u64)> {
+        let mut word = 0u64;
+        let sechdrs = vec![
+            SectionHeader::default(),
+            SectionHeader {
+                sh_addr: &raw mut word as u64,
+                ..Default::default()
+            },
+        ];
+        let rel_section = SectionHeader {
+            sh_info: 1,
+            ..Default::default()
+        };
+        let rela = goblin::elf64::reloc::Rela {
+            r_offset: 0,
+            r_info: ty as u64,
+            r_addend: addend,
+        };
+        let load_info = ModuleLoadInfo {
+            syms: vec![(Sym::default(), String::new())],
+        };
+        ArchRelocate::apply_relocate_add(&[rela], &rel_section, &sechdrs, &load_info, owner)?;
+        Ok((word, &raw const word as u64))
+    }
+
+    /// Make [`HookHelper`] record failed relocations instead of stopping at
+    /// them
+    pub(super) fn install_best_effort() {
+        Hooks {
+            best_effort_relocations: true,
+            ..Default::default()
+        }
+        .install();
+    }
+
+    #[test]
+    fn test_best_effort_collects_reloc_errors() {
+        let mut word = 0u64;
+        let sechdrs = vec![
+            SectionHeader::default(),
+            SectionHeader {
+                sh_addr: &raw mut word as u64,
+                ..Default::default()
+            },
+        ];
+        let rel_section = SectionHeader {
+            sh_info: 1,
+            ..Default::default()
+        };
+        // Relocation types no architecture defines
+        let relas = [0xfffe, 0xffff].map(|ty| goblin::elf64::reloc::Rela {
+            r_offset: 0,
+            r_info: ty,
+            r_addend: 0,
+        });
+        let load_info = ModuleLoadInfo {
+            syms: vec![(Sym::default(), String::new())],
+        };
+
+        let mut owner = crate::ModuleOwner::<TestHelper>::empty();
+        assert!(
+            ArchRelocate::apply_relocate_add(
+                &relas,
+                &rel_section,
+                &sechdrs,
+                &load_info,
+                &mut owner
+            )
+            .is_err()
+        );
+        assert!(owner.reloc_errors().is_empty());
+
+        install_best_effort();
+        let mut owner = crate::ModuleOwner::<HookHelper>::empty();
+        ArchRelocate::apply_relocate_add(&relas, &rel_section, &sechdrs, &load_info, &mut owner)
+            .unwrap();
+        let errors = owner
+            .reloc_errors()
+            .iter()
+            .map(|err| (err.section, err.index, err.rel_type))
+            .collect::<alloc::vec::Vec<_>>();
+        assert_eq!(errors, [(1, 0, 0xfffe), (1, 1, 0xffff)]);
+        assert_eq!(owner.call_init(), Err(ModuleErr::ENOEXEC));
+    }
+
+    #[test]
+    fn test_imm_check_boundaries() {
+        for bits in [12, 16, 21, 26, 28, 32] {
+            let min = -(1i64 << (bits - 1));
+            let max = (1i64 << (bits - 1)) - 1;
+            assert!(signed_imm_check(min, bits));
+            assert!(signed_imm_check(max, bits));
+            assert!(signed_imm_check(0, bits));
+            assert!(signed_imm_check(-1, bits));
+            assert!(!signed_imm_check(min - 1, bits));
+            assert!(!signed_imm_check(max + 1, bits));
+
+            let umax = (1u64 << bits) - 1;
+            assert!(unsigned_imm_check(0, bits));
+            assert!(unsigned_imm_check(umax, bits));
+            assert!(!unsigned_imm_check(umax + 1, bits));
+            // A negative value is a huge unsigned one
+            assert!(!unsigned_imm_check(-1i64 as u64, bits));
+        }
+
+        assert!(signed_imm_check(i64::MIN, 64));
+        assert!(signed_imm_check(i64::MAX, 64));
+        assert!(!signed_imm_check(i64::MIN, 63));
+        assert!(unsigned_imm_check(u64::MAX, 64));
+        assert!(!unsigned_imm_check(u64::MAX, 63));
+    }
+
+    #[test]
+    fn test_ptr_unaligned_access() {
+        let mut buf = [0u8; 16];
+        let ptr = Ptr(buf.as_mut_ptr() as u64).add(1);
+        ptr.write_unaligned::<u64>(0x0807_0605_0403_0201);
+        assert_eq!(ptr.read_unaligned::<u64>(), 0x0807_0605_0403_0201);
+        assert_eq!(buf[..10], [0, 1, 2, 3, 4, 5, 6, 7, 8, 0]);
+        ptr.add(8).write_unaligned::<i32>(-1);
+        assert_eq!(buf[9..13], [0xff; 4]);
+    }
+
+    #[test]
+    fn test_ptr_aligned_access() {
+        let mut word = 0u64;
+        let ptr = Ptr(&raw mut word as u64);
+        ptr.write::<u64>(42);
+        assert_eq!(ptr.read::<u64>(), 42);
+        ptr.add(4).write::<u32>(1);
+        assert_eq!(word, 42 | (1 << 32));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "is not aligned for u32")]
+    fn test_ptr_misaligned_access_asserts() {
+        let buf = [0u32; 2];
+        Ptr(buf.as_ptr() as u64).add(2).read::<u32>();
+    }
+}
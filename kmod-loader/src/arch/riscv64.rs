@@ -4,7 +4,7 @@ use int_enum::IntEnum;
 use super::*;
 use crate::{
     ModuleErr, Result,
-    arch::{Ptr, get_rela_sym_idx, get_rela_type},
+    arch::{Ptr, get_rela_sym_idx, get_rela_type, unsigned_imm_check},
     loader::{KernelModuleHelper, ModuleLoadInfo, ModuleOwner},
 };
 
@@ -149,9 +149,48 @@ const fn riscv_insn_valid_32bit_offset(offset: i64) -> bool {
     low <= offset && offset < high
 }
 
+/// See [`crate::ModuleLoader::supported_relocations`]; in match order
+pub const SUPPORTED_RELOCATIONS: &[u32] = &[
+    Rv64RelTy::R_RISCV_32 as u32,
+    Rv64RelTy::R_RISCV_64 as u32,
+    Rv64RelTy::R_RISCV_RELATIVE as u32,
+    Rv64RelTy::R_RISCV_BRANCH as u32,
+    Rv64RelTy::R_RISCV_JAL as u32,
+    Rv64RelTy::R_RISCV_RVC_BRANCH as u32,
+    Rv64RelTy::R_RISCV_RVC_JUMP as u32,
+    Rv64RelTy::R_RISCV_PCREL_HI20 as u32,
+    Rv64RelTy::R_RISCV_PCREL_LO12_I as u32,
+    Rv64RelTy::R_RISCV_PCREL_LO12_S as u32,
+    Rv64RelTy::R_RISCV_HI20 as u32,
+    Rv64RelTy::R_RISCV_LO12_I as u32,
+    Rv64RelTy::R_RISCV_LO12_S as u32,
+    Rv64RelTy::R_RISCV_GOT_HI20 as u32,
+    Rv64RelTy::R_RISCV_CALL_PLT as u32,
+    Rv64RelTy::R_RISCV_CALL as u32,
+    Rv64RelTy::R_RISCV_RELAX as u32,
+    Rv64RelTy::R_RISCV_ALIGN as u32,
+    Rv64RelTy::R_RISCV_ADD8 as u32,
+    Rv64RelTy::R_RISCV_ADD16 as u32,
+    Rv64RelTy::R_RISCV_ADD32 as u32,
+    Rv64RelTy::R_RISCV_ADD64 as u32,
+    Rv64RelTy::R_RISCV_SUB8 as u32,
+    Rv64RelTy::R_RISCV_SUB16 as u32,
+    Rv64RelTy::R_RISCV_SUB32 as u32,
+    Rv64RelTy::R_RISCV_SUB64 as u32,
+    Rv64RelTy::R_RISCV_SUB6 as u32,
+    Rv64RelTy::R_RISCV_SET6 as u32,
+    Rv64RelTy::R_RISCV_SET8 as u32,
+    Rv64RelTy::R_RISCV_SET16 as u32,
+    Rv64RelTy::R_RISCV_SET32 as u32,
+    Rv64RelTy::R_RISCV_32_PCREL as u32,
+    Rv64RelTy::R_RISCV_PLT32 as u32,
+];
+
+// With the C extension, 32-bit instructions are only 2-byte aligned, so all
+// multi-byte accesses below go through the unaligned `Ptr` methods.
 impl Rv64RelTy {
     fn apply_r_riscv_32_rela(location: Ptr, address: u64) -> Result<()> {
-        if address != address as u32 as u64 {
+        if !unsigned_imm_check(address, 32) {
             log::error!(
                 "R_RISCV_32: target {:016x} does not fit in 32 bits",
                 address
@@ -159,13 +198,13 @@ impl Rv64RelTy {
             return Err(ModuleErr::ENOEXEC);
         }
         // Write the lower 32 bits to the location
-        location.write(address as u32);
+        location.write_unaligned(address as u32);
         Ok(())
     }
 
     fn apply_r_riscv_64_rela(location: Ptr, address: u64) -> Result<()> {
         // Write the full 64 bits to the location
-        location.write(address);
+        location.write_unaligned(address);
         Ok(())
     }
 
@@ -177,8 +216,8 @@ impl Rv64RelTy {
         let imm10_5 = ((offset & 0x7e0) << (30 - 10)) as u32;
         let imm4_1 = ((offset & 0x1e) << (11 - 4)) as u32;
 
-        let original_inst = location.read::<u32>();
-        location.write((original_inst & 0x1fff07f) | imm12 | imm11 | imm10_5 | imm4_1);
+        let original_inst = location.read_unaligned::<u32>();
+        location.write_unaligned((original_inst & 0x1fff07f) | imm12 | imm11 | imm10_5 | imm4_1);
         Ok(())
     }
 
@@ -190,8 +229,8 @@ impl Rv64RelTy {
         let imm11 = ((offset & 0x800) << (20 - 11)) as u32;
         let imm10_1 = ((offset & 0x7fe) << (30 - 10)) as u32;
 
-        let original_inst = location.read::<u32>();
-        location.write((original_inst & 0xFFF) | imm20 | imm19_12 | imm11 | imm10_1);
+        let original_inst = location.read_unaligned::<u32>();
+        location.write_unaligned((original_inst & 0xFFF) | imm20 | imm19_12 | imm11 | imm10_1);
         Ok(())
     }
 
@@ -203,8 +242,8 @@ impl Rv64RelTy {
         let imm4_3 = ((offset & 0x18) << (12 - 5)) as u16;
         let imm2_1 = ((offset & 0x6) << (12 - 10)) as u16;
 
-        let original_inst = location.read::<u16>();
-        location.write((original_inst & 0xe383) | imm8 | imm7_6 | imm5 | imm4_3 | imm2_1);
+        let original_inst = location.read_unaligned::<u16>();
+        location.write_unaligned((original_inst & 0xe383) | imm8 | imm7_6 | imm5 | imm4_3 | imm2_1);
         Ok(())
     }
 
@@ -219,8 +258,8 @@ impl Rv64RelTy {
         let imm4 = ((offset & 0x10) << (12 - 5)) as u16;
         let imm3_1 = ((offset & 0xe) << (12 - 10)) as u16;
 
-        let original_inst = location.read::<u16>();
-        location.write(
+        let original_inst = location.read_unaligned::<u16>();
+        location.write_unaligned(
             (original_inst & 0xe003) | imm11 | imm10 | imm9_8 | imm7 | imm6 | imm5 | imm4 | imm3_1,
         );
         Ok(())
@@ -237,16 +276,16 @@ impl Rv64RelTy {
             return Err(ModuleErr::ENOEXEC);
         }
         let hi20 = (offset + 0x800) & 0xfffff000;
-        let original_inst = location.read::<u32>();
-        location.write((original_inst & 0xfff) | (hi20 as u32));
+        let original_inst = location.read_unaligned::<u32>();
+        location.write_unaligned((original_inst & 0xfff) | (hi20 as u32));
         Ok(())
     }
 
     fn apply_r_riscv_pcrel_lo12_i_rela(location: Ptr, address: u64) -> Result<()> {
         // address is the lo12 value to fill. It is calculated before calling this handler.
 
-        let original_inst = location.read::<u32>();
-        location.write((original_inst & 0xfffff) | ((address as u32 & 0xfff) << 20));
+        let original_inst = location.read_unaligned::<u32>();
+        location.write_unaligned((original_inst & 0xfffff) | ((address as u32 & 0xfff) << 20));
         Ok(())
     }
 
@@ -256,8 +295,8 @@ impl Rv64RelTy {
         let imm11_5 = (address as u32 & 0xfe0) << (31 - 11);
         let imm4_0 = (address as u32 & 0x1f) << (11 - 4);
 
-        let original_inst = location.read::<u32>();
-        location.write((original_inst & 0x1fff07f) | imm11_5 | imm4_0);
+        let original_inst = location.read_unaligned::<u32>();
+        location.write_unaligned((original_inst & 0x1fff07f) | imm11_5 | imm4_0);
         Ok(())
     }
 
@@ -268,8 +307,8 @@ impl Rv64RelTy {
         // Mirror C: ((s32)v + 0x800) & 0xfffff000
         // Do the wrapping add in i32, then mask in u32 to avoid overflowing literal issues.
         let hi20 = ((address32.wrapping_add(0x800)) as u32) & 0xfffff000u32;
-        let original_inst = location.read::<u32>();
-        location.write((original_inst & 0xfff) | hi20);
+        let original_inst = location.read_unaligned::<u32>();
+        location.write_unaligned((original_inst & 0xfff) | hi20);
         Ok(())
     }
 
@@ -279,8 +318,8 @@ impl Rv64RelTy {
         let address = address as i32;
         let hi20 = (address.wrapping_add(0x800)) & (0xfffff000_u32 as i32);
         let lo12 = address.wrapping_sub(hi20);
-        let original_inst = location.read::<u32>();
-        location.write((original_inst & 0xfffff) | ((lo12 as u32 & 0xfff) << 20));
+        let original_inst = location.read_unaligned::<u32>();
+        location.write_unaligned((original_inst & 0xfffff) | ((lo12 as u32 & 0xfff) << 20));
         Ok(())
     }
 
@@ -292,8 +331,8 @@ impl Rv64RelTy {
         let lo12 = address.wrapping_sub(hi20);
         let imm11_5 = (lo12 as u32 & 0xfe0) << (31 - 11);
         let imm4_0 = (lo12 as u32 & 0x1f) << (11 - 4);
-        let original_inst = location.read::<u32>();
-        location.write((original_inst & 0x1fff07f) | imm11_5 | imm4_0);
+        let original_inst = location.read_unaligned::<u32>();
+        location.write_unaligned((original_inst & 0x1fff07f) | imm11_5 | imm4_0);
         Ok(())
     }
 
@@ -323,8 +362,8 @@ impl Rv64RelTy {
         }
 
         let hi20 = offset.wrapping_add(0x800) & 0xfffff000;
-        let original_inst = location.read::<u32>();
-        location.write((original_inst & 0xfff) | (hi20 as u32));
+        let original_inst = location.read_unaligned::<u32>();
+        location.write_unaligned((original_inst & 0xfff) | (hi20 as u32));
         Ok(())
     }
 
@@ -355,11 +394,11 @@ impl Rv64RelTy {
         }
         let hi20 = (offset.wrapping_add(0x800)) & 0xfffff000;
         let lo12 = (offset.wrapping_sub(hi20)) & 0xfff;
-        let original_auipc = location.read::<u32>();
-        location.write((original_auipc & 0xfff) | (hi20 as u32));
+        let original_auipc = location.read_unaligned::<u32>();
+        location.write_unaligned((original_auipc & 0xfff) | (hi20 as u32));
         let original_jalr_ptr = location.add(4);
-        let original_jalr = original_jalr_ptr.read::<u32>();
-        original_jalr_ptr.write((original_jalr & 0xfffff) | ((lo12 as u32) << 20));
+        let original_jalr = original_jalr_ptr.read_unaligned::<u32>();
+        original_jalr_ptr.write_unaligned((original_jalr & 0xfffff) | ((lo12 as u32) << 20));
         Ok(())
     }
 
@@ -375,11 +414,11 @@ impl Rv64RelTy {
         }
         let hi20 = (offset.wrapping_add(0x800)) & 0xfffff000;
         let lo12 = (offset.wrapping_sub(hi20)) & 0xfff;
-        let original_auipc = location.read::<u32>();
-        location.write((original_auipc & 0xfff) | (hi20 as u32));
+        let original_auipc = location.read_unaligned::<u32>();
+        location.write_unaligned((original_auipc & 0xfff) | (hi20 as u32));
         let original_jalr_ptr = location.add(4);
-        let original_jalr = original_jalr_ptr.read::<u32>();
-        original_jalr_ptr.write((original_jalr & 0xfffff) | ((lo12 as u32) << 20));
+        let original_jalr = original_jalr_ptr.read_unaligned::<u32>();
+        original_jalr_ptr.write_unaligned((original_jalr & 0xfffff) | ((lo12 as u32) << 20));
         Ok(())
     }
 
@@ -396,8 +435,8 @@ impl Rv64RelTy {
     }
 
     fn apply_r_riscv_add16_rela(location: Ptr, address: u64) -> Result<()> {
-        let value = location.read::<u16>();
-        location.write(value.wrapping_add(address as u16));
+        let value = location.read_unaligned::<u16>();
+        location.write_unaligned(value.wrapping_add(address as u16));
         Ok(())
     }
 
@@ -409,20 +448,20 @@ impl Rv64RelTy {
     }
 
     fn apply_r_riscv_add32_rela(location: Ptr, address: u64) -> Result<()> {
-        let value = location.read::<u32>();
-        location.write(value.wrapping_add(address as u32));
+        let value = location.read_unaligned::<u32>();
+        location.write_unaligned(value.wrapping_add(address as u32));
         Ok(())
     }
 
     fn apply_r_riscv_add64_rela(location: Ptr, address: u64) -> Result<()> {
-        let value = location.read::<u64>();
-        location.write(value.wrapping_add(address));
+        let value = location.read_unaligned::<u64>();
+        location.write_unaligned(value.wrapping_add(address));
         Ok(())
     }
 
     fn apply_r_riscv_sub16_rela(location: Ptr, address: u64) -> Result<()> {
-        let value = location.read::<u16>();
-        location.write(value.wrapping_sub(address as u16));
+        let value = location.read_unaligned::<u16>();
+        location.write_unaligned(value.wrapping_sub(address as u16));
         Ok(())
     }
 
@@ -441,14 +480,14 @@ impl Rv64RelTy {
     }
 
     fn apply_r_riscv_sub32_rela(location: Ptr, address: u64) -> Result<()> {
-        let value = location.read::<u32>();
-        location.write(value.wrapping_sub(address as u32));
+        let value = location.read_unaligned::<u32>();
+        location.write_unaligned(value.wrapping_sub(address as u32));
         Ok(())
     }
 
     fn apply_r_riscv_sub64_rela(location: Ptr, address: u64) -> Result<()> {
-        let value = location.read::<u64>();
-        location.write(value.wrapping_sub(address));
+        let value = location.read_unaligned::<u64>();
+        location.write_unaligned(value.wrapping_sub(address));
         Ok(())
     }
 
@@ -467,19 +506,19 @@ impl Rv64RelTy {
 
     /// See <https://codebrowser.dev/linux/linux/arch/riscv/kernel/module.c.html#394>
     fn apply_r_riscv_set16_rela(location: Ptr, address: u64) -> Result<()> {
-        location.write(address as u16);
+        location.write_unaligned(address as u16);
         Ok(())
     }
 
     /// See <https://codebrowser.dev/linux/linux/arch/riscv/kernel/module.c.html#401>
     fn apply_r_riscv_set32_rela(location: Ptr, address: u64) -> Result<()> {
-        location.write(address as u32);
+        location.write_unaligned(address as u32);
         Ok(())
     }
 
     /// See <https://codebrowser.dev/linux/linux/arch/riscv/kernel/module.c.html#408>
     fn apply_r_riscv_32_pcrel_rela(location: Ptr, address: u64) -> Result<()> {
-        location.write(address.wrapping_sub(location.0) as u32);
+        location.write_unaligned(address.wrapping_sub(location.0) as u32);
         Ok(())
     }
 
@@ -505,7 +544,7 @@ impl Rv64RelTy {
                 return Err(ModuleErr::EINVAL);
             }
         }
-        location.write(offset as u32);
+        location.write_unaligned(offset as u32);
         Ok(())
     }
 
@@ -519,7 +558,9 @@ impl Rv64RelTy {
         let location = Ptr(location);
         match self {
             Rv64RelTy::R_RISCV_32 => Self::apply_r_riscv_32_rela(location, address),
-            Rv64RelTy::R_RISCV_64 => Self::apply_r_riscv_64_rela(location, address),
+            Rv64RelTy::R_RISCV_64 | Rv64RelTy::R_RISCV_RELATIVE => {
+                Self::apply_r_riscv_64_rela(location, address)
+            }
             Rv64RelTy::R_RISCV_BRANCH => Self::apply_r_riscv_branch_rela(location, address),
             Rv64RelTy::R_RISCV_JAL => Self::apply_r_riscv_jal_rela(location, address),
             Rv64RelTy::R_RISCV_RVC_BRANCH => Self::apply_r_riscv_rvc_branch_rela(location, address),
@@ -570,6 +611,12 @@ impl Rv64RelTy {
 
 type Rv64RelTy = ArchRelocationType;
 
+/// Whether `rel_type` is one of the thread-local storage relocations,
+/// including the TLS descriptor ones this enum does not list
+const fn is_tls_relocation(rel_type: u32) -> bool {
+    matches!(rel_type, 6..=12 | 21 | 22 | 29..=32 | 49 | 50 | 62..=65)
+}
+
 pub struct ArchRelocate;
 
 #[allow(unused_assignments)]
@@ -582,7 +629,7 @@ impl ArchRelocate {
         load_info: &ModuleLoadInfo,
         module: &mut ModuleOwner<H>,
     ) -> Result<()> {
-        for rela in rela_list {
+        for (idx, rela) in rela_list.iter().enumerate() {
             let rel_type = get_rela_type(rela.r_info);
             let sym_idx = get_rela_sym_idx(rela.r_info);
 
@@ -591,18 +638,28 @@ impl ArchRelocate {
                 .sh_addr
                 .wrapping_add(rela.r_offset);
 
-            let reloc_type = ArchRelocationType::try_from(rel_type).map_err(|_| {
+            if is_tls_relocation(rel_type) {
+                reject_tls_relocation(module, rel_section, idx, rel_type)?;
+                continue;
+            }
+            let Ok(reloc_type) = ArchRelocationType::try_from(rel_type) else {
                 log::error!(
-                    "[{:?}]: Invalid relocation type: {}",
+                    "[{:?}]: Invalid relocation type {} at index {}",
                     module.name(),
-                    rel_type
+                    rel_type,
+                    idx
                 );
-                ModuleErr::EINVAL
-            })?;
+                module.reloc_failed(rel_section, idx, rel_type, ModuleErr::EINVAL)?;
+                continue;
+            };
 
             let (sym, sym_name) = &load_info.syms[sym_idx];
 
             let mut target_addr = sym.st_value.wrapping_add(rela.r_addend as u64);
+            if reloc_type == Rv64RelTy::R_RISCV_RELATIVE {
+                target_addr =
+                    relative_load_bias(sechdrs, rel_section).wrapping_add(rela.r_addend as u64);
+            }
 
             if reloc_type == Rv64RelTy::R_RISCV_PCREL_LO12_I
                 || reloc_type == Rv64RelTy::R_RISCV_PCREL_LO12_S
@@ -660,14 +717,21 @@ impl ArchRelocate {
                         module.name(),
                         sym_name
                     );
-                    return Err(ModuleErr::EINVAL);
+                    module.reloc_failed(rel_section, idx, rel_type, ModuleErr::EINVAL)?;
+                    continue;
                 }
             }
             let res = reloc_type.apply_relocation(module, sechdrs, location, target_addr);
             match res {
                 Err(e) => {
-                    log::error!("[{:?}]: ({}) {:?}", module.name(), sym_name, e);
-                    return Err(e);
+                    log::error!(
+                        "[{:?}]: relocation {} against '{}' failed: {:?}",
+                        module.name(),
+                        idx,
+                        sym_name,
+                        e
+                    );
+                    module.reloc_failed(rel_section, idx, rel_type, e)?;
                 }
                 Ok(_) => { /* Successfully applied relocation */ }
             }
@@ -760,3 +824,22 @@ fn emit_plt_entry_func(_address: u64, plt_entry_addr: u64, plt_idx_entry_addr: u
         insn_jr: OPC_JALR | (REG_T1 << 15),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative() {
+        let (word, addr) =
+            crate::arch::tests::relocate_word(Rv64RelTy::R_RISCV_RELATIVE as u32, 0x40);
+        assert_eq!(word, addr + 0x40);
+    }
+
+    #[test]
+    fn test_tls_unsupported() {
+        let err =
+            crate::arch::tests::try_relocate_word(Rv64RelTy::R_RISCV_TLS_TPREL64 as u32, 0).err();
+        assert_eq!(err, Some(ModuleErr::EOPNOTSUPP));
+    }
+}
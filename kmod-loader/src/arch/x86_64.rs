@@ -3,13 +3,55 @@ use int_enum::IntEnum;
 
 use crate::{
     ModuleErr, Result,
-    arch::{Ptr, get_rela_sym_idx, get_rela_type},
+    arch::{
+        Ptr, get_rela_sym_idx, get_rela_type, reject_tls_relocation, relative_load_bias,
+        signed_imm_check, unsigned_imm_check,
+    },
     loader::{KernelModuleHelper, ModuleLoadInfo, ModuleOwner},
 };
 
 #[derive(Debug, Clone, Copy, Default)]
 #[repr(C)]
-pub struct ModuleArchSpecific {}
+pub struct ModuleArchSpecific {
+    got: GotSection,
+}
+
+/// The `.got` section reserved by the linker script, sized in
+/// [`module_frob_arch_sections`] for the GOT-relative relocations that could
+/// not be relaxed.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+struct GotSection {
+    shndx: usize,
+    num_entries: usize,
+    max_entries: usize,
+}
+
+impl ModuleArchSpecific {
+    /// Find or create the GOT slot holding `address`, returning its address.
+    fn emit_got_entry(&mut self, sechdrs: &[SectionHeader], address: u64) -> Option<u64> {
+        let got = &mut self.got;
+        if got.max_entries == 0 {
+            return None;
+        }
+        let entries = unsafe {
+            core::slice::from_raw_parts_mut(sechdrs[got.shndx].sh_addr as *mut u64, got.max_entries)
+        };
+        let idx = match entries[..got.num_entries]
+            .iter()
+            .position(|&entry| entry == address)
+        {
+            Some(idx) => idx,
+            None if got.num_entries < got.max_entries => {
+                entries[got.num_entries] = address;
+                got.num_entries += 1;
+                got.num_entries - 1
+            }
+            None => return None,
+        };
+        Some(&entries[idx] as *const u64 as u64)
+    }
+}
 
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, IntEnum)]
@@ -50,14 +92,127 @@ pub enum ArchRelocationType {
     R_X86_64_PC8 = 15,
     /// Place relative 64-bit signed
     R_X86_64_PC64 = 24,
+    /// Load from 32 bit signed pc relative offset to GOT entry without REX
+    /// prefix, relaxable
+    R_X86_64_GOTPCRELX = 41,
+    /// Load from 32 bit signed pc relative offset to GOT entry with REX
+    /// prefix, relaxable
+    R_X86_64_REX_GOTPCRELX = 42,
 }
 
 type X64RelTy = ArchRelocationType;
 
+/// See [`crate::ModuleLoader::supported_relocations`]; in match order
+pub const SUPPORTED_RELOCATIONS: &[u32] = &[
+    X64RelTy::R_X86_64_NONE as u32,
+    X64RelTy::R_X86_64_64 as u32,
+    X64RelTy::R_X86_64_RELATIVE as u32,
+    X64RelTy::R_X86_64_32 as u32,
+    X64RelTy::R_X86_64_32S as u32,
+    X64RelTy::R_X86_64_PC32 as u32,
+    X64RelTy::R_X86_64_PLT32 as u32,
+    X64RelTy::R_X86_64_GOTPCREL as u32,
+    X64RelTy::R_X86_64_GOTPCRELX as u32,
+    X64RelTy::R_X86_64_REX_GOTPCRELX as u32,
+    X64RelTy::R_X86_64_PC64 as u32,
+];
+
 impl ArchRelocationType {
-    fn apply_relocation(&self, location: u64, mut target_addr: u64) -> Result<()> {
-        let size;
+    const fn is_got_relative(&self) -> bool {
+        matches!(
+            self,
+            X64RelTy::R_X86_64_GOTPCREL
+                | X64RelTy::R_X86_64_GOTPCRELX
+                | X64RelTy::R_X86_64_REX_GOTPCRELX
+        )
+    }
+
+    /// Try to rewrite the GOT load at `location` into direct addressing of
+    /// `target_addr` (S + A), so that no GOT slot is needed. Only `mov` (to
+    /// `lea`), `call` and `jmp` are relaxed, and only when the target is
+    /// within ±2GB of the instruction.
+    ///
+    /// See "Optimize GOTPCRELX Relocations" in the x86-64 psABI.
+    fn relax_gotpcrelx(&self, location: u64, target_addr: u64) -> bool {
+        const MOV: u8 = 0x8b;
+        const LEA: u8 = 0x8d;
+        const INDIRECT: u8 = 0xff;
+        const CALL_MODRM: u8 = 0x15;
+        const JMP_MODRM: u8 = 0x25;
+
+        let location = Ptr(location);
+        let insn = Ptr(location.0 - 2);
+        let (opcode, modrm) = (insn.read::<u8>(), insn.add(1).read::<u8>());
+        let disp = target_addr.wrapping_sub(location.0) as i64;
+        let fits = |disp: i64| disp == disp as i32 as i64;
+        if !fits(disp) || location.as_slice::<u8>(4).iter().any(|&b| b != 0) {
+            return false;
+        }
+        match (self, opcode, modrm) {
+            // mov foo@GOTPCREL(%rip), %reg -> lea foo(%rip), %reg
+            (_, MOV, _) if modrm & 0xc7 == 0x05 => {
+                insn.write::<u8>(LEA);
+                location.write_unaligned::<i32>(disp as i32);
+            }
+            // call *foo@GOTPCREL(%rip) -> addr32 call foo
+            (X64RelTy::R_X86_64_GOTPCRELX, INDIRECT, CALL_MODRM) => {
+                insn.write::<u8>(0x67);
+                insn.add(1).write::<u8>(0xe8);
+                location.write_unaligned::<i32>(disp as i32);
+            }
+            // jmp *foo@GOTPCREL(%rip) -> jmp foo; nop
+            (X64RelTy::R_X86_64_GOTPCRELX, INDIRECT, JMP_MODRM) if fits(disp + 1) => {
+                insn.write::<u8>(0xe9);
+                insn.add(1).write_unaligned::<i32>(disp as i32 + 1);
+                location.add(3).write::<u8>(0x90);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Apply the relocation at `location` against `target_addr`, the symbol
+    /// value without the addend
+    fn apply_relocation(&self, location: u64, mut target_addr: u64, addend: Addend) -> Result<()> {
         let location = Ptr(location);
+        let size = match self {
+            X64RelTy::R_X86_64_NONE => return Ok(()),
+            X64RelTy::R_X86_64_64 | X64RelTy::R_X86_64_RELATIVE | X64RelTy::R_X86_64_PC64 => 8,
+            X64RelTy::R_X86_64_32
+            | X64RelTy::R_X86_64_32S
+            | X64RelTy::R_X86_64_PC32
+            | X64RelTy::R_X86_64_PLT32
+            | X64RelTy::R_X86_64_GOTPCREL
+            | X64RelTy::R_X86_64_GOTPCRELX
+            | X64RelTy::R_X86_64_REX_GOTPCRELX => 4,
+            _ => {
+                log::error!("x86/modules: Unsupported relocation type: {:?}", self);
+                return Err(ModuleErr::ENOEXEC);
+            }
+        };
+        let addend = match addend {
+            Addend::Explicit(addend) => {
+                // if (memcmp(loc, &zero, size))
+                if location.as_slice::<u8>(size).iter().any(|&b| b != 0) {
+                    log::error!(
+                        "x86/modules: Invalid relocation target, existing value is nonzero for type {:?}, loc: {:#x}, value: {:#x}",
+                        self,
+                        location.0,
+                        target_addr.wrapping_add(addend as u64)
+                    );
+                    return Err(ModuleErr::ENOEXEC);
+                }
+                addend
+            }
+            #[cfg(test)]
+            Addend::Implicit => match (self, size) {
+                (X64RelTy::R_X86_64_32, _) => location.read_unaligned::<u32>() as i64,
+                (_, 4) => location.read_unaligned::<i32>() as i64,
+                _ => location.read_unaligned::<i64>(),
+            },
+        };
+        target_addr = target_addr.wrapping_add(addend as u64);
+
         let overflow = || {
             log::error!(
                 "overflow in relocation type {:?}, target address {:#x}",
@@ -68,59 +223,50 @@ impl ArchRelocationType {
             ModuleErr::ENOEXEC
         };
         match self {
-            X64RelTy::R_X86_64_NONE => return Ok(()),
-            X64RelTy::R_X86_64_64 => {
-                size = 8;
-            }
-            X64RelTy::R_X86_64_32 => {
-                if target_addr != target_addr as u32 as u64 {
-                    return Err(overflow());
-                }
-                size = 4;
+            X64RelTy::R_X86_64_32 if !unsigned_imm_check(target_addr, 32) => {
+                return Err(overflow());
             }
-            X64RelTy::R_X86_64_32S => {
-                // Check if the value fits in a signed 32-bit integer
-                // C code: if ((s64)val != *(s32 *)&val) goto overflow;
-                // This checks: i64_value != sign_extend(low_32_bits_as_i32)
-                if (target_addr as i64) != ((target_addr as i32) as i64) {
-                    return Err(overflow());
-                }
-                size = 4;
-            }
-            X64RelTy::R_X86_64_PC32 | X64RelTy::R_X86_64_PLT32 => {
-                target_addr = target_addr.wrapping_sub(location.0);
-                size = 4;
+            // C code: if ((s64)val != *(s32 *)&val) goto overflow;
+            X64RelTy::R_X86_64_32S if !signed_imm_check(target_addr as i64, 32) => {
+                return Err(overflow());
             }
-            X64RelTy::R_X86_64_PC64 => {
+            X64RelTy::R_X86_64_PC32
+            | X64RelTy::R_X86_64_PLT32
+            | X64RelTy::R_X86_64_GOTPCREL
+            | X64RelTy::R_X86_64_GOTPCRELX
+            | X64RelTy::R_X86_64_REX_GOTPCRELX
+            | X64RelTy::R_X86_64_PC64 => {
                 target_addr = target_addr.wrapping_sub(location.0);
-                size = 8;
-            }
-            _ => {
-                log::error!("x86/modules: Unsupported relocation type: {:?}", self);
-                return Err(ModuleErr::ENOEXEC);
             }
+            _ => {}
         }
-        // if (memcmp(loc, &zero, size))
-        if location.as_slice::<u8>(size).iter().any(|&b| b != 0) {
-            log::error!(
-                "x86/modules: Invalid relocation target, existing value is nonzero for type {:?}, loc: {:#x}, value: {:#x}",
-                self,
-                location.0,
-                target_addr
-            );
-            return Err(ModuleErr::ENOEXEC);
-        } else {
-            // Write the relocated value
-            match size {
-                4 => location.write::<u32>(target_addr as u32),
-                8 => location.write::<u64>(target_addr),
-                _ => unreachable!(),
-            }
+        // Write the relocated value
+        match size {
+            4 => location.write_unaligned::<u32>(target_addr as u32),
+            8 => location.write_unaligned::<u64>(target_addr),
+            _ => unreachable!(),
         }
         Ok(())
     }
 }
 
+/// Where the addend of a relocation is kept
+#[derive(Debug, Clone, Copy)]
+enum Addend {
+    /// In `r_addend`, as in `SHT_RELA` sections. The location must be zero.
+    Explicit(i64),
+    /// In the location itself, as in `SHT_REL` sections. Only tests use it
+    /// until the loader applies `SHT_REL` sections.
+    #[cfg(test)]
+    Implicit,
+}
+
+/// Whether `rel_type` is one of the thread-local storage relocations,
+/// `R_X86_64_DTPMOD64` to `R_X86_64_TPOFF32` and the TLS descriptor ones
+const fn is_tls_relocation(rel_type: u32) -> bool {
+    matches!(rel_type, 16..=23 | 34..=36)
+}
+
 pub struct ArchRelocate;
 
 #[allow(unused_assignments)]
@@ -131,9 +277,9 @@ impl ArchRelocate {
         rel_section: &SectionHeader,
         sechdrs: &[SectionHeader],
         load_info: &ModuleLoadInfo,
-        module: &ModuleOwner<H>,
+        module: &mut ModuleOwner<H>,
     ) -> Result<()> {
-        for rela in rela_list {
+        for (idx, rela) in rela_list.iter().enumerate() {
             let rel_type = get_rela_type(rela.r_info);
             let sym_idx = get_rela_sym_idx(rela.r_info);
 
@@ -141,30 +287,79 @@ impl ArchRelocate {
             let location = sechdrs[rel_section.sh_info as usize].sh_addr + rela.r_offset;
             let (sym, sym_name) = &load_info.syms[sym_idx];
 
-            let reloc_type = ArchRelocationType::try_from(rel_type).map_err(|_| {
+            if is_tls_relocation(rel_type) {
+                reject_tls_relocation(module, rel_section, idx, rel_type)?;
+                continue;
+            }
+            let Ok(reloc_type) = ArchRelocationType::try_from(rel_type) else {
                 log::error!(
-                    "[{:?}]: Invalid relocation type: {}",
+                    "[{:?}]: Invalid relocation type {} at index {}",
                     module.name(),
-                    rel_type
+                    rel_type,
+                    idx
                 );
-                ModuleErr::ENOEXEC
-            })?;
+                module.reloc_failed(rel_section, idx, rel_type, ModuleErr::ENOEXEC)?;
+                continue;
+            };
+
+            let mut target_addr = sym.st_value;
+            if matches!(reloc_type, X64RelTy::R_X86_64_RELATIVE) {
+                target_addr = relative_load_bias(sechdrs, rel_section);
+            }
 
-            let target_addr = sym.st_value.wrapping_add(rela.r_addend as u64);
+            if reloc_type.is_got_relative() {
+                let relaxable = matches!(
+                    reloc_type,
+                    X64RelTy::R_X86_64_GOTPCRELX | X64RelTy::R_X86_64_REX_GOTPCRELX
+                );
+                if relaxable
+                    && rela.r_offset >= 2
+                    && reloc_type
+                        .relax_gotpcrelx(location, target_addr.wrapping_add(rela.r_addend as u64))
+                {
+                    log::info!(
+                        "[{:?}]: Relaxed {:?} against '{}' at location {:#x}",
+                        module.name(),
+                        reloc_type,
+                        sym_name,
+                        location
+                    );
+                    continue;
+                }
+                // Fall back to loading the address from a GOT slot: G + A - P
+                let Some(got) = module.arch.emit_got_entry(sechdrs, sym.st_value) else {
+                    log::error!(
+                        "[{:?}]: No GOT entry available for '{}' ({:?})",
+                        module.name(),
+                        sym_name,
+                        reloc_type
+                    );
+                    module.reloc_failed(rel_section, idx, rel_type, ModuleErr::ENOEXEC)?;
+                    continue;
+                };
+                target_addr = got;
+            }
 
             log::info!(
                 "[{:?}]: Applying relocation {:?} at location {:#x} with target addr {:#x}",
                 module.name(),
                 reloc_type,
                 location,
-                target_addr
+                target_addr.wrapping_add(rela.r_addend as u64)
             );
 
-            let res = reloc_type.apply_relocation(location, target_addr);
+            let res =
+                reloc_type.apply_relocation(location, target_addr, Addend::Explicit(rela.r_addend));
             match res {
                 Err(e) => {
-                    log::error!("[{:?}]: '{}' {:?}", module.name(), sym_name, e);
-                    return Err(e);
+                    log::error!(
+                        "[{:?}]: relocation {} against '{}' failed: {:?}",
+                        module.name(),
+                        idx,
+                        sym_name,
+                        e
+                    );
+                    module.reloc_failed(rel_section, idx, rel_type, e)?;
                 }
                 Ok(_) => { /* Successfully applied relocation */ }
             }
@@ -173,9 +368,166 @@ impl ArchRelocate {
     }
 }
 
+/// Size the `.got` section for the GOT-relative relocations of the module.
+/// Relocations that get relaxed at apply time leave their slot unused.
 pub fn module_frob_arch_sections<H: KernelModuleHelper>(
     elf: &mut Elf,
     owner: &mut ModuleOwner<H>,
 ) -> Result<()> {
+    let mut num_gots = 0;
+    for (idx, rela_sec) in elf.shdr_relocs.iter() {
+        let shdr = &elf.section_headers[*idx];
+        if shdr.sh_type != goblin::elf::section_header::SHT_RELA {
+            continue;
+        }
+        num_gots += rela_sec
+            .iter()
+            .filter(|rela| X64RelTy::try_from(rela.r_type).is_ok_and(|ty| ty.is_got_relative()))
+            .count();
+    }
+    if num_gots == 0 {
+        return Ok(());
+    }
+
+    let Some(got_idx) = elf
+        .section_headers
+        .iter()
+        .position(|shdr| elf.shdr_strtab.get_at(shdr.sh_name) == Some(".got"))
+    else {
+        log::error!("{:?}: module .GOT section(s) missing", owner.name());
+        return Err(ModuleErr::ENOEXEC);
+    };
+    log::info!("[{:?}]: Need up to {} GOT entries", owner.name(), num_gots);
+
+    let got_sec = &mut elf.section_headers[got_idx];
+    got_sec.sh_type = goblin::elf::section_header::SHT_NOBITS;
+    got_sec.sh_flags = goblin::elf::section_header::SHF_ALLOC as u64;
+    got_sec.sh_addralign = 64;
+    got_sec.sh_size = (num_gots as u64 + 1) * size_of::<u64>() as u64;
+    owner.arch.got = GotSection {
+        shndx: got_idx,
+        num_entries: 0,
+        max_entries: num_gots,
+    };
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `mov foo@GOTPCREL(%rip), %rax` followed by padding
+    const MOV_GOTPCREL: [u8; 8] = [0x48, 0x8b, 0x05, 0, 0, 0, 0, 0xcc];
+
+    #[test]
+    fn test_relax_gotpcrelx() {
+        let mut code = MOV_GOTPCREL;
+        let location = code.as_ptr() as u64 + 3;
+        let target = location + 0x1000;
+        assert!(X64RelTy::R_X86_64_REX_GOTPCRELX.relax_gotpcrelx(location, target - 4));
+        // lea foo(%rip), %rax
+        assert_eq!(code[..3], [0x48, 0x8d, 0x05]);
+        assert_eq!(
+            i32::from_le_bytes(code[3..7].try_into().unwrap()),
+            0x1000 - 4
+        );
+
+        // call *foo@GOTPCREL(%rip) -> addr32 call foo
+        let mut code = [0xff, 0x15, 0, 0, 0, 0];
+        let location = code.as_ptr() as u64 + 2;
+        assert!(X64RelTy::R_X86_64_GOTPCRELX.relax_gotpcrelx(location, location + 0x100));
+        assert_eq!(code[..2], [0x67, 0xe8]);
+        assert_eq!(i32::from_le_bytes(code[2..].try_into().unwrap()), 0x100);
+    }
+
+    #[test]
+    fn test_gotpcrelx_out_of_range_keeps_got() {
+        let mut code = MOV_GOTPCREL;
+        let location = code.as_ptr() as u64 + 3;
+        let far = location.wrapping_add(1 << 40);
+        assert!(!X64RelTy::R_X86_64_REX_GOTPCRELX.relax_gotpcrelx(location, far));
+        assert_eq!(code, MOV_GOTPCREL);
+
+        // The load goes through a GOT slot holding the far address instead
+        let mut got = [0u64; 2];
+        let sechdrs = [
+            SectionHeader::default(),
+            SectionHeader {
+                sh_addr: got.as_mut_ptr() as u64,
+                ..Default::default()
+            },
+        ];
+        let mut arch = ModuleArchSpecific {
+            got: GotSection {
+                shndx: 1,
+                num_entries: 0,
+                max_entries: 2,
+            },
+        };
+        let slot = arch.emit_got_entry(&sechdrs, far).unwrap();
+        assert_eq!(slot, got.as_ptr() as u64);
+        assert_eq!(arch.emit_got_entry(&sechdrs, far), Some(slot));
+        X64RelTy::R_X86_64_REX_GOTPCRELX
+            .apply_relocation(location, slot, Addend::Explicit(-4))
+            .unwrap();
+        // Still an indirect mov, now pointing at the slot
+        assert_eq!(code[..3], MOV_GOTPCREL[..3]);
+        let disp = i32::from_le_bytes(code[3..7].try_into().unwrap()) as i64;
+        assert_eq!((location as i64 + 4 + disp) as u64, slot);
+        assert_eq!(got[0], far);
+    }
+
+    #[test]
+    fn test_relative() {
+        let (word, addr) =
+            crate::arch::tests::relocate_word(X64RelTy::R_X86_64_RELATIVE as u32, 0x40);
+        assert_eq!(word, addr + 0x40);
+    }
+
+    #[test]
+    fn test_implicit_addend() {
+        let cases = [
+            (X64RelTy::R_X86_64_64, 0x40),
+            (X64RelTy::R_X86_64_PC64, -8),
+            (X64RelTy::R_X86_64_32, 0x40),
+            (X64RelTy::R_X86_64_32S, -8),
+            (X64RelTy::R_X86_64_PC32, -4),
+        ];
+        let mut word = 0u64;
+        let location = &raw mut word as u64;
+        for (ty, addend) in cases {
+            let target = match ty {
+                X64RelTy::R_X86_64_32 | X64RelTy::R_X86_64_32S => 0x1000_0000,
+                _ => location + 0x1000,
+            };
+            word = 0;
+            ty.apply_relocation(location, target, Addend::Explicit(addend))
+                .unwrap();
+            let rela = word;
+
+            word = match ty {
+                X64RelTy::R_X86_64_64 | X64RelTy::R_X86_64_PC64 => addend as u64,
+                _ => addend as u32 as u64,
+            };
+            ty.apply_relocation(location, target, Addend::Implicit)
+                .unwrap();
+            assert_eq!(word, rela, "{ty:?}");
+        }
+
+        // RELA needs a zeroed location, REL reads the addend from it
+        word = 0x40;
+        let err = X64RelTy::R_X86_64_64.apply_relocation(location, 0x1000, Addend::Explicit(0));
+        assert_eq!(err, Err(ModuleErr::ENOEXEC));
+        X64RelTy::R_X86_64_64
+            .apply_relocation(location, 0x1000, Addend::Implicit)
+            .unwrap();
+        assert_eq!(word, 0x1040);
+    }
+
+    #[test]
+    fn test_tls_unsupported() {
+        // R_X86_64_TPOFF64
+        let err = crate::arch::tests::try_relocate_word(18, 0).err();
+        assert_eq!(err, Some(ModuleErr::EOPNOTSUPP));
+    }
+}
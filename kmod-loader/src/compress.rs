@@ -0,0 +1,214 @@
+//! Decompression of compressed module images (`.ko.gz`, `.ko.xz`)
+//!
+//! See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/decompress.c>
+
+use alloc::vec::Vec;
+
+use crate::{ModuleErr, Result};
+
+#[cfg(feature = "gzip")]
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+#[cfg(feature = "xz")]
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// The compression format of a module image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionFormat {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "xz")]
+    Xz,
+}
+
+impl CompressionFormat {
+    /// Detect the format from the magic bytes at the start of `data`
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        #[cfg(feature = "gzip")]
+        if data.starts_with(GZIP_MAGIC) {
+            return Some(CompressionFormat::Gzip);
+        }
+        #[cfg(feature = "xz")]
+        if data.starts_with(XZ_MAGIC) {
+            return Some(CompressionFormat::Xz);
+        }
+        None
+    }
+}
+
+/// Decompress a module image. If `format` is `None`, it is detected from the
+/// magic bytes.
+pub fn decompress(data: &[u8], format: Option<CompressionFormat>) -> Result<Vec<u8>> {
+    let Some(format) = format.or_else(|| CompressionFormat::detect(data)) else {
+        log::error!("Unknown module compression format");
+        return Err(ModuleErr::ENOEXEC);
+    };
+    match format {
+        #[cfg(feature = "gzip")]
+        CompressionFormat::Gzip => gunzip(data),
+        #[cfg(feature = "xz")]
+        CompressionFormat::Xz => unxz(data),
+    }
+}
+
+/// Length of the gzip member header, or `None` if it is malformed.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc1952#section-2.3>
+#[cfg(feature = "gzip")]
+fn gzip_header_len(data: &[u8]) -> Option<usize> {
+    const FHCRC: u8 = 1 << 1;
+    const FEXTRA: u8 = 1 << 2;
+    const FNAME: u8 = 1 << 3;
+    const FCOMMENT: u8 = 1 << 4;
+    const DEFLATE: u8 = 8;
+
+    // magic, method, flags, mtime, xfl, os
+    if data.len() < 10 || !data.starts_with(GZIP_MAGIC) || data[2] != DEFLATE {
+        return None;
+    }
+    let flags = data[3];
+    let mut len = 10;
+    if flags & FEXTRA != 0 {
+        let xlen = u16::from_le_bytes([*data.get(len)?, *data.get(len + 1)?]);
+        len += 2 + xlen as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            len += data.get(len..)?.iter().position(|&b| b == 0)? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        len += 2;
+    }
+    (len <= data.len()).then_some(len)
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip(data: &[u8]) -> Result<Vec<u8>> {
+    let Some(header_len) = gzip_header_len(data) else {
+        log::error!("Invalid gzip header");
+        return Err(ModuleErr::ENOEXEC);
+    };
+    let out = miniz_oxide::inflate::decompress_to_vec(&data[header_len..]).map_err(|err| {
+        log::error!("Failed to decompress gzip module: {:?}", err.status);
+        ModuleErr::ENOEXEC
+    })?;
+    // The trailer ends with the uncompressed size modulo 2^32
+    let isize = data
+        .last_chunk::<4>()
+        .map(|trailer| u32::from_le_bytes(*trailer));
+    if isize != Some(out.len() as u32) {
+        log::error!("Truncated gzip module: size mismatch");
+        return Err(ModuleErr::ENOEXEC);
+    }
+    Ok(out)
+}
+
+#[cfg(feature = "xz")]
+fn unxz(data: &[u8]) -> Result<Vec<u8>> {
+    use lzma_rust2::Read;
+
+    let mut reader = lzma_rust2::XzReader::new(data, false);
+    let mut out = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = reader.read(&mut buf).map_err(|err| {
+            log::error!("Failed to decompress xz module: {:?}", err);
+            ModuleErr::ENOEXEC
+        })?;
+        if n == 0 {
+            break;
+        }
+        out.extend_from_slice(&buf[..n]);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "gzip")]
+    fn gzip(data: &[u8], name: &str) -> Vec<u8> {
+        const FNAME: u8 = 1 << 3;
+        let mut out = alloc::vec![0x1f, 0x8b, 8, FNAME, 0, 0, 0, 0, 0, 3];
+        out.extend_from_slice(name.as_bytes());
+        out.push(0);
+        out.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(data, 6));
+        // CRC32 (not checked) and ISIZE
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out
+    }
+
+    #[cfg(feature = "xz")]
+    fn xz(data: &[u8]) -> Vec<u8> {
+        use lzma_rust2::Write;
+
+        let mut writer = lzma_rust2::XzWriter::new(Vec::new(), Default::default()).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert_eq!(
+            decompress(b"\x7fELF", None).unwrap_err(),
+            ModuleErr::ENOEXEC
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gunzip() {
+        let data = b"\x7fELF module image ".repeat(64);
+        let compressed = gzip(&data, "hello.ko");
+        assert_eq!(
+            CompressionFormat::detect(&compressed),
+            Some(CompressionFormat::Gzip)
+        );
+        assert_eq!(decompress(&compressed, None).unwrap(), data);
+
+        let truncated = &compressed[..compressed.len() - 6];
+        assert_eq!(
+            decompress(truncated, Some(CompressionFormat::Gzip)).unwrap_err(),
+            ModuleErr::ENOEXEC
+        );
+    }
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn test_unxz() {
+        let data = b"\x7fELF module image ".repeat(64);
+        let compressed = xz(&data);
+        assert_eq!(
+            CompressionFormat::detect(&compressed),
+            Some(CompressionFormat::Xz)
+        );
+        assert_eq!(decompress(&compressed, None).unwrap(), data);
+    }
+
+    #[cfg(all(feature = "gzip", target_arch = "x86_64"))]
+    #[test]
+    fn test_loader_from_compressed() {
+        use alloc::ffi::CString;
+
+        use crate::{ModuleLoader, TestHelper, testing::HELLO};
+
+        extern "C" fn stub() {}
+
+        let elf = goblin::elf::Elf::parse(HELLO).unwrap();
+        for sym in elf.syms.iter().filter(|sym| sym.is_import()) {
+            let name = elf.strtab.get_at(sym.st_name).unwrap();
+            TestHelper::register_symbol(name, stub as *const () as usize);
+        }
+
+        let compressed = gzip(HELLO, "hello.ko");
+        let mut buf = Vec::new();
+        let owner = ModuleLoader::<TestHelper>::from_compressed(&compressed, None, &mut buf)
+            .unwrap()
+            .load_module(CString::default())
+            .unwrap();
+        assert_eq!(owner.name(), "hello");
+        assert_eq!(buf, HELLO);
+    }
+}
@@ -0,0 +1,95 @@
+use alloc::ffi::CString;
+use core::ops::Deref;
+
+use crate::{
+    Result,
+    loader::{KernelModuleHelper, ModuleLoader, ModuleOwner},
+};
+
+/// A module whose init function has run successfully. Dropping the handle
+/// runs the module's exit function and releases its memory.
+pub struct ModuleHandle<H: KernelModuleHelper> {
+    owner: ModuleOwner<H>,
+}
+
+impl<H: KernelModuleHelper> ModuleHandle<H> {
+    /// Load the module in `elf_data` with `args` and run its init function
+    pub fn load_and_init(elf_data: &[u8], args: CString) -> Result<Self> {
+        let owner = ModuleLoader::<H>::new(elf_data)?.load_module(args)?;
+        Self::init(owner)
+    }
+
+    /// Run the init function of a loaded module. If it fails, the module is
+    /// released without calling its exit function and the error it returned
+    /// is passed on.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/main.c#L2507>
+    pub fn init(mut owner: ModuleOwner<H>) -> Result<Self> {
+        let ret = owner.call_init()?;
+        if ret != 0 {
+            log::error!("[{}]: init function failed with {}", owner.name(), ret);
+            owner.release_failed();
+            return Err(kmod_tools::errno_to_err(ret));
+        }
+        Ok(ModuleHandle { owner })
+    }
+}
+
+impl<H: KernelModuleHelper> Deref for ModuleHandle<H> {
+    type Target = ModuleOwner<H>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.owner
+    }
+}
+
+impl<H: KernelModuleHelper> Drop for ModuleHandle<H> {
+    fn drop(&mut self) {
+        self.owner.unload();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        ffi::c_int,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{ModuleErr, TestHelper};
+
+    static INITS: AtomicUsize = AtomicUsize::new(0);
+    static EXITS: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C" fn init_ok() -> c_int {
+        INITS.fetch_add(1, Ordering::SeqCst);
+        0
+    }
+
+    unsafe extern "C" fn init_fail() -> c_int {
+        -(ModuleErr::ENODEV as c_int)
+    }
+
+    unsafe extern "C" fn exit() {
+        EXITS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_module_handle_lifecycle() {
+        let owner = ModuleOwner::<TestHelper>::with_fns(init_ok, exit);
+        let handle = ModuleHandle::init(owner).unwrap();
+        assert_eq!(INITS.load(Ordering::SeqCst), 1);
+        assert_eq!(EXITS.load(Ordering::SeqCst), 0);
+        assert_eq!(handle.state(), kmod_tools::ModuleState::Live);
+
+        drop(handle);
+        assert_eq!(INITS.load(Ordering::SeqCst), 1);
+        assert_eq!(EXITS.load(Ordering::SeqCst), 1);
+
+        // A failed init returns its errno and never runs exit
+        let owner = ModuleOwner::<TestHelper>::with_fns(init_fail, exit);
+        assert_eq!(ModuleHandle::init(owner).err(), Some(ModuleErr::ENODEV));
+        assert_eq!(EXITS.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,210 @@
+//! A [`KernelModuleHelper`] for host-side tools, backed by anonymous mappings
+//! whose permissions are set with `mprotect`
+
+extern crate std;
+
+use alloc::boxed::Box;
+
+use memmap2::MmapMut;
+
+use crate::{KernelModuleHelper, SectionMemOps, SectionPerm, symbol_map};
+
+/// A [`KernelModuleHelper`] that maps every section with `mmap` and applies
+/// [`SectionPerm`] with `mprotect`, so the text of a loaded module is really
+/// executable and its read-only data really read-only.
+///
+/// Symbols are resolved from a process-wide map filled with
+/// [`StdHelper::register_symbol`]. Sections both writable and executable are
+/// not supported and fail the load, as does any `mprotect` error.
+pub struct StdHelper;
+
+impl StdHelper {
+    /// Make `name` resolve to `addr`
+    pub fn register_symbol(name: &str, addr: usize) {
+        symbol_map::register(name, addr);
+    }
+
+    /// Address that symbols missing from the map resolve to. With `None`
+    /// (the default) they fail to resolve.
+    pub fn set_default_symbol(addr: Option<usize>) {
+        symbol_map::set_default(addr);
+    }
+}
+
+impl KernelModuleHelper for StdHelper {
+    fn vmalloc(size: usize) -> Option<Box<dyn SectionMemOps>> {
+        let map = MmapMut::map_anon(size)
+            .inspect_err(|err| log::error!("Failed to map {size} bytes: {err}"))
+            .ok()?;
+        Some(Box::new(StdMem { map }))
+    }
+
+    fn resolve_symbol(name: &str) -> Option<usize> {
+        symbol_map::resolve(name)
+    }
+}
+
+struct StdMem {
+    map: MmapMut,
+}
+
+impl SectionMemOps for StdMem {
+    fn as_ptr(&self) -> *const u8 {
+        self.map.as_ptr()
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.map.as_mut_ptr()
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// On failure the region is left readable and writable
+    fn change_perms(&mut self, perms: SectionPerm) -> bool {
+        let ok = if perms.contains(SectionPerm::WRITE | SectionPerm::EXECUTE) {
+            log::error!("StdHelper does not support {perms} mappings");
+            false
+        } else {
+            self.protect(prot_flags(perms))
+                .inspect_err(|err| log::error!("Failed to change permissions to {perms}: {err}"))
+                .is_ok()
+        };
+        if !ok {
+            let _ = self
+                .protect(libc::PROT_READ | libc::PROT_WRITE)
+                .inspect_err(|err| log::error!("Failed to restore permissions: {err}"));
+        }
+        ok
+    }
+}
+
+impl StdMem {
+    fn protect(&mut self, prot: libc::c_int) -> std::io::Result<()> {
+        let ret = unsafe { libc::mprotect(self.map.as_mut_ptr().cast(), self.map.len(), prot) };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+}
+
+/// The `PROT_*` flags for `perms`
+fn prot_flags(perms: SectionPerm) -> libc::c_int {
+    let mut prot = libc::PROT_NONE;
+    if perms.contains(SectionPerm::READ) {
+        prot |= libc::PROT_READ;
+    }
+    if perms.contains(SectionPerm::WRITE) {
+        prot |= libc::PROT_WRITE;
+    }
+    if perms.contains(SectionPerm::EXECUTE) {
+        prot |= libc::PROT_EXEC;
+    }
+    prot
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use super::*;
+
+    /// The permission column of the `/proc/self/maps` line covering `addr`
+    #[cfg(target_os = "linux")]
+    fn mapped_perms(addr: usize) -> String {
+        let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+        maps.lines()
+            .find_map(|line| {
+                let (range, rest) = line.split_once(' ')?;
+                let (start, end) = range.split_once('-')?;
+                let start = usize::from_str_radix(start, 16).ok()?;
+                let end = usize::from_str_radix(end, 16).ok()?;
+                (start..end).contains(&addr).then(|| rest[..3].to_string())
+            })
+            .unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_change_perms() {
+        let mut mem = StdHelper::vmalloc(4096).unwrap();
+        let addr = mem.as_ptr() as usize;
+        assert_eq!(mapped_perms(addr), "rw-");
+        assert!(mem.change_perms(SectionPerm::READ | SectionPerm::EXECUTE));
+        assert_eq!(mapped_perms(addr), "r-x");
+        assert!(mem.change_perms(SectionPerm::READ));
+        assert_eq!(mapped_perms(addr), "r--");
+        assert!(mem.change_perms(SectionPerm::READ | SectionPerm::WRITE));
+        assert_eq!(mapped_perms(addr), "rw-");
+
+        // A refused change leaves the region writable
+        assert!(mem.change_perms(SectionPerm::READ | SectionPerm::EXECUTE));
+        assert!(!mem.change_perms(SectionPerm::all()));
+        assert_eq!(mapped_perms(addr), "rw-");
+
+        assert!(mem.change_perms(SectionPerm::empty()));
+        assert_eq!(mapped_perms(addr), "---");
+    }
+
+    /// Whether writing to `ptr` kills a forked child with `SIGSEGV`
+    #[cfg(target_os = "linux")]
+    fn write_faults(ptr: *mut u8) -> bool {
+        unsafe {
+            let pid = libc::fork();
+            assert!(pid >= 0);
+            if pid == 0 {
+                // Only async-signal-safe calls between fork and _exit
+                ptr.write_volatile(0xc3);
+                libc::_exit(0);
+            }
+            let mut status = 0;
+            assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+            libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGSEGV
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_write_to_text_faults() {
+        let mut mem = StdHelper::vmalloc(4096).unwrap();
+        let ptr = mem.as_mut_ptr();
+        assert!(!write_faults(ptr));
+        assert!(mem.change_perms(SectionPerm::READ | SectionPerm::EXECUTE));
+        assert!(write_faults(ptr));
+        assert!(mem.change_perms(SectionPerm::READ | SectionPerm::WRITE));
+        assert!(!write_faults(ptr));
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn test_load_hello_module() {
+        use alloc::ffi::CString;
+
+        use crate::{ModuleLoader, testing::HELLO};
+
+        extern "C" fn stub() {}
+
+        let elf = goblin::elf::Elf::parse(HELLO).unwrap();
+        for sym in elf.syms.iter().filter(|sym| sym.is_import()) {
+            let name = elf.strtab.get_at(sym.st_name).unwrap();
+            StdHelper::register_symbol(name, stub as *const () as usize);
+        }
+
+        let owner = ModuleLoader::<StdHelper>::new(HELLO)
+            .unwrap()
+            .load_module(CString::default())
+            .unwrap();
+        assert_eq!(owner.name(), "hello");
+        for entry in owner.memory_map() {
+            let expected = match entry.perms {
+                perms if perms.contains(SectionPerm::EXECUTE) => "r-x",
+                perms if perms.contains(SectionPerm::WRITE) => "rw-",
+                _ => "r--",
+            };
+            assert_eq!(mapped_perms(entry.base), expected, "{entry}");
+        }
+    }
+}
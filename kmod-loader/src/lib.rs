@@ -1,15 +1,97 @@
 #![no_std]
 #![allow(unsafe_op_in_unsafe_fn)]
 mod arch;
+#[cfg(any(feature = "gzip", feature = "xz"))]
+mod compress;
+mod handle;
+#[cfg(all(feature = "std", unix))]
+mod host;
 mod loader;
 mod module;
 mod param;
+mod registry;
+mod signature;
+#[cfg(any(test, feature = "std"))]
+mod symbol_map;
+#[cfg(any(test, feature = "std"))]
+mod testing;
+mod version;
 extern crate alloc;
-pub use arch::ArchRelocationType;
+pub use arch::{ArchRelocationType, SUPPORTED_RELOCATIONS};
 use ax_errno::{LinuxError, LinuxResult};
-pub use loader::{KernelModuleHelper, ModuleLoader, ModuleOwner, SectionMemOps, SectionPerm};
+#[cfg(any(feature = "gzip", feature = "xz"))]
+pub use compress::{CompressionFormat, decompress};
+pub use handle::ModuleHandle;
+#[cfg(all(feature = "std", unix))]
+pub use host::StdHelper;
+pub use loader::{
+    KernelModuleHelper, LoadPhase, LoadPipeline, ModuleLoadInfo, ModuleLoader, ModuleOwner,
+    ModuleSymbol, RelocError, ResolvedSymbol, SectionMapEntry, SectionMemOps, SectionPerm,
+    SymbolLicense,
+};
 #[doc(hidden)]
 pub use paste;
+pub use registry::ModuleRegistry;
+#[cfg(any(test, feature = "std"))]
+pub use testing::TestHelper;
 
 type Result<T> = LinuxResult<T>;
 type ModuleErr = LinuxError;
+
+/// The name of a symbol as presented in logs and error messages: Rust
+/// symbols are demangled into their path without the hash, other names are
+/// returned unchanged. Without the `demangle` feature every name is returned
+/// unchanged.
+pub fn demangle_sym(name: &str) -> alloc::string::String {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "demangle")] {
+            alloc::format!("{:#}", rustc_demangle::demangle(name))
+        } else {
+            alloc::string::ToString::to_string(name)
+        }
+    }
+}
+
+/// The address of the instruction that traps for a bug table entry, as
+/// `bug_addr()` computes it: `bug_addr_disp` is relative to the field itself,
+/// so `bug` must be an entry of the relocated table, e.g. one from
+/// [`ModuleOwner::bug_entries`].
+pub fn bug_addr(bug: &kmod_tools::kbindings::bug_entry) -> usize {
+    (&raw const bug.bug_addr_disp as usize).wrapping_add_signed(bug.bug_addr_disp as isize)
+}
+
+/// The address of the code of a static branch site, as `jump_entry_code()`
+/// computes it from an entry of the relocated `__jump_table`, e.g. one handed
+/// to [`KernelModuleHelper::patch_jump_entry`]
+pub fn jump_entry_code(entry: &kmod_tools::kbindings::jump_entry) -> usize {
+    (&raw const entry.code as usize).wrapping_add_signed(entry.code as isize)
+}
+
+/// The address a static branch site jumps to when enabled, as
+/// `jump_entry_target()` computes it
+pub fn jump_entry_target(entry: &kmod_tools::kbindings::jump_entry) -> usize {
+    (&raw const entry.target as usize).wrapping_add_signed(entry.target as isize)
+}
+
+/// The address of the `static_key` of a static branch site, as
+/// `jump_entry_key()` computes it, without the branch and init flags kept in
+/// the low bits
+pub fn jump_entry_key(entry: &kmod_tools::kbindings::jump_entry) -> usize {
+    (&raw const entry.key as usize).wrapping_add_signed(entry.key as isize) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demangle_sym() {
+        const INIT: &str = "_ZN5hello4init17h0123456789abcdefE";
+        if cfg!(feature = "demangle") {
+            assert_eq!(demangle_sym(INIT), "hello::init");
+        } else {
+            assert_eq!(demangle_sym(INIT), INIT);
+        }
+        assert_eq!(demangle_sym("printk"), "printk");
+    }
+}
@@ -1,16 +1,22 @@
 use alloc::{
     boxed::Box,
+    collections::BTreeMap,
     ffi::CString,
     string::{String, ToString},
     vec::Vec,
 };
-use core::{ffi::CStr, fmt::Display};
+use core::fmt::Display;
 
 use bitflags::bitflags;
-use goblin::elf::{Elf, SectionHeader};
-use kmod_tools::Module;
+use goblin::{
+    elf::{Elf, SectionHeader},
+    elf64::reloc::Rela,
+};
+use kmod_tools::{Module, ModuleState};
 
-use crate::{ModuleErr, Result, arch::ModuleArchSpecific, module::ModuleInfo};
+use crate::{
+    ModuleErr, Result, arch::ModuleArchSpecific, module::ModuleInfo, version::ModVersions,
+};
 
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -38,17 +44,19 @@ impl Display for SectionPerm {
 }
 
 impl SectionPerm {
-    /// Create ModuleSectionPermissions from ELF section flags
+    /// Create ModuleSectionPermissions from ELF section flags. Loaded memory
+    /// is always readable, so READ is set along with WRITE or EXECUTE even
+    /// without `SHF_ALLOC`.
     pub fn from_elf_flags(sh_flags: u64) -> Self {
         let mut perms = SectionPerm::empty();
         if (sh_flags & goblin::elf::section_header::SHF_ALLOC as u64) != 0 {
             perms |= SectionPerm::READ;
         }
         if (sh_flags & goblin::elf::section_header::SHF_WRITE as u64) != 0 {
-            perms |= SectionPerm::WRITE;
+            perms |= SectionPerm::READ | SectionPerm::WRITE;
         }
         if (sh_flags & goblin::elf::section_header::SHF_EXECINSTR as u64) != 0 {
-            perms |= SectionPerm::EXECUTE;
+            perms |= SectionPerm::READ | SectionPerm::EXECUTE;
         }
         perms
     }
@@ -58,20 +66,142 @@ impl SectionPerm {
 pub trait SectionMemOps: Send + Sync {
     fn as_ptr(&self) -> *const u8;
     fn as_mut_ptr(&mut self) -> *mut u8;
+    /// Size of the memory region in bytes
+    fn len(&self) -> usize;
+    /// Whether the memory region is empty
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Set every byte of the memory region to `byte`
+    fn fill(&mut self, byte: u8) {
+        unsafe { core::ptr::write_bytes(self.as_mut_ptr(), byte, self.len()) };
+    }
     /// Change the permissions of the memory region
     fn change_perms(&mut self, perms: SectionPerm) -> bool;
 }
 
+/// Which modules may use an exported symbol
+///
+/// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/internal.h#L38>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolLicense {
+    /// Exported with `EXPORT_SYMBOL`, usable by any module
+    Any,
+    /// Exported with `EXPORT_SYMBOL_GPL`, usable by GPL-compatible modules only
+    GplOnly,
+}
+
+/// An exported symbol as [`KernelModuleHelper::resolve_symbol_full`] finds
+/// it, with what the loader needs to check its use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedSymbol {
+    pub addr: usize,
+    /// License the symbol was exported under, GPL-only symbols are refused
+    /// to modules that are not GPL-compatible
+    pub license: SymbolLicense,
+    /// CRC from `__kcrctab`, checked against the module's `__versions`
+    /// section. `None` disables the check.
+    pub crc: Option<u32>,
+}
+
 /// Trait for kernel module helper functions
 pub trait KernelModuleHelper {
-    /// Allocate virtual memory for module section
-    fn vmalloc(size: usize) -> Box<dyn SectionMemOps>;
+    /// Page size of the target, a power of two. Every allocated section is
+    /// rounded up to it, so targets with 16K or 64K pages must override it.
+    const PAGE_SIZE: usize = 4096;
+    /// Allocate virtual memory for module section, or `None` if the
+    /// allocation failed
+    fn vmalloc(size: usize) -> Option<Box<dyn SectionMemOps>>;
+    /// Allocate virtual memory for module section, preferably at `hint`.
+    /// Used by [`ModuleLoader::load_module_at`]; the hint may be ignored,
+    /// which the default does by falling back to [`Self::vmalloc`].
+    fn vmalloc_at(_hint: usize, size: usize) -> Option<Box<dyn SectionMemOps>> {
+        Self::vmalloc(size)
+    }
     /// Resolve symbol name to address
     fn resolve_symbol(name: &str) -> Option<usize>;
+    /// Resolve symbol name to address and the license it was exported under
+    fn resolve_symbol_ex(name: &str) -> Option<(usize, SymbolLicense)> {
+        Self::resolve_symbol(name).map(|addr| (addr, SymbolLicense::Any))
+    }
+    /// Resolve symbol name with everything the loader checks, which is what
+    /// the loader calls. The default combines [`Self::resolve_symbol_ex`]
+    /// and [`Self::symbol_crc`], so helpers that export from one table can
+    /// override this alone.
+    fn resolve_symbol_full(name: &str) -> Option<ResolvedSymbol> {
+        Self::resolve_symbol_ex(name).map(|(addr, license)| ResolvedSymbol {
+            addr,
+            license,
+            crc: Self::symbol_crc(name),
+        })
+    }
     /// Flush CPU cache for the given memory region
     fn flsuh_cache(_addr: usize, _size: usize) {
         // Default implementation does nothing
     }
+    /// CRC of an exported symbol, checked against the module's `__versions`
+    /// section (`CONFIG_MODVERSIONS`). `None` disables the check for `name`.
+    fn symbol_crc(_name: &str) -> Option<u32> {
+        None
+    }
+    /// Check the relocated module, given as `(start, size, perms)` for each
+    /// allocated section, before its permissions are applied and its init
+    /// function can run, e.g. against a known hash. `perms` are the
+    /// permissions the section is about to get. Rejecting the module fails
+    /// the load with `EACCES`, unlike a rejected signature.
+    #[allow(clippy::result_unit_err)]
+    fn verify_module(_regions: &[(*const u8, usize, SectionPerm)]) -> core::result::Result<(), ()> {
+        Ok(())
+    }
+    /// Verify the PKCS#7 signature `sig` appended to the module image `data`
+    /// (`CONFIG_MODULE_SIG`). The default trusts no keys.
+    fn verify_signature(_data: &[u8], _sig: &[u8]) -> bool {
+        false
+    }
+    /// Whether modules without a valid signature are rejected
+    /// (`CONFIG_MODULE_SIG_FORCE`), with `EKEYREJECTED`. Otherwise they are
+    /// loaded with [`ModuleOwner::sig_ok`] unset.
+    fn sig_enforce() -> bool {
+        false
+    }
+    /// Whether objects whose OSABI is neither System V nor Linux are
+    /// rejected. Otherwise they are loaded with a warning.
+    fn strict_osabi() -> bool {
+        false
+    }
+    /// Whether `SHN_COMMON` symbols, as emitted by compilers defaulting to
+    /// `-fcommon`, are given zeroed memory of their own. Otherwise a module
+    /// with any of them fails to load and has to be rebuilt with
+    /// `-fno-common`.
+    fn allow_common_symbols() -> bool {
+        false
+    }
+    /// Address that unresolved weak symbols resolve to, e.g. a stub that
+    /// logs and returns. With `None` (the default) they are left as 0, as in
+    /// Linux, so the module has to check them before use.
+    fn weak_default() -> Option<usize> {
+        None
+    }
+    /// Whether relocations that fail are recorded in
+    /// [`ModuleOwner::reloc_errors`] instead of failing the load, e.g. to
+    /// see every missing relocation type at once when porting to a new
+    /// architecture. A module loaded with errors refuses to run its init
+    /// function.
+    fn best_effort_relocations() -> bool {
+        false
+    }
+    /// Patch the module's code as described by an x86 `.altinstructions` or
+    /// `.parainstructions` section, given its name and relocated contents,
+    /// e.g. for the features of the running CPU. The default leaves the
+    /// original instructions in place.
+    fn apply_alternatives(_name: &str, _section: &[u8]) {}
+    /// Arm a `static_branch_*()` site of the module before its init function
+    /// runs, given its entry in the relocated `__jump_table`. The embedder
+    /// checks the state of the key, see [`jump_entry_key`](crate::jump_entry_key),
+    /// and patches the code at [`jump_entry_code`](crate::jump_entry_code)
+    /// to a jump or a nop as needed. Text is already read-only at this point.
+    /// The default leaves every site as compiled.
+    fn patch_jump_entry(_entry: &kmod_tools::kbindings::jump_entry) {}
 }
 
 pub struct ModuleLoader<'a, H: KernelModuleHelper> {
@@ -80,11 +210,215 @@ pub struct ModuleLoader<'a, H: KernelModuleHelper> {
     __helper: core::marker::PhantomData<H>,
 }
 
+/// How far a [`LoadPipeline`] has got, named after the last step it
+/// completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LoadPhase {
+    /// The signature and the ELF headers are checked and `.modinfo` is read
+    Validated,
+    /// Sections are laid out, allocated and copied in
+    Allocated,
+    /// Undefined symbols are resolved and defined ones point into the
+    /// allocated sections
+    SymbolsResolved,
+    /// Relocations are applied
+    Relocated,
+    /// `struct module` and the special sections (`__param`, `__ex_table`,
+    /// ...) are read from the relocated image
+    ModuleRead,
+    /// The module is verified, its section permissions are applied and its
+    /// static branches are patched
+    Formed,
+}
+
+/// The steps of [`ModuleLoader::load_module`], to be run one at a time, e.g.
+/// to look at the resolved symbols before anything is relocated. Each step
+/// must follow the one before it and fails with `EINVAL` otherwise.
+/// [`Self::finish`] runs whatever steps are left.
+pub struct LoadPipeline<'a, H: KernelModuleHelper> {
+    loader: ModuleLoader<'a, H>,
+    owner: ModuleOwner<H>,
+    load_info: Option<ModuleLoadInfo>,
+    phase: LoadPhase,
+}
+
+impl<'a, H: KernelModuleHelper> LoadPipeline<'a, H> {
+    pub fn phase(&self) -> LoadPhase {
+        self.phase
+    }
+
+    /// The module as loaded so far. Which parts are valid depends on
+    /// [`Self::phase`], e.g. [`ModuleOwner::module`] is only read from the
+    /// image by [`Self::read_this_module`].
+    pub fn owner(&self) -> &ModuleOwner<H> {
+        &self.owner
+    }
+
+    /// The symbol table, once [`Self::simplify_symbols`] has resolved it
+    pub fn load_info(&self) -> Option<&ModuleLoadInfo> {
+        self.load_info.as_ref()
+    }
+
+    /// Move from `from` to `to`, running `step` in between
+    fn step(
+        mut self,
+        from: LoadPhase,
+        to: LoadPhase,
+        step: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<Self> {
+        if self.phase != from {
+            log::error!(
+                "[{:?}]: {:?} must follow {:?}, but the module is {:?}",
+                self.owner.name(),
+                to,
+                from,
+                self.phase
+            );
+            return Err(ModuleErr::EINVAL);
+        }
+        step(&mut self)?;
+        self.phase = to;
+        Ok(self)
+    }
+
+    /// Allocate the sections, from `base_hint` if given as in
+    /// [`ModuleLoader::load_module_at`]
+    pub fn layout_and_allocate(self, base_hint: Option<usize>) -> Result<Self> {
+        self.step(LoadPhase::Validated, LoadPhase::Allocated, |p| {
+            p.loader.layout_and_allocate(&mut p.owner, base_hint)
+        })
+    }
+
+    pub fn simplify_symbols(self) -> Result<Self> {
+        self.step(LoadPhase::Allocated, LoadPhase::SymbolsResolved, |p| {
+            let load_info = p.loader.simplify_symbols(&p.owner)?;
+            p.owner.set_symbols(&load_info);
+            p.load_info = Some(load_info);
+            Ok(())
+        })
+    }
+
+    pub fn apply_relocations(self) -> Result<Self> {
+        self.step(LoadPhase::SymbolsResolved, LoadPhase::Relocated, |p| {
+            let load_info = p.load_info.as_ref().ok_or(ModuleErr::EINVAL)?;
+            p.loader.apply_relocations(load_info, &mut p.owner, None)?;
+            p.loader.module_finalize(&p.owner);
+            Ok(())
+        })
+    }
+
+    /// Read `struct module` only now, as its fields (`init`, `exit`, the
+    /// parameter pointers) are only valid once relocated, but before the
+    /// permissions make it read-only
+    pub fn read_this_module(self) -> Result<Self> {
+        self.step(LoadPhase::Relocated, LoadPhase::ModuleRead, |p| {
+            p.loader.post_read_this_module(&mut p.owner)?;
+            p.loader.find_module_sections(&mut p.owner)
+        })
+    }
+
+    pub fn complete_formation(self) -> Result<Self> {
+        self.step(LoadPhase::ModuleRead, LoadPhase::Formed, |p| {
+            p.loader.verify_module(&p.owner)?;
+            p.loader.complete_formation(&mut p.owner)?;
+            p.owner.jump_label_add_module();
+            Ok(())
+        })
+    }
+
+    /// Run the remaining steps, then parse `args` into the module's
+    /// parameters
+    pub fn finish(mut self, args: CString) -> Result<ModuleOwner<H>> {
+        if self.phase == LoadPhase::Validated {
+            self = self.layout_and_allocate(None)?;
+        }
+        if self.phase == LoadPhase::Allocated {
+            self = self.simplify_symbols()?;
+        }
+        if self.phase == LoadPhase::SymbolsResolved {
+            self = self.apply_relocations()?;
+        }
+        if self.phase == LoadPhase::Relocated {
+            self = self.read_this_module()?;
+        }
+        if self.phase == LoadPhase::ModuleRead {
+            self = self.complete_formation()?;
+        }
+        let mut owner = self.owner;
+        self.loader.parse_args(&mut owner, args)?;
+        Ok(owner)
+    }
+}
+
 struct SectionPages {
     name: String,
     addr: Box<dyn SectionMemOps>,
+    /// Offset of the section in the module's layout, which does not depend
+    /// on where the allocator placed it
+    offset: usize,
     size: usize,
     perms: SectionPerm,
+    /// Only needed until the init function has run
+    init: bool,
+}
+
+/// Where a section of a loaded module lives, see [`ModuleOwner::memory_map`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionMapEntry {
+    pub name: String,
+    pub base: usize,
+    pub size: usize,
+    pub perms: SectionPerm,
+}
+
+impl Display for SectionMapEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:#018x}-{:#018x} {:<3} {}",
+            self.base,
+            self.base + self.size,
+            self.perms,
+            self.name
+        )
+    }
+}
+
+/// A symbol the module defines, see [`ModuleOwner::symbols`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleSymbol {
+    /// The name from the module's string table
+    pub name: String,
+    pub addr: usize,
+    /// Size from the symbol table, 0 if unknown
+    pub size: usize,
+}
+
+impl Display for ModuleSymbol {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#018x} {:#8x} {}", self.addr, self.size, self.name)
+    }
+}
+
+/// A relocation that could not be applied, see
+/// [`KernelModuleHelper::best_effort_relocations`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelocError {
+    /// Index of the section the relocation applies to
+    pub section: usize,
+    /// Index of the relocation in its relocation section
+    pub index: usize,
+    pub rel_type: u32,
+    pub err: ModuleErr,
+}
+
+/// Whether a section only holds init code or data, which is freed once the
+/// module's init function succeeds. `#[init_fn]` places the init function in
+/// `.text.init`.
+///
+/// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/main.c#L1484>
+fn is_init_section(name: &str) -> bool {
+    name.starts_with(".init") || name == ".text.init"
 }
 
 pub struct ModuleOwner<H: KernelModuleHelper> {
@@ -92,6 +426,15 @@ pub struct ModuleOwner<H: KernelModuleHelper> {
     pages: Vec<SectionPages>,
     name: String,
     module: Module,
+    sig_ok: bool,
+    reloc_errors: Vec<RelocError>,
+    /// The relocation that stopped the load, outside best-effort mode
+    failed_reloc: Option<RelocError>,
+    /// Constructors from `.ctors` or `.init_array`, run before the init
+    /// function
+    ctors: Vec<unsafe extern "C" fn()>,
+    /// Defined symbols sorted by address, for [`Self::symbolize`]
+    symbols: Vec<ModuleSymbol>,
     #[allow(unused)]
     pub(crate) arch: ModuleArchSpecific,
     _helper: core::marker::PhantomData<H>,
@@ -103,14 +446,186 @@ impl<H: KernelModuleHelper> ModuleOwner<H> {
         &self.name
     }
 
+    /// The module's relocated `struct module`
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Whether the module carried a signature that
+    /// [`KernelModuleHelper::verify_signature`] accepted
+    pub fn sig_ok(&self) -> bool {
+        self.sig_ok
+    }
+
+    /// The relocated exception table (`__ex_table`), for the embedder to
+    /// register the fixups of the module's faulting instructions. The `insn`
+    /// and `fixup` fields are offsets from the fields themselves; entries are
+    /// in section order, not sorted.
+    pub fn exception_table(&self) -> &[kmod_tools::kbindings::exception_table_entry] {
+        self.module.exception_table()
+    }
+
+    /// The relocated bug table (`__bug_table`) emitted by the module's
+    /// `BUG()`/`WARN()` sites. Use [`bug_addr`](crate::bug_addr) to get the
+    /// address of the trapping instruction of an entry.
+    pub fn bug_entries(&self) -> &[kmod_tools::kbindings::bug_entry] {
+        self.module.bug_table()
+    }
+
+    /// The relocated static key jump table (`__jump_table`). Each entry has
+    /// already been handed to [`KernelModuleHelper::patch_jump_entry`] when
+    /// the module is loaded.
+    pub fn jump_entries(&self) -> &[kmod_tools::kbindings::jump_entry] {
+        self.module.jump_entries()
+    }
+
+    /// Hand every jump table entry to [`KernelModuleHelper::patch_jump_entry`]
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/jump_label.c#L646>
+    fn jump_label_add_module(&self) {
+        for entry in self.jump_entries() {
+            H::patch_jump_entry(entry);
+        }
+    }
+
+    /// The relocations that failed while loading in best-effort mode
+    pub fn reloc_errors(&self) -> &[RelocError] {
+        &self.reloc_errors
+    }
+
+    /// Record a relocation that failed. In best-effort mode the load goes on,
+    /// otherwise `err` is returned to fail it.
+    pub(crate) fn reloc_failed(
+        &mut self,
+        rel_section: &SectionHeader,
+        index: usize,
+        rel_type: u32,
+        err: ModuleErr,
+    ) -> Result<()> {
+        let reloc_error = RelocError {
+            section: rel_section.sh_info as usize,
+            index,
+            rel_type,
+            err,
+        };
+        if !H::best_effort_relocations() {
+            self.failed_reloc = Some(reloc_error);
+            return Err(err);
+        }
+        self.reloc_errors.push(reloc_error);
+        Ok(())
+    }
+
+    /// The allocated sections as `(name, offset, size)`. Sections are laid
+    /// out back to back in section index order, so loading the same object
+    /// always yields the same layout; with
+    /// [`ModuleLoader::load_module_at`] the offsets are relative to the base
+    /// hint.
+    pub fn layout(&self) -> impl Iterator<Item = (&str, usize, usize)> {
+        self.pages
+            .iter()
+            .map(|page| (page.name.as_str(), page.offset, page.size))
+    }
+
+    /// The address, size and permissions of each section that is still
+    /// allocated, e.g. to map a faulting address back to a section
+    pub fn memory_map(&self) -> Vec<SectionMapEntry> {
+        self.pages
+            .iter()
+            .map(|page| SectionMapEntry {
+                name: page.name.clone(),
+                base: page.addr.as_ptr() as usize,
+                size: page.size,
+                perms: page.perms,
+            })
+            .collect()
+    }
+
+    /// The symbols the module defines, sorted by address. Section and file
+    /// symbols are left out.
+    pub fn symbols(&self) -> &[ModuleSymbol] {
+        &self.symbols
+    }
+
+    /// Map `addr` back to the symbol containing it and the offset into that
+    /// symbol, e.g. for a backtrace through the module. A symbol without a
+    /// size extends to the next one. Addresses outside the module's still
+    /// allocated sections give `None`.
+    pub fn symbolize(&self, addr: usize) -> Option<(String, usize)> {
+        self.pages.iter().find(|page| {
+            let base = page.addr.as_ptr() as usize;
+            (base..base + page.size).contains(&addr)
+        })?;
+        let idx = self.symbols.partition_point(|sym| sym.addr <= addr);
+        let sym = &self.symbols[idx.checked_sub(1)?];
+        let offset = addr - sym.addr;
+        if sym.size != 0 && offset >= sym.size {
+            return None;
+        }
+        Some((sym.name.clone(), offset))
+    }
+
+    /// Collect the defined symbols of the resolved symbol table
+    fn set_symbols(&mut self, load_info: &ModuleLoadInfo) {
+        use goblin::elf::{
+            section_header::{SHN_ABS, SHN_UNDEF},
+            sym::{STT_FILE, STT_SECTION},
+        };
+
+        self.symbols = load_info
+            .syms
+            .iter()
+            .filter(|(sym, name)| {
+                !name.is_empty()
+                    && ![SHN_UNDEF, SHN_ABS].contains(&(sym.st_shndx as u32))
+                    && ![STT_SECTION, STT_FILE].contains(&sym.st_type())
+            })
+            .map(|(sym, name)| ModuleSymbol {
+                name: name.clone(),
+                addr: sym.st_value as usize,
+                size: sym.st_size as usize,
+            })
+            .collect();
+        self.symbols.sort_by_key(|sym| sym.addr);
+    }
+
     pub(crate) fn set_name(&mut self, name: &str) {
         self.name = name.to_string();
     }
 
-    /// Call the module's init function
+    /// The lifecycle state of the module: [`ModuleState::Coming`] once
+    /// loaded, [`ModuleState::Live`] after a successful init and
+    /// [`ModuleState::Going`] once it fails to init or is unloaded
+    pub fn state(&self) -> ModuleState {
+        self.module.state()
+    }
+
+    /// Run the module's constructors, then call its init function. If it
+    /// succeeds, the memory of the init sections is released. If it fails,
+    /// the exit function is dropped so that unloading does not run it.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/main.c#L2507>
     pub fn call_init(&mut self) -> Result<i32> {
+        if !self.reloc_errors.is_empty() {
+            log::error!(
+                "[{}]: Refusing to init, {} relocations failed",
+                self.name,
+                self.reloc_errors.len()
+            );
+            return Err(ModuleErr::ENOEXEC);
+        }
         if let Some(init_fn) = self.module.take_init_fn() {
+            for ctor in core::mem::take(&mut self.ctors) {
+                unsafe { ctor() };
+            }
             let result = unsafe { init_fn() };
+            if result == 0 {
+                self.module.set_state(ModuleState::Live);
+                self.free_init_pages();
+            } else {
+                self.module.set_state(ModuleState::Going);
+                self.module.take_exit_fn();
+            }
             Ok(result)
         } else {
             log::warn!("The init function can only be called once.");
@@ -118,8 +633,19 @@ impl<H: KernelModuleHelper> ModuleOwner<H> {
         }
     }
 
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/main.c#L2555>
+    fn free_init_pages(&mut self) {
+        self.pages.retain(|page| {
+            if page.init {
+                log::info!("[{}]: Freeing init section '{}'", self.name, page.name);
+            }
+            !page.init
+        });
+    }
+
     /// Call the module's exit function
     pub fn call_exit(&mut self) {
+        self.module.set_state(ModuleState::Going);
         if let Some(exit_fn) = self.module.take_exit_fn() {
             log::warn!("Calling module exit function...");
             unsafe {
@@ -129,6 +655,88 @@ impl<H: KernelModuleHelper> ModuleOwner<H> {
             log::warn!("The exit function can only be called once.");
         }
     }
+
+    /// Unload the module: call its exit function if it has not run yet and
+    /// release the resources held by its parameters.
+    pub fn unload(&mut self) {
+        self.module.set_state(ModuleState::Going);
+        if self.module.exit_fn().is_some() {
+            self.call_exit();
+        }
+        crate::param::destroy_params(self.module.params_mut());
+    }
+
+    /// Format the parameter `name` like reading
+    /// `/sys/module/<module>/parameters/<name>`, through its `get` operation.
+    /// Fails with `ENOENT` if there is no such parameter. Its permissions are
+    /// not checked.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/params.c#L544>
+    pub fn show_param(&self, name: &str) -> Result<String> {
+        let params = self.module.params();
+        let idx = crate::param::find_param(params, name).ok_or(ModuleErr::ENOENT)?;
+        params[idx].get()
+    }
+
+    /// Set the parameter `name` from `value` like writing to
+    /// `/sys/module/<module>/parameters/<name>`, through its `set` operation.
+    /// Fails with `ENOENT` if there is no such parameter. Its permissions are
+    /// not checked.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/params.c#L563>
+    pub fn store_param(&mut self, name: &str, value: &str) -> Result<()> {
+        let params = self.module.params_mut();
+        let idx = crate::param::find_param(params, name).ok_or(ModuleErr::ENOENT)?;
+        params[idx].set(value)
+    }
+
+    /// Release the resources held by the parameters of a module whose init
+    /// function failed. Its exit function is never called.
+    pub(crate) fn release_failed(&mut self) {
+        self.module.take_exit_fn();
+        crate::param::destroy_params(self.module.params_mut());
+    }
+}
+
+#[cfg(test)]
+impl<H: KernelModuleHelper> ModuleOwner<H> {
+    /// An owner with no sections, for driving the relocation code in tests
+    pub(crate) fn empty() -> Self {
+        ModuleOwner {
+            module_info: ModuleInfo::new(),
+            pages: Vec::new(),
+            name: "test".to_string(),
+            module: Module::default(),
+            sig_ok: false,
+            reloc_errors: Vec::new(),
+            failed_reloc: None,
+            ctors: Vec::new(),
+            symbols: Vec::new(),
+            arch: ModuleArchSpecific::default(),
+            _helper: core::marker::PhantomData,
+        }
+    }
+
+    /// An owner with no sections whose module has the given init and exit
+    /// functions
+    pub(crate) fn with_fns(
+        init: unsafe extern "C" fn() -> core::ffi::c_int,
+        exit: unsafe extern "C" fn(),
+    ) -> Self {
+        let mut owner = Self::empty();
+        owner.module = Module::new(Some(init), Some(exit));
+        owner
+    }
+}
+
+/// Copy `struct module` out of the relocated `.gnu.linkonce.this_module`
+/// section. The section only has the alignment the helper's allocation gives
+/// it, which need not be that of [`Module`].
+///
+/// # Safety
+/// `addr` must point to `size_of::<Module>()` readable bytes.
+unsafe fn read_this_module(addr: *const u8) -> Module {
+    unsafe { core::ptr::read_unaligned(addr.cast::<Module>()) }
 }
 
 const fn align_up(addr: usize, align: usize) -> usize {
@@ -141,7 +749,19 @@ const fn align_up(addr: usize, align: usize) -> usize {
 
 const SKIP_SECTIONS: &[&str] = &[".note", ".modinfo", "__version"];
 
-pub(crate) struct ModuleLoadInfo {
+/// Name of the region holding the module's `SHN_COMMON` symbols, see
+/// [`KernelModuleHelper::allow_common_symbols`]
+const COMMON_SECTION: &str = "COMMON";
+
+/// Sections describing code the kernel patches once the module is relocated,
+/// see [`KernelModuleHelper::apply_alternatives`]
+const ALTERNATIVE_SECTIONS: &[&str] = &[".altinstructions", ".parainstructions"];
+
+/// The module's symbols, resolved against the kernel and relocated to where
+/// their sections were allocated, see [`ModuleLoader::allocate`]
+pub struct ModuleLoadInfo {
+    /// The relocated symbols, with their names as given by
+    /// [`crate::demangle_sym`] for logging
     pub(crate) syms: Vec<(goblin::elf::sym::Sym, String)>,
 }
 
@@ -152,6 +772,26 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
         if !elf.is_64 {
             return Err(ModuleErr::ENOEXEC);
         }
+        // The relocation code writes little-endian values on every arch
+        if !elf.little_endian {
+            log::error!("Unsupported big-endian module");
+            return Err(ModuleErr::ENOEXEC);
+        }
+        // Symbol binding and SHN_COMMON are handled with Linux semantics
+        let osabi = elf.header.e_ident[goblin::elf::header::EI_OSABI];
+        if !matches!(
+            osabi,
+            goblin::elf::header::ELFOSABI_SYSV | goblin::elf::header::ELFOSABI_LINUX
+        ) {
+            if H::strict_osabi() {
+                log::error!("Unsupported ELF OSABI {}", osabi);
+                return Err(ModuleErr::ENOEXEC);
+            }
+            log::warn!(
+                "Unexpected ELF OSABI {}, loading with Linux semantics",
+                osabi
+            );
+        }
         Ok(ModuleLoader {
             elf,
             elf_data,
@@ -159,12 +799,47 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
         })
     }
 
-    /// Check module signature
+    /// Decompress a compressed module image (`.ko.gz`, `.ko.xz`) into `buf`
+    /// and parse it. If `format` is `None`, it is detected from the magic
+    /// bytes.
+    #[cfg(any(feature = "gzip", feature = "xz"))]
+    pub fn from_compressed(
+        data: &[u8],
+        format: Option<crate::CompressionFormat>,
+        buf: &'a mut Vec<u8>,
+    ) -> Result<Self> {
+        *buf = crate::decompress(data, format)?;
+        Self::new(buf)
+    }
+
+    /// Check the signature appended to the module, if any, and strip it from
+    /// the image. Returns whether the signature was verified; an unverified
+    /// module is only rejected if [`KernelModuleHelper::sig_enforce`] is set,
+    /// with `EKEYREJECTED`, which no other check returns.
     ///
     /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/signing.c#L70>
-    fn module_sig_check(&self) -> bool {
-        // TODO: implement module signature check
-        true
+    fn module_sig_check(&mut self) -> Result<bool> {
+        let reason = match crate::signature::split_signature(self.elf_data) {
+            Ok((data, sig)) => {
+                self.elf_data = data;
+                if H::verify_signature(data, sig) {
+                    return Ok(true);
+                }
+                "module with unverified signature"
+            }
+            Err(ModuleErr::ENODATA) => "unsigned module",
+            Err(ModuleErr::ENOPKG) => "module with unsupported crypto",
+            Err(err) => {
+                log::error!("Malformed module signature");
+                return Err(err);
+            }
+        };
+        if H::sig_enforce() {
+            log::error!("Loading of {} is rejected", reason);
+            return Err(ModuleErr::EKEYREJECTED);
+        }
+        log::warn!("Loading {}", reason);
+        Ok(false)
     }
 
     /// Check userspace passed ELF module against our expectations, and cache
@@ -277,8 +952,7 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
                 log::error!("Module({:?}) info: {:?}", o.name(), o.module_info);
             }
         }
-        let mut owner = owner.ok_or(ModuleErr::ENOEXEC)?;
-        let module_name = owner.name();
+        let module_name = owner.as_ref().map_or("", |o| o.name());
 
         if num_sym_secs != 1 {
             log::error!("{}: module has no symbols (stripped?)", module_name);
@@ -299,7 +973,13 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
          *   o The section size must match the kernel's run time's struct module
          *     size
          */
-        if num_mod_secs != 1 {
+        if num_mod_secs == 0 {
+            log::error!(
+                "{}: module has no .gnu.linkonce.this_module section",
+                module_name
+            );
+            return Err(ModuleErr::ENOENT);
+        } else if num_mod_secs > 1 {
             log::error!(
                 "{}: Only one .gnu.linkonce.this_module section must exist.",
                 module_name
@@ -323,45 +1003,150 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             );
             return Err(ModuleErr::ENOEXEC);
         }
+        // Without .modinfo, the module is described by its name alone
+        let mut owner = match owner {
+            Some(owner) => owner,
+            None => {
+                log::warn!("Module has no .modinfo section, using the name in __this_module");
+                Self::new_owner(self.this_module_info(mod_idx)?)
+            }
+        };
         // If we didn't load the .modinfo 'name' field earlier, fall back to
         // on-disk struct mod 'name' field.
         if owner.name().is_empty() {
-            self.pre_read_this_module(mod_idx, &mut owner)?;
+            owner.set_name(&self.read_this_module_name(mod_idx)?);
         }
         Ok(owner)
     }
 
     /// Load the module into kernel space
-    pub fn load_module(mut self, args: CString) -> Result<ModuleOwner<H>> {
-        if !self.module_sig_check() {
-            log::error!("Module signature check failed");
-            return Err(ModuleErr::ENOEXEC);
-        }
-        // let arch = offset_of!(kmod::kbindings::module, arch);
-        // log::error!("Offset of module.arch: {}", arch);
+    pub fn load_module(self, args: CString) -> Result<ModuleOwner<H>> {
+        self.load(args, None)
+    }
+
+    /// Load the module into kernel space, asking for its sections to be laid
+    /// out back to back from `base_hint` through
+    /// [`KernelModuleHelper::vmalloc_at`], e.g. to keep them within branch
+    /// range of the kernel. This is only a hint: the helper may place the
+    /// sections elsewhere, and relocations are applied against wherever they
+    /// end up.
+    pub fn load_module_at(self, args: CString, base_hint: usize) -> Result<ModuleOwner<H>> {
+        self.load(args, Some(base_hint))
+    }
+
+    fn load(self, args: CString, base_hint: Option<usize>) -> Result<ModuleOwner<H>> {
+        let owner = self
+            .pipeline()?
+            .layout_and_allocate(base_hint)?
+            .simplify_symbols()?
+            .apply_relocations()?
+            .read_this_module()?
+            .complete_formation()?
+            .finish(args)?;
+
+        log::error!("Module({:?}) loaded successfully!", owner.name());
+        Ok(owner)
+    }
+
+    /// Check the module's signature and headers, then hand the remaining
+    /// steps of [`Self::load_module`] to a [`LoadPipeline`]
+    pub fn pipeline(mut self) -> Result<LoadPipeline<'a, H>> {
+        let sig_ok = self.module_sig_check()?;
         let mut owner = self.elf_validity_cache_copy()?;
+        owner.sig_ok = sig_ok;
+        Ok(LoadPipeline {
+            loader: self,
+            owner,
+            load_info: None,
+            phase: LoadPhase::Validated,
+        })
+    }
 
-        self.layout_and_allocate(&mut owner)?;
+    /// Allocate the module's sections and resolve its symbols, without
+    /// relocating it, for embedders that apply relocations on demand with
+    /// [`apply_relocations_for`]. The signature is checked first, as by
+    /// [`Self::load_module`]. The returned owner is not a usable module:
+    /// its `struct module` is not read yet and its init function cannot run.
+    ///
+    /// [`apply_relocations_for`]: Self::apply_relocations_for
+    pub fn allocate(&mut self) -> Result<(ModuleOwner<H>, ModuleLoadInfo)> {
+        let sig_ok = self.module_sig_check()?;
+        let mut owner = self.elf_validity_cache_copy()?;
+        owner.sig_ok = sig_ok;
+        self.layout_and_allocate(&mut owner, None)?;
         let load_info = self.simplify_symbols(&owner)?;
-        self.apply_relocations(load_info, &mut owner)?;
+        owner.set_symbols(&load_info);
+        Ok((owner, load_info))
+    }
 
-        self.post_read_this_module(&mut owner)?;
+    /// Apply only the relocations that patch `target_section`, e.g. `.text`.
+    /// Fails with `ENOENT` if the module has no such section; a section
+    /// without relocations is left as is.
+    pub fn apply_relocations_for(
+        &self,
+        target_section: &str,
+        load_info: &ModuleLoadInfo,
+        owner: &mut ModuleOwner<H>,
+    ) -> Result<()> {
+        self.find_section(target_section)?;
+        self.apply_relocations(load_info, owner, Some(target_section))
+    }
 
-        self.find_module_sections(&mut owner)?;
+    /// Count the relocations of each type across all `SHT_RELA` sections,
+    /// without applying them.
+    pub fn relocation_histogram(&self) -> BTreeMap<u32, usize> {
+        let mut histogram = BTreeMap::new();
+        for (idx, relocs) in self.elf.shdr_relocs.iter() {
+            if self.elf.section_headers[*idx].sh_type != goblin::elf::section_header::SHT_RELA {
+                continue;
+            }
+            for reloc in relocs.iter() {
+                *histogram.entry(reloc.r_type).or_insert(0) += 1;
+            }
+        }
+        histogram
+    }
 
-        self.complete_formation(&mut owner)?;
+    /// Relocation types the loader can apply on this architecture, in the
+    /// order its relocation code matches them. Modules using any other type
+    /// fail to load; see [`Self::unsupported_relocations`] for the ones a
+    /// given module is missing.
+    pub fn supported_relocations() -> &'static [u32] {
+        crate::arch::SUPPORTED_RELOCATIONS
+    }
 
-        self.parse_args(&mut owner, args)?;
+    /// Relocation types used by the module that the loader cannot apply on
+    /// this architecture, in ascending order. A module using any of them
+    /// will fail to load.
+    pub fn unsupported_relocations(&self) -> Vec<u32> {
+        self.relocation_histogram()
+            .into_keys()
+            .filter(|&ty| !crate::arch::is_relocation_supported(ty))
+            .collect()
+    }
 
-        log::error!("Module({:?}) loaded successfully!", owner.name());
-        Ok(owner)
+    /// The names of the symbols the module imports, i.e. its `SHN_UNDEF`
+    /// global and weak symbols, demangled like [`crate::demangle_sym`] and in
+    /// symbol table order. Nothing is resolved, so this works before any
+    /// [`KernelModuleHelper`] is ready.
+    pub fn undefined_symbols(&self) -> Vec<String> {
+        self.elf
+            .syms
+            .iter()
+            .filter(|sym| {
+                sym.st_shndx == goblin::elf::section_header::SHN_UNDEF as usize
+                    && sym.st_bind() != goblin::elf::sym::STB_LOCAL
+            })
+            .filter_map(|sym| self.elf.strtab.get_at(sym.st_name))
+            .map(crate::demangle_sym)
+            .collect()
     }
 
     /// Args looks like "foo=bar,bar2 baz=fuz wiz". Parse them and set module parameters.
     fn parse_args(&self, owner: &mut ModuleOwner<H>, args: CString) -> Result<()> {
         let name = owner.name().to_string();
         let kparams = owner.module.params_mut();
-        let after_dashes = crate::param::parse_args(&name, args, kparams, i16::MIN, i16::MAX)?;
+        let after_dashes = crate::param::parse_args_leveled(&name, args, kparams)?;
         if !after_dashes.is_empty() {
             log::warn!(
                 "[{}]: parameters '{}' after '--' ignored",
@@ -372,7 +1157,8 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
         Ok(())
     }
 
-    /// Find section by name
+    /// Find section by name. Fails with `ENOENT` if there is no such
+    /// section, and `ENOEXEC` if a section name cannot be read.
     fn find_section(&self, name: &str) -> Result<&SectionHeader> {
         for shdr in &self.elf.section_headers {
             let sec_name = self
@@ -386,55 +1172,78 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             }
         }
         log::error!("Section '{}' not found", name);
-        Err(ModuleErr::ENOEXEC)
+        Err(ModuleErr::ENOENT)
     }
 
     fn pre_read_modinfo(&self, info_idx: usize) -> Result<ModuleOwner<H>> {
-        let modinfo_shdr = &self.elf.section_headers[info_idx];
-        let file_offset = modinfo_shdr.sh_offset as usize;
-        let size = modinfo_shdr.sh_size as usize;
-
-        let mut modinfo_data = &self.elf_data[file_offset..file_offset + size];
-        let mut module_info = ModuleInfo::new();
-
-        log::info!("Reading .modinfo section (size: {:#x})", size);
-
-        // read the modinfo data
-        // format is key=value\0key=value\0...
-        loop {
-            if modinfo_data.is_empty() {
-                break;
-            }
-            let cstr = CStr::from_bytes_until_nul(modinfo_data)
-                .map_err(|_| ModuleErr::EINVAL)
-                .unwrap();
-            let str_slice = cstr.to_str().map_err(|_| ModuleErr::EINVAL)?;
-            modinfo_data = &modinfo_data[cstr.to_bytes_with_nul().len()..];
-
-            let mut split = str_slice.splitn(2, '=');
-            let key = split.next().ok_or(ModuleErr::EINVAL)?.to_string();
-            let value = split.next().ok_or(ModuleErr::EINVAL)?.to_string();
-            module_info.add_kv(key, value);
-        }
+        Ok(Self::new_owner(self.read_modinfo(info_idx)?))
+    }
 
+    /// An owner for a module described by `module_info`, named after its
+    /// `name` entry if it has one
+    fn new_owner(module_info: ModuleInfo) -> ModuleOwner<H> {
         let name = module_info
             .get("name")
             .map(|s| s.to_string())
-            .unwrap_or_else(|| "".to_string());
+            .unwrap_or_default();
 
-        Ok(ModuleOwner {
+        ModuleOwner {
             name,
             module_info,
             pages: Vec::new(),
             module: Module::default(),
+            sig_ok: false,
+            reloc_errors: Vec::new(),
+            failed_reloc: None,
+            ctors: Vec::new(),
+            symbols: Vec::new(),
             arch: ModuleArchSpecific::default(),
             _helper: core::marker::PhantomData,
-        })
+        }
+    }
+
+    /// Read the `.modinfo` section without loading the module, e.g. to look at
+    /// its name and dependencies. Without one, only the name is taken from
+    /// `.gnu.linkonce.this_module`, as when loading.
+    pub(crate) fn module_info(&self) -> Result<ModuleInfo> {
+        let section_idx = |name| {
+            self.elf
+                .section_headers
+                .iter()
+                .position(|shdr| self.elf.shdr_strtab.get_at(shdr.sh_name) == Some(name))
+        };
+        if let Some(info_idx) = section_idx(".modinfo") {
+            return self.read_modinfo(info_idx);
+        }
+        let mod_idx = section_idx(".gnu.linkonce.this_module").ok_or(ModuleErr::ENOENT)?;
+        self.this_module_info(mod_idx)
+    }
+
+    /// A minimal stand-in for a missing `.modinfo`: just the module name from
+    /// the on-disk `__this_module` in section `mod_idx`
+    fn this_module_info(&self, mod_idx: usize) -> Result<ModuleInfo> {
+        let mut module_info = ModuleInfo::new();
+        module_info.add_kv("name".to_string(), self.read_this_module_name(mod_idx)?);
+        Ok(module_info)
+    }
+
+    fn read_modinfo(&self, info_idx: usize) -> Result<ModuleInfo> {
+        let modinfo_shdr = &self.elf.section_headers[info_idx];
+        let file_offset = modinfo_shdr.sh_offset as usize;
+        let size = modinfo_shdr.sh_size as usize;
+
+        log::info!("Reading .modinfo section (size: {:#x})", size);
+        let modinfo_data = self
+            .elf_data
+            .get(file_offset..file_offset + size)
+            .ok_or(ModuleErr::ENOEXEC)?;
+        ModuleInfo::parse(modinfo_data)
     }
 
     /// Read the __this_module structure to get module name. If the name of owner
     /// is not set, set it here.
-    fn pre_read_this_module(&self, idx: usize, owner: &mut ModuleOwner<H>) -> Result<()> {
+    /// The module name in the on-disk `__this_module` in section `idx`
+    fn read_this_module_name(&self, idx: usize) -> Result<String> {
         let this_module_shdr = &self.elf.section_headers[idx];
         let size = this_module_shdr.sh_size as usize;
         if size != core::mem::size_of::<Module>() {
@@ -445,19 +1254,22 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             );
             return Err(ModuleErr::ENOEXEC);
         }
-        let modinfo_data = this_module_shdr.sh_addr as *mut u8;
-        let module = unsafe { core::ptr::read(modinfo_data as *const Module) };
-        let name = module.name();
-        owner.set_name(name);
-        Ok(())
+        // Not laid out yet, so read the on-disk copy
+        let offset = this_module_shdr.sh_offset as usize;
+        let Some(data) = self.elf_data.get(offset..offset + size) else {
+            log::error!("Truncated .gnu.linkonce.this_module section");
+            return Err(ModuleErr::ENOEXEC);
+        };
+        let module = unsafe { core::ptr::read_unaligned(data.as_ptr() as *const Module) };
+        Ok(module.name().to_string())
     }
 
     /// After relocating, read the __this_module structure to get init and exit function pointers
     fn post_read_this_module(&mut self, owner: &mut ModuleOwner<H>) -> Result<()> {
         let this_module_shdr = self.find_section(".gnu.linkonce.this_module")?;
         // the data address is the allocated virtual address and it has been relocated
-        let modinfo_data = this_module_shdr.sh_addr as *mut u8;
-        let module = unsafe { core::ptr::read(modinfo_data as *const Module) };
+        let modinfo_data = this_module_shdr.sh_addr as *const u8;
+        let module = unsafe { read_this_module(modinfo_data) };
 
         let init_fn = module.init_fn();
         let exit_fn = module.exit_fn();
@@ -468,7 +1280,16 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             exit_fn.map(|f| f as *const ())
         );
 
+        if module.name() != owner.name() {
+            log::warn!(
+                "Module name '{}' in __this_module does not match modinfo name '{}'",
+                module.name(),
+                owner.name()
+            );
+        }
+
         owner.module = module;
+        owner.module.set_state(ModuleState::Unformed);
         Ok(())
     }
 
@@ -482,12 +1303,40 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
         Ok((num, addr))
     }
 
+    /// Like [`section_objs`](Self::section_objs), for a section that is
+    /// later used in place as a `[T]`, so it must be aligned for `T`
+    fn section_table<T>(&self, owner: &ModuleOwner<H>, name: &str) -> Result<(usize, *mut T)> {
+        let (num, addr) = self.section_objs(name, size_of::<T>())?;
+        if num != 0 && !addr.cast::<T>().is_aligned() {
+            log::error!(
+                "[{}]: {} section at {:p} is not aligned to {} bytes",
+                owner.name(),
+                name,
+                addr,
+                core::mem::align_of::<T>()
+            );
+            return Err(ModuleErr::ENOEXEC);
+        }
+        Ok((num, addr as *mut T))
+    }
+
     fn find_module_sections(&self, owner: &mut ModuleOwner<H>) -> Result<()> {
-        let (num_kparams, kparam_addr) =
-            self.section_objs("__param", size_of::<kmod_tools::kernel_param>())?;
+        let (num_kp, kp) = self.section_table(owner, "__param")?;
+        let (num_exentries, extable) = self.section_table(owner, "__ex_table")?;
+        let (num_bugs, bug_table) = self.section_table(owner, "__bug_table")?;
+        let (num_jump_entries, jump_entries) = self.section_table(owner, "__jump_table")?;
+
         let raw_module = owner.module.raw_mod();
-        raw_module.kp = kparam_addr as *mut kmod_tools::kernel_param;
-        raw_module.num_kp = num_kparams as _;
+        raw_module.kp = kp;
+        raw_module.num_kp = num_kp as _;
+        raw_module.extable = extable;
+        raw_module.num_exentries = num_exentries as _;
+        raw_module.bug_table = bug_table;
+        raw_module.num_bugs = num_bugs as _;
+        raw_module.jump_entries = jump_entries;
+        raw_module.num_jump_entries = num_jump_entries as _;
+
+        owner.ctors = self.find_ctors()?;
 
         // TODO: implement finding other sections:
         // __ksymtab
@@ -497,6 +1346,73 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
         Ok(())
     }
 
+    /// Read the relocated constructor pointers from `.ctors` or, failing
+    /// that, `.init_array`. Null entries are skipped.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/main.c#L2158>
+    fn find_ctors(&self) -> Result<Vec<unsafe extern "C" fn()>> {
+        let ptr_size = size_of::<usize>();
+        let (mut num, mut addr) = self.section_objs(".ctors", ptr_size)?;
+        let (num_init_array, init_array) = self.section_objs(".init_array", ptr_size)?;
+        if num_init_array != 0 {
+            if num != 0 {
+                log::error!("Module has both .ctors and .init_array");
+                return Err(ModuleErr::EINVAL);
+            }
+            (num, addr) = (num_init_array, init_array);
+        }
+        Ok((0..num)
+            .filter_map(|i| {
+                let ptr = unsafe { addr.cast::<usize>().add(i).read_unaligned() };
+                (ptr != 0)
+                    .then(|| unsafe { core::mem::transmute::<usize, unsafe extern "C" fn()>(ptr) })
+            })
+            .collect())
+    }
+
+    /// Hand the relocated alternatives sections to
+    /// [`KernelModuleHelper::apply_alternatives`]
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/arch/x86/kernel/module.c#L253>
+    fn module_finalize(&self, owner: &ModuleOwner<H>) {
+        for shdr in &self.elf.section_headers {
+            let Some(name) = self.elf.shdr_strtab.get_at(shdr.sh_name) else {
+                continue;
+            };
+            if !ALTERNATIVE_SECTIONS.contains(&name)
+                || shdr.sh_flags & goblin::elf::section_header::SHF_ALLOC as u64 == 0
+                || shdr.sh_size == 0
+            {
+                continue;
+            }
+            log::info!(
+                "[{}]: Applying alternatives from '{}', {:#x} bytes",
+                owner.name(),
+                name,
+                shdr.sh_size
+            );
+            // Allocated and relocated by now
+            let section = unsafe {
+                core::slice::from_raw_parts(shdr.sh_addr as *const u8, shdr.sh_size as usize)
+            };
+            H::apply_alternatives(name, section);
+        }
+    }
+
+    /// Hand the relocated sections to [`KernelModuleHelper::verify_module`],
+    /// failing with `EACCES` if it rejects them
+    fn verify_module(&self, owner: &ModuleOwner<H>) -> Result<()> {
+        let regions = owner
+            .pages
+            .iter()
+            .map(|page| (page.addr.as_ptr(), page.size, page.perms))
+            .collect::<Vec<_>>();
+        H::verify_module(&regions).map_err(|()| {
+            log::error!("[{}]: Module rejected by verification", owner.name());
+            ModuleErr::EACCES
+        })
+    }
+
     /// Finally it's fully formed, ready to start executing.
     fn complete_formation(&self, owner: &mut ModuleOwner<H>) -> Result<()> {
         for page in &mut owner.pages {
@@ -510,15 +1426,21 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             }
             H::flsuh_cache(page.addr.as_ptr() as usize, page.size);
         }
+        owner.module.set_state(ModuleState::Coming);
         Ok(())
     }
 
     /// Layout sections and allocate memory
     /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/main.c#L2363>
-    fn layout_and_allocate(&mut self, owner: &mut ModuleOwner<H>) -> Result<()> {
+    fn layout_and_allocate(
+        &mut self,
+        owner: &mut ModuleOwner<H>,
+        base_hint: Option<usize>,
+    ) -> Result<()> {
         // Allow arches to frob section contents and sizes
         #[cfg(feature = "module-sections")]
         crate::arch::module_frob_arch_sections(&mut self.elf, owner)?;
+        let mut offset = 0;
         for shdr in self.elf.section_headers.iter_mut() {
             let sec_name = self
                 .elf
@@ -548,18 +1470,17 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
                 continue;
             }
 
-            let aligned_size = align_up(size, 4096);
+            let aligned_size = align_up(size, H::PAGE_SIZE);
 
             // Allocate memory for the section
-            let mut addr = H::vmalloc(aligned_size);
-            if addr.as_ptr().is_null() {
-                return Err(ModuleErr::ENOSPC);
-            }
-
+            let section_offset = offset;
+            offset += aligned_size;
+            let mut addr = Self::alloc_section(sec_name, section_offset, aligned_size, base_hint)?;
             let raw_addr = addr.as_ptr() as u64;
 
-            // Copy section data from ELF to allocated memory
-            // For SHT_NOBITS sections (like .bss), memory is already zeroed by vmalloc
+            // Zero the section, then copy its data from the ELF file. SHT_NOBITS
+            // sections (like .bss) stay zeroed.
+            addr.fill(0);
             if shdr.sh_type != goblin::elf::section_header::SHT_NOBITS {
                 let section_data = &self.elf_data[file_offset..file_offset + size];
                 unsafe {
@@ -571,8 +1492,10 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             owner.pages.push(SectionPages {
                 name: sec_name.to_string(),
                 addr,
+                offset: section_offset,
                 size: aligned_size,
                 perms,
+                init: is_init_section(sec_name),
             });
 
             // update section address
@@ -581,11 +1504,30 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             shdr.sh_addr = raw_addr;
         }
 
+        if H::allow_common_symbols() {
+            let (offsets, size) = self.common_layout();
+            if !offsets.is_empty() {
+                let aligned_size = align_up(size.max(1), H::PAGE_SIZE);
+                let mut addr =
+                    Self::alloc_section(COMMON_SECTION, offset, aligned_size, base_hint)?;
+                addr.fill(0);
+                owner.pages.push(SectionPages {
+                    name: COMMON_SECTION.to_string(),
+                    addr,
+                    offset,
+                    size: aligned_size,
+                    perms: SectionPerm::READ | SectionPerm::WRITE,
+                    init: false,
+                });
+            }
+        }
+
         for page in &owner.pages {
             log::error!(
-                "Allocated section '{:>26}' at {:p} [{}] ({:8<#x})",
+                "Allocated section '{:>26}' at {:p} (+{:#x}) [{}] ({:8<#x})",
                 page.name,
                 page.addr.as_ptr(),
+                page.offset,
                 page.perms,
                 page.size
             );
@@ -594,11 +1536,83 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
         Ok(())
     }
 
+    /// Allocate `size` bytes for section `name`, at `offset` from the base
+    /// hint if there is one
+    fn alloc_section(
+        name: &str,
+        offset: usize,
+        size: usize,
+        base_hint: Option<usize>,
+    ) -> Result<Box<dyn SectionMemOps>> {
+        let addr = match base_hint {
+            Some(base) => H::vmalloc_at(base + offset, size),
+            None => H::vmalloc(size),
+        };
+        let Some(addr) = addr else {
+            log::error!(
+                "Failed to allocate {:#x} bytes for section '{}'",
+                size,
+                name
+            );
+            return Err(ModuleErr::ENOMEM);
+        };
+
+        if addr.len() < size {
+            log::error!(
+                "Allocated {:#x} bytes for section '{}', expected {:#x}",
+                addr.len(),
+                name,
+                size
+            );
+            return Err(ModuleErr::ENOMEM);
+        }
+        Ok(addr)
+    }
+
+    /// Lay out the `SHN_COMMON` symbols back to back, each aligned to its
+    /// `st_value`, like a linker does in `.bss`. Returns the offset of each
+    /// by symbol index, and the total size.
+    fn common_layout(&self) -> (BTreeMap<usize, usize>, usize) {
+        let mut offsets = BTreeMap::new();
+        let mut size = 0usize;
+        for (idx, sym) in self.elf.syms.iter().enumerate() {
+            if sym.st_shndx != goblin::elf::section_header::SHN_COMMON as usize {
+                continue;
+            }
+            let align = (sym.st_value as usize).max(1);
+            let offset = size.next_multiple_of(align);
+            offsets.insert(idx, offset);
+            size = offset + sym.st_size as usize;
+        }
+        (offsets, size)
+    }
+
     /// Change all symbols so that st_value encodes the pointer directly.
     ///
     /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/main.c#L1367>
     fn simplify_symbols(&self, owner: &ModuleOwner<H>) -> Result<ModuleLoadInfo> {
         let mut loadinfo = ModuleLoadInfo { syms: Vec::new() };
+        let versions = self
+            .elf
+            .section_headers
+            .iter()
+            .find(|shdr| self.elf.shdr_strtab.get_at(shdr.sh_name) == Some("__versions"))
+            .map(|shdr| {
+                let offset = shdr.sh_offset as usize;
+                let Some(data) = self.elf_data.get(offset..offset + shdr.sh_size as usize) else {
+                    log::error!("__versions section is out of bounds");
+                    return Err(ModuleErr::ENOEXEC);
+                };
+                Ok(ModVersions::new(data))
+            })
+            .transpose()?;
+
+        let common_base = owner
+            .pages
+            .iter()
+            .find(|page| page.name == COMMON_SECTION)
+            .map(|page| page.addr.as_ptr() as u64);
+        let (common_offsets, _) = self.common_layout();
 
         // Skip the first symbol (index 0), which is always the undefined symbol
         for (idx, sym) in self.elf.syms.iter().enumerate() {
@@ -614,6 +1628,7 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
                 .get_at(sym.st_name)
                 .unwrap_or("<unknown>")
                 .to_string();
+            let name = crate::demangle_sym(&sym_name);
 
             let sym_value = sym.st_value;
             let sym_size = sym.st_size;
@@ -621,7 +1636,7 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             // For debugging purposes, print symbol info
             log::debug!(
                 "Symbol: ('{}') [{}] Value: 0x{:016x} Size: {}",
-                sym_name,
+                name,
                 sym_section_to_str(sym.st_shndx as _),
                 sym_value,
                 sym_size
@@ -633,12 +1648,18 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             match sym.st_shndx as _ {
                 goblin::elf::section_header::SHN_UNDEF => {
                     // Undefined symbol
-                    let sym_address = H::resolve_symbol(&sym_name);
+                    let resolved = resolve_symbol_name::<H>(&sym_name);
+                    if let Some(versions) = &versions
+                        && !versions.check(owner.name(), &sym_name, resolved.and_then(|s| s.crc))
+                    {
+                        return Err(ModuleErr::EINVAL);
+                    }
                     // Ok if resolved.
-                    if let Some(addr) = sym_address {
+                    if let Some(ResolvedSymbol { addr, license, .. }) = resolved {
+                        check_symbol_license(owner, &name, license)?;
                         log::error!(
                             "  -> Resolved undefined symbol '{}' ({}) to address 0x{:016x}",
-                            sym_name,
+                            name,
                             sym_bind_to_str(sym.st_bind()),
                             addr
                         );
@@ -649,13 +1670,16 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
                         if sym.st_bind() == goblin::elf::sym::STB_WEAK {
                             log::warn!(
                                 "  -> Unresolved weak symbol '{}' ({})",
-                                sym_name,
+                                name,
                                 sym_bind_to_str(sym.st_bind())
                             );
+                            if let Some(addr) = H::weak_default() {
+                                updated_sym.st_value = addr as u64;
+                            }
                         } else {
                             log::warn!(
                                 "  -> Unresolved symbol '{}' ({})",
-                                sym_name,
+                                name,
                                 sym_bind_to_str(sym.st_bind())
                             );
                             return Err(ModuleErr::ENOENT);
@@ -664,16 +1688,19 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
                 }
                 goblin::elf::section_header::SHN_ABS => {
                     // Don't need to do anything
-                    log::debug!("Absolute symbol: {} 0x{:x}", sym_name, sym_value);
+                    log::debug!("Absolute symbol: {} 0x{:x}", name, sym_value);
                 }
                 goblin::elf::section_header::SHN_COMMON => {
-                    // Ignore common symbols
-                    // We compiled with -fno-common. These are not supposed to happen.
-                    log::debug!("Common symbol: {}", sym_name);
-                    log::warn!("{:?}: please compile with -fno-common", owner.name());
-                    return Err(ModuleErr::ENOEXEC);
+                    log::debug!("Common symbol: {}", name);
+                    // Only laid out when the helper allows common symbols
+                    let Some(base) = common_base else {
+                        log::warn!("{:?}: please compile with -fno-common", owner.name());
+                        return Err(ModuleErr::ENOEXEC);
+                    };
+                    updated_sym.st_value = base + common_offsets[&idx] as u64;
                 }
-                ty => {
+                _ => {
+                    let ty = self.sym_section_index(idx, &sym)?;
                     /* Divert to percpu allocation if a percpu var. */
                     // if (sym[i].st_shndx == info->index.pcpu)
                     //     secbase = (unsigned long)mod_percpu(mod);
@@ -684,11 +1711,16 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
                     // TODO: Handle special sections like percpu
                     // Normal symbol defined in a section
                     // Add section base address to symbol's offset within the section
-                    let secbase = self.elf.section_headers[ty as usize].sh_addr;
+                    let secbase = self
+                        .elf
+                        .section_headers
+                        .get(ty)
+                        .ok_or(ModuleErr::ENOEXEC)?
+                        .sh_addr;
                     updated_sym.st_value = sym.st_value.wrapping_add(secbase);
                     log::trace!(
                         "  -> Defined symbol '{}' in section {} at address 0x{:016x} (base: 0x{:016x} + offset: 0x{:016x})",
-                        sym_name,
+                        name,
                         ty,
                         updated_sym.st_value,
                         secbase,
@@ -698,75 +1730,225 @@ impl<'a, H: KernelModuleHelper> ModuleLoader<'a, H> {
             }
 
             // Push the updated symbol to the list
-            loadinfo.syms.push((updated_sym, sym_name));
+            loadinfo.syms.push((updated_sym, name));
         }
 
         Ok(loadinfo)
     }
 
+    /// The section index of the `idx`-th symbol. Objects with too many sections
+    /// for `st_shndx` set it to `SHN_XINDEX` and keep the real index in the
+    /// `SHT_SYMTAB_SHNDX` section instead.
+    fn sym_section_index(&self, idx: usize, sym: &goblin::elf::Sym) -> Result<usize> {
+        if sym.st_shndx != goblin::elf::section_header::SHN_XINDEX as usize {
+            return Ok(sym.st_shndx);
+        }
+        let Some(shndx) = self
+            .elf
+            .section_headers
+            .iter()
+            .find(|shdr| shdr.sh_type == goblin::elf::section_header::SHT_SYMTAB_SHNDX)
+        else {
+            log::error!(
+                "Symbol {} uses SHN_XINDEX without a SHT_SYMTAB_SHNDX section",
+                idx
+            );
+            return Err(ModuleErr::ENOEXEC);
+        };
+        let offset = shndx.sh_offset as usize + idx * size_of::<u32>();
+        let entry = self
+            .elf_data
+            .get(offset..offset + size_of::<u32>())
+            .ok_or(ModuleErr::ENOEXEC)?;
+        let entry = entry.try_into().unwrap();
+        let index = if self.elf.little_endian {
+            u32::from_le_bytes(entry)
+        } else {
+            u32::from_be_bytes(entry)
+        };
+        Ok(index as usize)
+    }
+
     /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/main.c#L1438>
+    ///
+    /// Relocation sections are applied one after another, only those that
+    /// patch `target_section` if it is given. If one of them fails, the
+    /// sections before it stay patched, but the module memory is owned by
+    /// `owner`, so it is released as a whole when [`load_module`] returns the
+    /// error.
+    ///
+    /// [`load_module`]: Self::load_module
     fn apply_relocations(
         &self,
-        load_info: ModuleLoadInfo,
+        load_info: &ModuleLoadInfo,
         owner: &mut ModuleOwner<H>,
+        target_section: Option<&str>,
     ) -> Result<()> {
-        for shdr in self.elf.section_headers.iter() {
-            let infosec = shdr.sh_info;
+        self.apply_relocation_sections(load_info, owner, target_section)
+            .map_err(|(shndx, index, err)| {
+                let sec_name = self
+                    .elf
+                    .shdr_strtab
+                    .get_at(self.elf.section_headers[shndx].sh_name)
+                    .unwrap_or("<unknown>");
+                match index {
+                    Some(index) => log::error!(
+                        "[{:?}]: Failed to apply relocation {} of section '{}' ({}): {:?}",
+                        owner.name(),
+                        index,
+                        sec_name,
+                        shndx,
+                        err
+                    ),
+                    None => log::error!(
+                        "[{:?}]: Failed to apply relocation section '{}' ({}): {:?}",
+                        owner.name(),
+                        sec_name,
+                        shndx,
+                        err
+                    ),
+                }
+                err
+            })?;
+        if !owner.reloc_errors.is_empty() {
+            log::warn!(
+                "[{:?}]: {} relocations failed, the module cannot run",
+                owner.name(),
+                owner.reloc_errors.len()
+            );
+        }
+        Ok(())
+    }
 
-            let sec_name = self
-                .elf
-                .shdr_strtab
-                .get_at(shdr.sh_name)
-                .ok_or(ModuleErr::ENOEXEC)?;
+    /// The `SHT_RELA` sections that apply to allocated sections, as the name
+    /// of the section they relocate and their entries, without applying
+    /// them. Malformed sections are skipped here; loading the module reports
+    /// them.
+    pub fn reloc_sections(&self) -> impl Iterator<Item = (&str, &[Rela])> {
+        self.rela_sections()
+            .filter_map(|res| res.ok())
+            .map(|(_, to_sec_name, rela_list)| (to_sec_name, rela_list))
+    }
 
-            // Not a valid relocation section?
-            if infosec >= self.elf.section_headers.len() as u32 {
-                continue;
-            }
-            // Don't bother with non-allocated sections
-            if self.elf.section_headers[infosec as usize].sh_flags
-                & goblin::elf::section_header::SHF_ALLOC as u64
-                == 0
-            {
-                continue;
-            }
+    /// The relocation sections to apply, as their index, the name of the
+    /// section they relocate and their entries. A malformed section yields
+    /// its index along with the error.
+    fn rela_sections(
+        &self,
+    ) -> impl Iterator<Item = core::result::Result<(usize, &str, &[Rela]), (usize, ModuleErr)>>
+    {
+        self.elf
+            .section_headers
+            .iter()
+            .enumerate()
+            .filter_map(|(shndx, shdr)| {
+                self.rela_section(shndx, shdr)
+                    .map_err(|err| (shndx, err))
+                    .transpose()
+            })
+    }
 
-            // Skip non-relocation sections
-            if shdr.sh_type != goblin::elf::section_header::SHT_RELA {
-                continue;
-            }
+    fn rela_section(
+        &self,
+        shndx: usize,
+        shdr: &SectionHeader,
+    ) -> Result<Option<(usize, &str, &[Rela])>> {
+        let infosec = shdr.sh_info;
+
+        let sec_name = self
+            .elf
+            .shdr_strtab
+            .get_at(shdr.sh_name)
+            .ok_or(ModuleErr::ENOEXEC)?;
+
+        // Not a valid relocation section?
+        if infosec >= self.elf.section_headers.len() as u32 {
+            return Ok(None);
+        }
+        // Don't bother with non-allocated sections
+        if self.elf.section_headers[infosec as usize].sh_flags
+            & goblin::elf::section_header::SHF_ALLOC as u64
+            == 0
+        {
+            return Ok(None);
+        }
 
-            let to_section = &self.elf.section_headers[infosec as usize];
-            let to_sec_name = self
-                .elf
-                .shdr_strtab
-                .get_at(to_section.sh_name)
-                .ok_or(ModuleErr::ENOEXEC)?;
+        // Skip non-relocation sections
+        if shdr.sh_type != goblin::elf::section_header::SHT_RELA {
+            return Ok(None);
+        }
 
-            let rela_entries = shdr.sh_size as usize / shdr.sh_entsize as usize;
+        let to_section = &self.elf.section_headers[infosec as usize];
+        let to_sec_name = self
+            .elf
+            .shdr_strtab
+            .get_at(to_section.sh_name)
+            .ok_or(ModuleErr::ENOEXEC)?;
+
+        if shdr.sh_size == 0 {
+            return Ok(None);
+        }
+        if shdr.sh_entsize as usize != size_of::<Rela>() {
             log::error!(
-                "Applying relocations for section '{}' to '{}', {} entries",
+                "Relocation section '{}' has invalid entry size {}",
                 sec_name,
-                to_sec_name,
-                rela_entries
+                shdr.sh_entsize
             );
+            return Err(ModuleErr::ENOEXEC);
+        }
 
-            let offset = shdr.sh_offset as usize;
-            // Size of Elf64_Rela
-            debug_assert!(shdr.sh_entsize == 24);
+        let offset = shdr.sh_offset as usize;
+        let Some(data_buf) = self.elf_data.get(offset..offset + shdr.sh_size as usize) else {
+            log::error!("Relocation section '{}' is out of bounds", sec_name);
+            return Err(ModuleErr::ENOEXEC);
+        };
+        let rela_list = unsafe {
+            goblin::elf64::reloc::from_raw_rela(data_buf.as_ptr() as _, shdr.sh_size as usize)
+        };
+        log::debug!(
+            "Relocation section '{}' for '{}', {} entries",
+            sec_name,
+            to_sec_name,
+            rela_list.len()
+        );
+        Ok(Some((shndx, to_sec_name, rela_list)))
+    }
 
-            let data_buf = &self.elf_data[offset..offset + shdr.sh_size as usize];
-            let rela_list = unsafe {
-                goblin::elf64::reloc::from_raw_rela(data_buf.as_ptr() as _, shdr.sh_size as usize)
-            };
+    /// Apply all relocation sections, returning the index of the section
+    /// that failed and, if known, of the relocation in it along with the
+    /// error.
+    fn apply_relocation_sections(
+        &self,
+        load_info: &ModuleLoadInfo,
+        owner: &mut ModuleOwner<H>,
+        target_section: Option<&str>,
+    ) -> core::result::Result<(), (usize, Option<usize>, ModuleErr)> {
+        for res in self.rela_sections() {
+            let (shndx, to_sec_name, rela_list) = res.map_err(|(shndx, err)| (shndx, None, err))?;
+            if target_section.is_some_and(|target| target != to_sec_name) {
+                continue;
+            }
+            let shdr = &self.elf.section_headers[shndx];
+            log::error!(
+                "Applying relocations to '{}', {} entries",
+                to_sec_name,
+                rela_list.len()
+            );
 
             crate::arch::ArchRelocate::apply_relocate_add(
                 rela_list,
                 shdr,
                 &self.elf.section_headers,
-                &load_info,
+                load_info,
                 owner,
-            )?;
+            )
+            .map_err(|err| {
+                (
+                    shndx,
+                    owner.failed_reloc.take().map(|reloc| reloc.index),
+                    err,
+                )
+            })?;
         }
         Ok(())
     }
@@ -796,6 +1978,38 @@ const fn sym_section_to_str(shndx: u32) -> &'static str {
 
 // #define SHN_LIVEPATCH	0xff20
 
+/// Resolve an undefined symbol by its raw name first, then, for Rust symbols,
+/// by its demangled path (without the hash), in case the exporter is keyed by
+/// that instead.
+fn resolve_symbol_name<H: KernelModuleHelper>(name: &str) -> Option<ResolvedSymbol> {
+    H::resolve_symbol_full(name).or_else(|| {
+        let demangled = crate::demangle_sym(name);
+        if demangled == name {
+            return None;
+        }
+        log::debug!("  -> Retrying '{}' as '{}'", name, demangled);
+        H::resolve_symbol_full(&demangled)
+    })
+}
+
+/// A module that is not GPL-compatible must not use GPL-only symbols.
+fn check_symbol_license<H: KernelModuleHelper>(
+    owner: &ModuleOwner<H>,
+    sym_name: &str,
+    license: SymbolLicense,
+) -> Result<()> {
+    if license == SymbolLicense::GplOnly && !owner.module_info.is_gpl_compatible() {
+        log::error!(
+            "{:?}: module using GPL-only symbol '{}' has incompatible license '{}'",
+            owner.name(),
+            sym_name,
+            owner.module_info.get("license").unwrap_or("<none>")
+        );
+        return Err(ModuleErr::EPERM);
+    }
+    Ok(())
+}
+
 /// Check if the ELF file is for a supported architecture
 fn elf_check_arch(elf: &goblin::elf::Elf) -> Result<()> {
     if elf.header.e_machine != goblin::elf::header::EM_AARCH64
@@ -815,3 +2029,1123 @@ fn elf_check_arch(elf: &goblin::elf::Elf) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{HookHelper, Hooks};
+
+    /// Allocates through [`crate::TestHelper`] and resolves nothing, whatever
+    /// hooks the test installs
+    struct NoopHelper;
+
+    impl KernelModuleHelper for NoopHelper {
+        fn vmalloc(size: usize) -> Option<Box<dyn SectionMemOps>> {
+            crate::TestHelper::vmalloc(size)
+        }
+
+        fn resolve_symbol(_name: &str) -> Option<usize> {
+            None
+        }
+    }
+
+    fn owner_with_license<H: KernelModuleHelper>(license: &str) -> ModuleOwner<H> {
+        let mut owner = ModuleOwner::empty();
+        owner
+            .module_info
+            .add_kv("license".to_string(), license.to_string());
+        owner
+    }
+
+    const TEXT_ADDR: u64 = 0x1000;
+
+    /// A symbol of [`build_object`]
+    #[derive(Clone, Copy)]
+    struct ObjSym<'a> {
+        name: &'a str,
+        shndx: u16,
+        value: u64,
+        size: u64,
+        bind: u8,
+    }
+
+    /// A global symbol `name` of size zero with the given `st_shndx` and
+    /// `st_value`
+    fn sym(name: &str, shndx: u16, value: u64) -> ObjSym<'_> {
+        ObjSym {
+            name,
+            shndx,
+            value,
+            size: 0,
+            bind: goblin::elf::sym::STB_GLOBAL,
+        }
+    }
+
+    impl ObjSym<'_> {
+        fn size(self, size: u64) -> Self {
+            Self { size, ..self }
+        }
+
+        fn weak(self) -> Self {
+            Self {
+                bind: goblin::elf::sym::STB_WEAK,
+                ..self
+            }
+        }
+    }
+
+    /// Build a minimal ELF64 relocatable object: a `.text` section (index 1,
+    /// at [`TEXT_ADDR`]) and the given symbols, plus a `SHT_SYMTAB_SHNDX`
+    /// table if `xindex` is given. Each entry of `relas` adds a `.rela.text`
+    /// section with the given `(r_offset, symbol index, type)` relocations
+    /// and zero addends.
+    fn build_object(
+        syms: &[ObjSym],
+        xindex: Option<&[u32]>,
+        relas: &[&[(u64, u32, u32)]],
+    ) -> Vec<u8> {
+        use goblin::elf::section_header::*;
+
+        let mut strtab = alloc::vec![0u8];
+        let mut symtab = alloc::vec![0u8; 24];
+        for &ObjSym {
+            name,
+            shndx,
+            value,
+            size,
+            bind,
+        } in syms
+        {
+            let st_name = strtab.len() as u32;
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+            symtab.extend_from_slice(&st_name.to_le_bytes());
+            symtab.push(bind << 4);
+            symtab.push(0);
+            symtab.extend_from_slice(&shndx.to_le_bytes());
+            symtab.extend_from_slice(&value.to_le_bytes());
+            symtab.extend_from_slice(&size.to_le_bytes());
+        }
+        let shndx_table: Vec<u8> = xindex
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|idx| idx.to_le_bytes())
+            .collect();
+        let rela_tables: Vec<Vec<u8>> = relas
+            .iter()
+            .map(|relas| {
+                relas
+                    .iter()
+                    .flat_map(|&(offset, sym, ty)| {
+                        let mut rela = [0u8; 24];
+                        rela[..8].copy_from_slice(&offset.to_le_bytes());
+                        let info = ((sym as u64) << 32) | ty as u64;
+                        rela[8..16].copy_from_slice(&info.to_le_bytes());
+                        rela
+                    })
+                    .collect()
+            })
+            .collect();
+        let shstrtab =
+            b"\0.text\0.symtab\0.strtab\0.shstrtab\0.symtab_shndx\0.rela.text\0.altinstructions\0.init_array\0__ex_table\0__bug_table\0__jump_table\0";
+
+        // (name offset, type, flags, link, info, entsize, addr, data)
+        type Section<'a> = (u32, u32, u32, u32, u32, u64, u64, &'a [u8]);
+        let mut sections: Vec<Section> = alloc::vec![
+            (0, SHT_NULL, 0, 0, 0, 0, 0, &[]),
+            (
+                1,
+                SHT_PROGBITS,
+                SHF_ALLOC | SHF_EXECINSTR,
+                0,
+                0,
+                0,
+                TEXT_ADDR,
+                &[0; 16]
+            ),
+            (7, SHT_SYMTAB, 0, 3, 1, 24, 0, &symtab),
+            (15, SHT_STRTAB, 0, 0, 0, 0, 0, &strtab),
+            (23, SHT_STRTAB, 0, 0, 0, 0, 0, shstrtab),
+        ];
+        if xindex.is_some() {
+            sections.push((33, SHT_SYMTAB_SHNDX, 0, 2, 0, 4, 0, &shndx_table));
+        }
+        for table in &rela_tables {
+            sections.push((47, SHT_RELA, SHF_INFO_LINK, 2, 1, 24, 0, table));
+        }
+
+        let mut elf = alloc::vec![0u8; 64];
+        let mut offsets = Vec::new();
+        for section in &sections {
+            elf.resize(elf.len().next_multiple_of(8), 0);
+            offsets.push(elf.len() as u64);
+            elf.extend_from_slice(section.7);
+        }
+        elf.resize(elf.len().next_multiple_of(8), 0);
+        let shoff = elf.len() as u64;
+        for (section, offset) in sections.iter().zip(offsets) {
+            let (name, ty, flags, link, info, entsize, addr, data) = *section;
+            elf.extend_from_slice(&name.to_le_bytes());
+            elf.extend_from_slice(&ty.to_le_bytes());
+            elf.extend_from_slice(&(flags as u64).to_le_bytes());
+            elf.extend_from_slice(&addr.to_le_bytes());
+            elf.extend_from_slice(&offset.to_le_bytes());
+            elf.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            elf.extend_from_slice(&link.to_le_bytes());
+            elf.extend_from_slice(&info.to_le_bytes());
+            elf.extend_from_slice(&8u64.to_le_bytes());
+            elf.extend_from_slice(&entsize.to_le_bytes());
+        }
+
+        elf[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+        elf[16..18].copy_from_slice(&1u16.to_le_bytes()); // ET_REL
+        elf[18..20].copy_from_slice(&62u16.to_le_bytes()); // EM_X86_64
+        elf[20..24].copy_from_slice(&1u32.to_le_bytes());
+        elf[40..48].copy_from_slice(&shoff.to_le_bytes());
+        elf[52..54].copy_from_slice(&64u16.to_le_bytes());
+        elf[58..60].copy_from_slice(&64u16.to_le_bytes());
+        elf[60..62].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+        elf[62..64].copy_from_slice(&4u16.to_le_bytes());
+        elf
+    }
+
+    #[test]
+    fn test_simplify_symbols_xindex() {
+        let xindex = goblin::elf::section_header::SHN_XINDEX as u16;
+        let object = build_object(
+            &[sym("direct", 1, 0x10), sym("extended", xindex, 0x20)],
+            Some(&[0, 0, 1]),
+            &[],
+        );
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        let owner = owner_with_license("GPL");
+        let loadinfo = loader.simplify_symbols(&owner).unwrap();
+        assert_eq!(loadinfo.syms[1].0.st_value, TEXT_ADDR + 0x10);
+        assert_eq!(loadinfo.syms[2].0.st_value, TEXT_ADDR + 0x20);
+
+        // SHN_XINDEX without the table is malformed
+        let object = build_object(&[sym("extended", xindex, 0x20)], None, &[]);
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        assert_eq!(
+            loader.simplify_symbols(&owner).err(),
+            Some(ModuleErr::ENOEXEC)
+        );
+    }
+
+    #[cfg(feature = "demangle")]
+    #[test]
+    fn test_resolve_mangled_symbols() {
+        const INIT: &str = "_ZN5hello4init17h0123456789abcdefE";
+        const EXIT: &str = "_ZN5hello4exit17h0123456789abcdefE";
+
+        Hooks {
+            resolve_symbol: Some(|name| match name {
+                INIT => Some(0x100),
+                "hello::exit" => Some(0x200),
+                _ => None,
+            }),
+            ..Default::default()
+        }
+        .install();
+
+        let object = build_object(&[sym(INIT, 0, 0), sym(EXIT, 0, 0)], None, &[]);
+        let loader = ModuleLoader::<HookHelper>::new(&object).unwrap();
+        let owner = owner_with_license("GPL");
+        let loadinfo = loader.simplify_symbols(&owner).unwrap();
+        // Only known by its mangled name
+        assert_eq!(loadinfo.syms[1].0.st_value, 0x100);
+        // Only known by its demangled path
+        assert_eq!(loadinfo.syms[2].0.st_value, 0x200);
+    }
+
+    struct VecMem(Vec<u8>);
+
+    impl SectionMemOps for VecMem {
+        fn as_ptr(&self) -> *const u8 {
+            self.0.as_ptr()
+        }
+
+        fn as_mut_ptr(&mut self) -> *mut u8 {
+            self.0.as_mut_ptr()
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn change_perms(&mut self, _perms: SectionPerm) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_reject_big_endian() {
+        // A bare big-endian ELF64 relocatable header for x86_64
+        let mut elf = [0u8; 64];
+        elf[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 2, 1]);
+        elf[16..18].copy_from_slice(&1u16.to_be_bytes()); // ET_REL
+        elf[18..20].copy_from_slice(&62u16.to_be_bytes()); // EM_X86_64
+        elf[20..24].copy_from_slice(&1u32.to_be_bytes());
+        elf[52..54].copy_from_slice(&64u16.to_be_bytes());
+        assert!(!Elf::parse(&elf).unwrap().little_endian);
+        assert_eq!(
+            ModuleLoader::<NoopHelper>::new(&elf).err(),
+            Some(ModuleErr::ENOEXEC)
+        );
+
+        elf[5] = 1;
+        elf[16..18].copy_from_slice(&1u16.to_le_bytes());
+        elf[18..20].copy_from_slice(&62u16.to_le_bytes());
+        elf[20..24].copy_from_slice(&1u32.to_le_bytes());
+        elf[52..54].copy_from_slice(&64u16.to_le_bytes());
+        assert!(ModuleLoader::<NoopHelper>::new(&elf).is_ok());
+    }
+
+    #[test]
+    fn test_osabi() {
+        const ELFOSABI_FREEBSD: u8 = 9;
+
+        Hooks {
+            strict_osabi: true,
+            ..Default::default()
+        }
+        .install();
+
+        let mut object = build_object(&[], None, &[]);
+        for osabi in [
+            goblin::elf::header::ELFOSABI_SYSV,
+            goblin::elf::header::ELFOSABI_LINUX,
+        ] {
+            object[goblin::elf::header::EI_OSABI] = osabi;
+            assert!(ModuleLoader::<HookHelper>::new(&object).is_ok());
+        }
+
+        object[goblin::elf::header::EI_OSABI] = ELFOSABI_FREEBSD;
+        // Only warned about by default
+        assert!(ModuleLoader::<NoopHelper>::new(&object).is_ok());
+        assert_eq!(
+            ModuleLoader::<HookHelper>::new(&object).err(),
+            Some(ModuleErr::ENOEXEC)
+        );
+    }
+
+    #[test]
+    fn test_section_mem_fill() {
+        let mut mem = VecMem(alloc::vec![0xaa; 4096]);
+        assert_eq!(mem.len(), 4096);
+        assert!(!mem.is_empty());
+        mem.fill(0);
+        assert!(mem.0.iter().all(|&b| b == 0));
+        mem.fill(0x5a);
+        assert!(mem.0.iter().all(|&b| b == 0x5a));
+    }
+
+    #[test]
+    fn test_section_perm_from_elf_flags() {
+        use goblin::elf::section_header::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE};
+
+        for bits in 0..8u32 {
+            let flags = [SHF_ALLOC, SHF_WRITE, SHF_EXECINSTR]
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| bits & (1 << i) != 0)
+                .fold(0u64, |flags, (_, flag)| flags | flag as u64);
+            let perms = SectionPerm::from_elf_flags(flags);
+            assert_eq!(
+                perms.contains(SectionPerm::WRITE),
+                flags & SHF_WRITE as u64 != 0
+            );
+            assert_eq!(
+                perms.contains(SectionPerm::EXECUTE),
+                flags & SHF_EXECINSTR as u64 != 0
+            );
+            // Readable whenever it is anything at all
+            assert_eq!(perms.contains(SectionPerm::READ), flags != 0, "{flags:#x}");
+        }
+        assert_eq!(
+            SectionPerm::from_elf_flags(SHF_EXECINSTR as u64),
+            SectionPerm::READ | SectionPerm::EXECUTE
+        );
+    }
+
+    #[test]
+    fn test_layout_allocation_failure() {
+        let object = build_object(&[], None, &[]);
+
+        // Fail every allocation larger than a page
+        Hooks {
+            vmalloc: Some(|size| {
+                (size <= 4096).then(|| Box::new(VecMem(alloc::vec![0; size])) as _)
+            }),
+            ..Default::default()
+        }
+        .install();
+        let mut loader = ModuleLoader::<HookHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        assert_eq!(owner.pages.len(), 1);
+        assert_eq!(owner.pages[0].size, 4096);
+
+        Hooks {
+            vmalloc: Some(|size| {
+                (size <= 2048).then(|| Box::new(VecMem(alloc::vec![0; size])) as _)
+            }),
+            ..Default::default()
+        }
+        .install();
+        let mut loader = ModuleLoader::<HookHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        assert_eq!(
+            loader.layout_and_allocate(&mut owner, None).err(),
+            Some(ModuleErr::ENOMEM)
+        );
+    }
+
+    #[test]
+    fn test_layout_page_size() {
+        let object = build_object(&[], None, &[]);
+        let mut loader = ModuleLoader::<HookHelper<16384>>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        assert_eq!(owner.pages.len(), 1);
+        assert_eq!(owner.pages[0].size, 16384);
+        assert_eq!(owner.pages[0].addr.len(), 16384);
+    }
+
+    #[test]
+    fn test_apply_relocations_reports_failing_section() {
+        const R_NONE: u32 = 0;
+        const R_INVALID: u32 = 0xfe;
+
+        let object = build_object(
+            &[],
+            None,
+            &[&[(0, 0, R_NONE)], &[(0, 0, R_NONE), (0, 0, R_INVALID)]],
+        );
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        let loadinfo = loader.simplify_symbols(&owner).unwrap();
+        // The second entry of the second `.rela.text`, after the five fixed
+        // sections
+        assert_eq!(
+            loader.apply_relocation_sections(&loadinfo, &mut owner, None),
+            Err((6, Some(1), ModuleErr::ENOEXEC))
+        );
+        assert_eq!(
+            loader.apply_relocations(&loadinfo, &mut owner, None),
+            Err(ModuleErr::ENOEXEC)
+        );
+
+        let object = build_object(&[], None, &[&[(0, 0, R_NONE)], &[(0, 0, R_NONE)]]);
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        let loadinfo = loader.simplify_symbols(&owner).unwrap();
+        assert!(
+            loader
+                .apply_relocations(&loadinfo, &mut owner, None)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_relocation_section_entsize() {
+        const R_NONE: u32 = 0;
+        // sh_entsize of the `.rela.text` section header, the sixth one
+        let set_entsize = |object: &mut Vec<u8>, entsize: u64| {
+            let shoff = u64::from_le_bytes(object[40..48].try_into().unwrap()) as usize;
+            let field = shoff + 5 * 64 + 56;
+            object[field..field + 8].copy_from_slice(&entsize.to_le_bytes());
+        };
+
+        for entsize in [0, 16] {
+            let mut object = build_object(&[], None, &[&[(0, 0, R_NONE)]]);
+            set_entsize(&mut object, entsize);
+            let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+            let mut owner = owner_with_license("GPL");
+            let loadinfo = loader.simplify_symbols(&owner).unwrap();
+            assert_eq!(
+                loader.apply_relocations(&loadinfo, &mut owner, None),
+                Err(ModuleErr::ENOEXEC)
+            );
+        }
+
+        // An empty relocation section is skipped whatever its entry size
+        let mut object = build_object(&[], None, &[&[]]);
+        set_entsize(&mut object, 0);
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        let loadinfo = loader.simplify_symbols(&owner).unwrap();
+        assert!(
+            loader
+                .apply_relocations(&loadinfo, &mut owner, None)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_relocation_histogram() {
+        const R_NONE: u32 = 0;
+        const R_64: u32 = 1;
+        const R_INVALID: u32 = 0xfe;
+
+        let object = build_object(
+            &[],
+            None,
+            &[
+                &[(0, 0, R_NONE), (0, 0, R_64)],
+                &[(0, 0, R_64), (0, 0, R_INVALID), (0, 0, R_64)],
+            ],
+        );
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        let histogram = loader.relocation_histogram();
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            [(R_NONE, 1), (R_64, 3), (R_INVALID, 1)]
+        );
+        assert!(loader.unsupported_relocations().contains(&R_INVALID));
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(loader.unsupported_relocations(), [R_INVALID]);
+    }
+
+    #[test]
+    fn test_supported_relocations() {
+        let supported = ModuleLoader::<NoopHelper>::supported_relocations();
+        assert!(
+            supported
+                .iter()
+                .all(|&ty| crate::ArchRelocationType::try_from(ty).is_ok())
+        );
+        #[cfg(target_arch = "x86_64")]
+        {
+            use goblin::elf::reloc::{R_X86_64_64, R_X86_64_COPY};
+
+            assert!(supported.contains(&R_X86_64_64));
+            assert!(!supported.contains(&R_X86_64_COPY));
+        }
+    }
+
+    #[test]
+    fn test_missing_sections() {
+        let object = build_object(&[], None, &[]);
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        assert_eq!(
+            loader.find_section(".text").map(|shdr| shdr.sh_addr),
+            Ok(TEXT_ADDR)
+        );
+        assert_eq!(
+            loader.find_section(".modinfo").err(),
+            Some(ModuleErr::ENOENT)
+        );
+        assert_eq!(
+            loader.load_module(CString::default()).err(),
+            Some(ModuleErr::ENOENT)
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_weak_default() {
+        use goblin::elf::reloc::R_X86_64_64;
+
+        const STUB: usize = 0xffff_8000_0000_1000;
+
+        Hooks {
+            weak_default: Some(STUB),
+            ..Default::default()
+        }
+        .install();
+
+        // `extern void hook(void) __attribute__((weak));` with `.quad hook`
+        // at the start of `.text`
+        let object = build_object(&[sym("hook", 0, 0).weak()], None, &[&[(0, 1, R_X86_64_64)]]);
+
+        let mut loader = ModuleLoader::<HookHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        let load_info = loader.simplify_symbols(&owner).unwrap();
+        assert_eq!(load_info.syms[1].0.st_value, STUB as u64);
+        loader
+            .apply_relocations(&load_info, &mut owner, None)
+            .unwrap();
+        let text = owner.memory_map()[0].base as *const u64;
+        assert_eq!(unsafe { text.read() }, STUB as u64);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_relative_bias_is_placed_address() {
+        use goblin::elf::reloc::R_X86_64_RELATIVE;
+
+        // `.text` is linked at TEXT_ADDR, not 0
+        let object = build_object(&[], None, &[&[(0, 0, R_X86_64_RELATIVE)]]);
+
+        let mut loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        let load_info = loader.simplify_symbols(&owner).unwrap();
+        loader
+            .apply_relocations(&load_info, &mut owner, None)
+            .unwrap();
+        let text = owner.memory_map()[0].base;
+        assert_ne!(text as u64, TEXT_ADDR);
+        assert_eq!(unsafe { (text as *const u64).read() }, text as u64);
+    }
+
+    #[test]
+    fn test_common_symbols() {
+        use goblin::elf::section_header::SHN_COMMON;
+
+        Hooks {
+            allow_common_symbols: true,
+            ..Default::default()
+        }
+        .install();
+
+        // `int a; long b[4] __attribute__((aligned(16)));` built with -fcommon
+        let object = build_object(
+            &[
+                sym("a", SHN_COMMON as u16, 4).size(4),
+                sym("b", SHN_COMMON as u16, 16).size(32),
+            ],
+            None,
+            &[],
+        );
+
+        let mut loader = ModuleLoader::<HookHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        let load_info = loader.simplify_symbols(&owner).unwrap();
+        let common = owner
+            .memory_map()
+            .into_iter()
+            .find(|entry| entry.name == COMMON_SECTION)
+            .unwrap();
+        assert_eq!(common.perms, SectionPerm::READ | SectionPerm::WRITE);
+        assert_eq!(load_info.syms[1].0.st_value, common.base as u64);
+        assert_eq!(load_info.syms[2].0.st_value, common.base as u64 + 16);
+        let bss = unsafe { core::slice::from_raw_parts(common.base as *const u8, 48) };
+        assert!(bss.iter().all(|&b| b == 0));
+
+        // Refused unless allowed
+        let mut loader = ModuleLoader::<crate::TestHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        assert!(owner.layout().all(|(name, ..)| name != COMMON_SECTION));
+        assert_eq!(
+            loader.simplify_symbols(&owner).err(),
+            Some(ModuleErr::ENOEXEC)
+        );
+    }
+
+    #[test]
+    fn test_symbolize() {
+        let object = build_object(&[sym("first", 1, 0), sym("second", 1, 8)], None, &[]);
+        let mut loader = ModuleLoader::<crate::TestHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        let load_info = loader.simplify_symbols(&owner).unwrap();
+        owner.set_symbols(&load_info);
+
+        let text = owner.memory_map()[0].base;
+        let names: Vec<_> = owner
+            .symbols()
+            .iter()
+            .map(|sym| sym.name.as_str())
+            .collect();
+        assert_eq!(names, ["first", "second"]);
+        assert_eq!(owner.symbolize(text), Some(("first".to_string(), 0)));
+        assert_eq!(owner.symbolize(text + 5), Some(("first".to_string(), 5)));
+        assert_eq!(owner.symbolize(text + 9), Some(("second".to_string(), 1)));
+        assert_eq!(owner.symbolize(text - 1), None);
+        assert_eq!(owner.symbolize(text + owner.memory_map()[0].size), None);
+    }
+
+    #[test]
+    fn test_undefined_symbols() {
+        let object = build_object(
+            &[
+                sym("strlen", 0, 0),
+                sym("my_init", 1, 0),
+                sym("printk", 0, 0),
+            ],
+            None,
+            &[],
+        );
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        assert_eq!(loader.undefined_symbols(), ["strlen", "printk"]);
+
+        let object = build_object(&[sym("my_init", 1, 0)], None, &[]);
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        assert!(loader.undefined_symbols().is_empty());
+    }
+
+    #[test]
+    fn test_reloc_sections() {
+        const R_NONE: u32 = 0;
+        const R_64: u32 = 1;
+
+        let object = build_object(
+            &[],
+            None,
+            &[&[(0, 0, R_NONE), (0, 0, R_64)], &[], &[(0, 0, R_64)]],
+        );
+        let loader = ModuleLoader::<NoopHelper>::new(&object).unwrap();
+        let sections: Vec<_> = loader
+            .reloc_sections()
+            .map(|(name, relas)| {
+                let types: Vec<_> = relas.iter().map(|rela| rela.r_info as u32).collect();
+                (name, types)
+            })
+            .collect();
+        // Empty relocation sections are left out
+        assert_eq!(
+            sections,
+            [
+                (".text", alloc::vec![R_NONE, R_64]),
+                (".text", alloc::vec![R_64])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_init_pages_freed_after_init() {
+        unsafe extern "C" fn init_ok() -> core::ffi::c_int {
+            0
+        }
+        unsafe extern "C" fn init_fail() -> core::ffi::c_int {
+            -1
+        }
+
+        let owner_with_sections = |init_fn| {
+            let mut owner = owner_with_license::<NoopHelper>("GPL");
+            owner.module = Module::new(Some(init_fn), None);
+            for (i, name) in [".text", ".init.text", ".text.init", ".data", ".init.data"]
+                .into_iter()
+                .enumerate()
+            {
+                owner.pages.push(SectionPages {
+                    name: name.to_string(),
+                    addr: Box::new(VecMem(alloc::vec![0; 4096])),
+                    offset: i * 4096,
+                    size: 4096,
+                    perms: SectionPerm::READ,
+                    init: is_init_section(name),
+                });
+            }
+            owner
+        };
+        let names = |owner: &ModuleOwner<NoopHelper>| {
+            owner
+                .pages
+                .iter()
+                .map(|page| page.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut owner = owner_with_sections(init_ok);
+        assert_eq!(owner.call_init(), Ok(0));
+        assert_eq!(names(&owner), [".text", ".data"]);
+
+        // A failed init leaves the module intact for the caller to unload
+        let mut owner = owner_with_sections(init_fail);
+        assert_eq!(owner.call_init(), Ok(-1));
+        assert_eq!(owner.pages.len(), 5);
+    }
+
+    #[test]
+    fn test_module_state_transitions() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        unsafe extern "C" fn init_ok() -> core::ffi::c_int {
+            0
+        }
+        unsafe extern "C" fn init_fail() -> core::ffi::c_int {
+            -1
+        }
+        static EXITS: AtomicUsize = AtomicUsize::new(0);
+        unsafe extern "C" fn exit() {
+            EXITS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // As left by load_module
+        let loaded = |init_fn| {
+            let mut owner = owner_with_license::<NoopHelper>("GPL");
+            owner.module = Module::new(Some(init_fn), Some(exit));
+            owner.module.set_state(ModuleState::Coming);
+            owner
+        };
+
+        let mut owner = loaded(init_ok);
+        assert_eq!(owner.state(), ModuleState::Coming);
+        assert_eq!(owner.call_init(), Ok(0));
+        assert_eq!(owner.state(), ModuleState::Live);
+        owner.unload();
+        assert_eq!(owner.state(), ModuleState::Going);
+        assert_eq!(EXITS.load(Ordering::Relaxed), 1);
+
+        // The exit function of a module that failed to init never runs
+        let mut owner = loaded(init_fail);
+        assert_eq!(owner.call_init(), Ok(-1));
+        assert_eq!(owner.state(), ModuleState::Going);
+        owner.unload();
+        assert_eq!(EXITS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_gpl_only_symbol_license() {
+        let proprietary = owner_with_license::<NoopHelper>("Proprietary");
+        assert_eq!(
+            check_symbol_license(&proprietary, "gpl_sym", SymbolLicense::GplOnly),
+            Err(ModuleErr::EPERM)
+        );
+        assert!(check_symbol_license(&proprietary, "sym", SymbolLicense::Any).is_ok());
+
+        let gpl = owner_with_license::<NoopHelper>("GPL");
+        assert!(check_symbol_license(&gpl, "gpl_sym", SymbolLicense::GplOnly).is_ok());
+        let dual = owner_with_license::<NoopHelper>("Dual MIT/GPL");
+        assert!(check_symbol_license(&dual, "gpl_sym", SymbolLicense::GplOnly).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_symbol_full() {
+        // Export `gpl_sym` through the full resolver only
+        Hooks {
+            resolve_symbol: Some(|_| {
+                unreachable!("the loader resolves through resolve_symbol_full")
+            }),
+            resolve_symbol_full: Some(|name| {
+                (name == "gpl_sym").then_some(ResolvedSymbol {
+                    addr: 0x1234,
+                    license: SymbolLicense::GplOnly,
+                    crc: Some(0xabcd),
+                })
+            }),
+            ..Default::default()
+        }
+        .install();
+
+        let object = build_object(&[sym("gpl_sym", 0, 0)], None, &[]);
+        let loader = ModuleLoader::<HookHelper>::new(&object).unwrap();
+        let load_info = loader.simplify_symbols(&owner_with_license("GPL")).unwrap();
+        let (sym, _) = load_info
+            .syms
+            .iter()
+            .find(|(_, name)| name == "gpl_sym")
+            .unwrap();
+        assert_eq!(sym.st_value, 0x1234);
+        assert_eq!(
+            loader
+                .simplify_symbols(&owner_with_license("Proprietary"))
+                .err(),
+            Some(ModuleErr::EPERM)
+        );
+        assert_eq!(
+            resolve_symbol_name::<HookHelper>("gpl_sym").and_then(|sym| sym.crc),
+            Some(0xabcd)
+        );
+        let full = <HookHelper>::resolve_symbol_full("gpl_sym");
+
+        // Export `gpl_sym` through the separate license and CRC hooks, which
+        // the default combines
+        Hooks {
+            resolve_symbol_ex: Some(|name| {
+                (name == "gpl_sym").then_some((0x1234, SymbolLicense::GplOnly))
+            }),
+            symbol_crc: Some(|_| Some(0xabcd)),
+            ..Default::default()
+        }
+        .install();
+        assert_eq!(<HookHelper>::resolve_symbol_full("gpl_sym"), full);
+        assert_eq!(<HookHelper>::resolve_symbol_full("sym"), None);
+    }
+
+    #[test]
+    fn test_show_and_store_param() {
+        use core::ffi::c_int;
+
+        use kmod_tools::KernelParam;
+
+        let mut value: c_int = 42;
+        let mut params = [KernelParam::new(
+            c"debug_level",
+            &kapi::param::param_ops_int,
+            (&raw mut value).cast(),
+            0o644,
+            0,
+        )];
+        let mut owner = ModuleOwner::<crate::TestHelper>::empty();
+        let raw = owner.module.raw_mod();
+        raw.kp = params.as_mut_ptr().cast();
+        raw.num_kp = params.len() as _;
+
+        assert_eq!(owner.show_param("debug_level").unwrap(), "42\n");
+        owner.store_param("debug-level", "7").unwrap();
+        assert_eq!(owner.show_param("debug_level").unwrap(), "7\n");
+        assert_eq!(
+            owner.store_param("debug_level", "seven"),
+            Err(ModuleErr::EINVAL)
+        );
+        assert_eq!(owner.show_param("verbose"), Err(ModuleErr::ENOENT));
+        assert_eq!(owner.store_param("verbose", "1"), Err(ModuleErr::ENOENT));
+        assert_eq!(value, 7);
+    }
+
+    #[test]
+    fn test_read_this_module_unaligned() {
+        unsafe extern "C" fn init() -> core::ffi::c_int {
+            0
+        }
+
+        let mut module = Module::new(Some(init), None);
+        let name = &mut module.raw_mod().name;
+        for (dst, &src) in name.iter_mut().zip(b"hello\0") {
+            *dst = src as _;
+        }
+
+        // Place the struct one byte past an aligned address
+        let size = core::mem::size_of::<Module>();
+        let mut buf = alloc::vec![0u64; size / 8 + 2];
+        let unaligned = unsafe { buf.as_mut_ptr().cast::<u8>().add(1) };
+        unsafe { core::ptr::write_unaligned(unaligned.cast::<Module>(), module) };
+
+        let module = unsafe { read_this_module(unaligned) };
+        assert_eq!(module.name(), "hello");
+        assert_eq!(
+            module.init_fn().map(|f| f as *const ()),
+            Some(init as *const ())
+        );
+    }
+
+    /// Rename the `.text` section of a [`build_object`] object to `name`,
+    /// which must be in its `.shstrtab`, and replace its 16 bytes of contents
+    fn rename_text(object: &mut [u8], name: &str, contents: &[u8]) {
+        let shdr_field = |object: &[u8], idx: usize, field: usize| {
+            let shoff = u64::from_le_bytes(object[40..48].try_into().unwrap()) as usize;
+            let at = shoff + idx * 64 + field;
+            u64::from_le_bytes(object[at..at + 8].try_into().unwrap()) as usize
+        };
+        let shstrtab = shdr_field(object, 4, 24);
+        let mut needle = alloc::vec![0u8];
+        needle.extend_from_slice(name.as_bytes());
+        needle.push(0);
+        let name_offset = object[shstrtab..]
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .unwrap()
+            + 1;
+        let text_shdr = u64::from_le_bytes(object[40..48].try_into().unwrap()) as usize + 64;
+        object[text_shdr..text_shdr + 4].copy_from_slice(&(name_offset as u32).to_le_bytes());
+        let offset = shdr_field(object, 1, 24);
+        object[offset..offset + contents.len()].copy_from_slice(contents);
+    }
+
+    #[test]
+    fn test_apply_alternatives_hook() {
+        extern crate std;
+
+        use std::sync::Mutex;
+
+        static CALLS: Mutex<Vec<(String, Vec<u8>)>> = Mutex::new(Vec::new());
+
+        Hooks {
+            apply_alternatives: Some(|name, section| {
+                CALLS
+                    .lock()
+                    .unwrap()
+                    .push((name.to_string(), section.to_vec()));
+            }),
+            ..Default::default()
+        }
+        .install();
+
+        let load = |object: &[u8]| {
+            let mut loader = ModuleLoader::<HookHelper>::new(object).unwrap();
+            let mut owner = ModuleOwner::empty();
+            loader.layout_and_allocate(&mut owner, None).unwrap();
+            loader.module_finalize(&owner);
+        };
+
+        // No alternatives, no call
+        let mut object = build_object(&[], None, &[]);
+        load(&object);
+        assert!(CALLS.lock().unwrap().is_empty());
+
+        let contents: Vec<u8> = (1..=16).collect();
+        rename_text(&mut object, ".altinstructions", &contents);
+        load(&object);
+        assert_eq!(
+            *CALLS.lock().unwrap(),
+            [(".altinstructions".to_string(), contents)]
+        );
+    }
+
+    #[test]
+    fn test_ctors_run_before_init() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static STEP: AtomicUsize = AtomicUsize::new(0);
+
+        unsafe extern "C" fn first() {
+            let _ = STEP.compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst);
+        }
+
+        unsafe extern "C" fn second() {
+            let _ = STEP.compare_exchange(1, 2, Ordering::SeqCst, Ordering::SeqCst);
+        }
+
+        unsafe extern "C" fn init() -> core::ffi::c_int {
+            if STEP.load(Ordering::SeqCst) == 2 {
+                0
+            } else {
+                -1
+            }
+        }
+
+        unsafe extern "C" fn exit() {}
+
+        let mut object = build_object(&[], None, &[]);
+        rename_text(&mut object, ".init_array", &[0; 16]);
+        let mut loader = ModuleLoader::<crate::TestHelper>::new(&object).unwrap();
+        let mut owner = ModuleOwner::with_fns(init, exit);
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+
+        // Stand in for the R_X86_64_64 relocations of `.rela.init_array`
+        let init_array = owner.memory_map()[0].base as *mut usize;
+        unsafe {
+            init_array.write(first as *const () as usize);
+            init_array.add(1).write(second as *const () as usize);
+        }
+        loader.find_module_sections(&mut owner).unwrap();
+        assert_eq!(STEP.load(Ordering::SeqCst), 0);
+
+        assert_eq!(owner.call_init(), Ok(0));
+        assert_eq!(STEP.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_exception_table() {
+        const R_X86_64_PC32: u32 = 2;
+
+        // One 12-byte entry pointing at offsets 4 and 12 of its own section
+        let mut object = build_object(
+            &[sym("insn", 1, 4), sym("fixup", 1, 12)],
+            None,
+            // The `insn` and `fixup` fields
+            &[&[(0, 1, R_X86_64_PC32), (4, 2, R_X86_64_PC32)]],
+        );
+        rename_text(&mut object, "__ex_table", &[0; 16]);
+
+        let mut loader = ModuleLoader::<crate::TestHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        let load_info = loader.simplify_symbols(&owner).unwrap();
+        loader
+            .apply_relocations(&load_info, &mut owner, None)
+            .unwrap();
+        loader.find_module_sections(&mut owner).unwrap();
+
+        let table = owner.exception_table();
+        assert_eq!(table.len(), 1);
+        let base = table.as_ptr() as i64;
+        let entry = &table[0];
+        assert_eq!((entry.insn, entry.fixup), (4, 8));
+        assert_eq!(&raw const entry.insn as i64 + entry.insn as i64, base + 4);
+        assert_eq!(
+            &raw const entry.fixup as i64 + entry.fixup as i64,
+            base + 12
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_bug_entries() {
+        const R_X86_64_PC32: u32 = 2;
+
+        // One 12-byte entry whose bug site and file name are at offsets 12
+        // and 14 of its own section
+        let mut object = build_object(
+            &[sym("bug_site", 1, 12), sym("file", 1, 14)],
+            None,
+            // The `bug_addr_disp` and `file_disp` fields
+            &[&[(0, 1, R_X86_64_PC32), (4, 2, R_X86_64_PC32)]],
+        );
+        let mut contents = [0u8; 16];
+        contents[8..10].copy_from_slice(&42u16.to_le_bytes());
+        contents[10..12].copy_from_slice(&1u16.to_le_bytes());
+        rename_text(&mut object, "__bug_table", &contents);
+
+        let mut loader = ModuleLoader::<crate::TestHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        let load_info = loader.simplify_symbols(&owner).unwrap();
+        loader
+            .apply_relocations(&load_info, &mut owner, None)
+            .unwrap();
+        loader.find_module_sections(&mut owner).unwrap();
+
+        let bugs = owner.bug_entries();
+        assert_eq!(bugs.len(), 1);
+        let base = bugs.as_ptr() as usize;
+        let bug = &bugs[0];
+        assert_eq!((bug.bug_addr_disp, bug.file_disp), (12, 10));
+        assert_eq!((bug.line, bug.flags), (42, 1));
+        assert_eq!(crate::bug_addr(bug), base + 12);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_jump_entries() {
+        extern crate std;
+
+        use std::sync::Mutex;
+
+        const R_X86_64_PC32: u32 = 2;
+        const R_X86_64_PC64: u32 = 24;
+
+        static ARMED: Mutex<Vec<(usize, usize, usize)>> = Mutex::new(Vec::new());
+
+        Hooks {
+            patch_jump_entry: Some(|entry| {
+                ARMED.lock().unwrap().push((
+                    crate::jump_entry_code(entry),
+                    crate::jump_entry_target(entry),
+                    crate::jump_entry_key(entry),
+                ));
+            }),
+            ..Default::default()
+        }
+        .install();
+
+        // One 16-byte entry whose code, target and key are 16, 20 and 24 bytes
+        // past the start of its section, the key with its branch bit set
+        let mut object = build_object(
+            &[sym("code", 1, 16), sym("target", 1, 20), sym("key", 1, 25)],
+            None,
+            // The `code`, `target` and `key` fields
+            &[&[
+                (0, 1, R_X86_64_PC32),
+                (4, 2, R_X86_64_PC32),
+                (8, 3, R_X86_64_PC64),
+            ]],
+        );
+        rename_text(&mut object, "__jump_table", &[0; 16]);
+
+        let mut loader = ModuleLoader::<HookHelper>::new(&object).unwrap();
+        let mut owner = owner_with_license("GPL");
+        loader.layout_and_allocate(&mut owner, None).unwrap();
+        let load_info = loader.simplify_symbols(&owner).unwrap();
+        loader
+            .apply_relocations(&load_info, &mut owner, None)
+            .unwrap();
+        loader.find_module_sections(&mut owner).unwrap();
+
+        let entries = owner.jump_entries();
+        assert_eq!(entries.len(), 1);
+        let base = entries.as_ptr() as usize;
+        let entry = &entries[0];
+        assert_eq!((entry.code, entry.target, entry.key), (16, 16, 17));
+
+        owner.jump_label_add_module();
+        assert_eq!(*ARMED.lock().unwrap(), [(base + 16, base + 20, base + 24)]);
+    }
+}
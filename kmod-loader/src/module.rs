@@ -1,5 +1,10 @@
-use alloc::{string::String, vec::Vec};
-use core::fmt::Debug;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{ffi::CStr, fmt::Debug};
+
+use crate::{ModuleErr, Result};
 
 #[derive(Clone)]
 pub struct ModuleInfo {
@@ -24,6 +29,56 @@ impl ModuleInfo {
         ModuleInfo { kv: Vec::new() }
     }
 
+    /// Parse the contents of a `.modinfo` section: a sequence of NUL-terminated
+    /// `key=value` entries, possibly separated by NUL padding. An entry without
+    /// `=` is kept as a flag key with an empty value. Invalid UTF-8 is replaced
+    /// with `U+FFFD`, except in the module name.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/main.c>
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut module_info = ModuleInfo::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let rest = &data[offset..];
+            let Ok(entry) = CStr::from_bytes_until_nul(rest) else {
+                log::error!(
+                    ".modinfo entry at offset {:#x} is not NUL-terminated: {:?}",
+                    offset,
+                    String::from_utf8_lossy(rest)
+                );
+                return Err(ModuleErr::ENOEXEC);
+            };
+            let entry_offset = offset;
+            offset += entry.to_bytes_with_nul().len();
+            if entry.is_empty() {
+                // Padding between entries
+                continue;
+            }
+            // A stray byte in e.g. a description shouldn't block loading, so
+            // decode lossily; only the module name has to be exact.
+            let lossy = entry.to_str().is_err();
+            let entry = String::from_utf8_lossy(entry.to_bytes());
+            let (key, value) = entry.split_once('=').unwrap_or((&entry, ""));
+            if lossy {
+                if key == "name" {
+                    log::error!(
+                        ".modinfo entry at offset {:#x} has a non UTF-8 name: {:?}",
+                        entry_offset,
+                        entry
+                    );
+                    return Err(ModuleErr::EINVAL);
+                }
+                log::warn!(
+                    ".modinfo entry at offset {:#x} is not valid UTF-8: {:?}",
+                    entry_offset,
+                    entry
+                );
+            }
+            module_info.add_kv(key.to_string(), value.to_string());
+        }
+        Ok(module_info)
+    }
+
     pub fn add_kv(&mut self, key: String, value: String) {
         self.kv.push((key, value));
     }
@@ -36,4 +91,114 @@ impl ModuleInfo {
         }
         None
     }
+
+    /// Whether the `license=` entry names a GPL-compatible license.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/include/linux/license.h#L5>
+    pub fn is_gpl_compatible(&self) -> bool {
+        matches!(
+            self.get("license"),
+            Some(
+                "GPL"
+                    | "GPL v2"
+                    | "GPL and additional rights"
+                    | "Dual BSD/GPL"
+                    | "Dual MIT/GPL"
+                    | "Dual MPL/GPL"
+            )
+        )
+    }
+
+    /// Names of the modules that must be loaded before this one.
+    ///
+    /// Collects the comma separated `depends=` list and the `pre:` modules of
+    /// every `softdep=` entry, e.g. `softdep=pre: a b post: c`.
+    pub fn dependencies(&self) -> Vec<String> {
+        let mut deps: Vec<String> = Vec::new();
+        let mut push = |name: &str| {
+            if !name.is_empty() && !deps.iter().any(|d| d == name) {
+                deps.push(name.to_string());
+            }
+        };
+        for (k, v) in &self.kv {
+            match k.as_str() {
+                "depends" => v.split(',').map(str::trim).for_each(&mut push),
+                "softdep" => {
+                    let mut in_pre = false;
+                    for word in v.split_ascii_whitespace() {
+                        match word {
+                            "pre:" => in_pre = true,
+                            "post:" => in_pre = false,
+                            name if in_pre => push(name),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        deps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dependencies() {
+        let mut info = ModuleInfo::new();
+        info.add_kv("name".to_string(), "c".to_string());
+        info.add_kv("depends".to_string(), "a,b".to_string());
+        info.add_kv("softdep".to_string(), "pre: d a post: e".to_string());
+        assert_eq!(info.dependencies(), ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn test_no_dependencies() {
+        let mut info = ModuleInfo::new();
+        info.add_kv("depends".to_string(), "".to_string());
+        assert!(info.dependencies().is_empty());
+    }
+
+    #[test]
+    fn test_is_gpl_compatible() {
+        let mut info = ModuleInfo::new();
+        assert!(!info.is_gpl_compatible());
+        info.add_kv("license".to_string(), "Dual BSD/GPL".to_string());
+        assert!(info.is_gpl_compatible());
+
+        let mut info = ModuleInfo::new();
+        info.add_kv("license".to_string(), "Proprietary".to_string());
+        assert!(!info.is_gpl_compatible());
+    }
+
+    #[test]
+    fn test_parse() {
+        let info = ModuleInfo::parse(b"name=hello\0license=GPL\0\0\0intree\0").unwrap();
+        assert_eq!(info.get("name"), Some("hello"));
+        assert_eq!(info.get("license"), Some("GPL"));
+        // A key without a value is a flag
+        assert_eq!(info.get("intree"), Some(""));
+    }
+
+    #[test]
+    fn test_parse_unterminated() {
+        assert_eq!(
+            ModuleInfo::parse(b"name=hello\0license=GPL").unwrap_err(),
+            ModuleErr::ENOEXEC
+        );
+    }
+
+    #[test]
+    fn test_parse_non_utf8() {
+        let info = ModuleInfo::parse(b"name=hello\0description=caf\xe9\0").unwrap();
+        assert_eq!(info.get("name"), Some("hello"));
+        assert_eq!(info.get("description"), Some("caf\u{fffd}"));
+
+        assert_eq!(
+            ModuleInfo::parse(b"name=hell\xf6\0").unwrap_err(),
+            ModuleErr::EINVAL
+        );
+    }
 }
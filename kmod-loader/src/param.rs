@@ -15,6 +15,11 @@ fn next_arg(mut args: &mut [u8]) -> Result<(&CStr, Option<&CStr>, &mut [u8])> {
     let mut in_quote = false;
     let mut quoted = false;
 
+    // The scan below relies on a NUL terminator to stop.
+    if args.last() != Some(&b'\0') {
+        return Err(LinuxError::EINVAL);
+    }
+
     if args[0] == b'"' {
         args = &mut args[1..];
         in_quote = true;
@@ -27,7 +32,9 @@ fn next_arg(mut args: &mut [u8]) -> Result<(&CStr, Option<&CStr>, &mut [u8])> {
         if b.is_ascii_whitespace() && !in_quote {
             break;
         }
-        if equals.is_none() && b == b'=' {
+        // An '=' inside a quoted value is part of the value, unless the whole
+        // "param=value" pair is quoted.
+        if equals.is_none() && b == b'=' && (!in_quote || quoted) {
             equals = Some(idx);
         }
         if b == b'"' {
@@ -35,8 +42,9 @@ fn next_arg(mut args: &mut [u8]) -> Result<(&CStr, Option<&CStr>, &mut [u8])> {
         }
         idx += 1;
     }
-    let param_start = args.as_ptr();
-    let val_start = if let Some(equals_idx) = equals {
+    // Only record offsets here: a pointer taken into `args` would be
+    // invalidated by the writes below
+    let val_idx = equals.map(|equals_idx| {
         // Split at equals
         args[equals_idx] = b'\0';
         let mut val_idx = equals_idx + 1;
@@ -47,30 +55,28 @@ fn next_arg(mut args: &mut [u8]) -> Result<(&CStr, Option<&CStr>, &mut [u8])> {
                 args[idx - 1] = b'\0';
             }
         }
-        let val_start = unsafe { args.as_ptr().add(val_idx) };
-        Some(val_start)
-    } else {
-        None
-    };
+        val_idx
+    });
 
     if quoted && idx > 0 && args[idx - 1] == b'"' {
         args[idx - 1] = b'\0';
     }
-    if args[idx] != b'\0' {
+    // Split the terminated arg off the rest, so that the returned strings
+    // never alias the returned slice. At the end of the string the rest is
+    // empty.
+    let (arg, rest) = if args[idx] != b'\0' {
         args[idx] = b'\0';
-        args = &mut args[idx + 1..];
+        args.split_at_mut(idx + 1)
     } else {
-        args = &mut args[idx..];
-    }
-
-    args = skip_spaces(args);
-
-    let (param, val) = unsafe {
-        let param = CStr::from_ptr(param_start as _);
-        let val = val_start.map(|v| CStr::from_ptr(v as _));
-        (param, val)
+        (args, &mut [][..])
     };
-    Ok((param, val, args))
+    let arg: &[u8] = arg;
+
+    let cstr =
+        |start: usize| CStr::from_bytes_until_nul(&arg[start..]).map_err(|_| LinuxError::EINVAL);
+    let param = cstr(0)?;
+    let val = val_idx.map(cstr).transpose()?;
+    Ok((param, val, skip_spaces(rest)))
 }
 
 fn skip_spaces(mut args: &mut [u8]) -> &mut [u8] {
@@ -141,14 +147,9 @@ fn parse_one(
                 kp.ops().set
             );
             let set = kp.ops().set.unwrap();
-            let res = unsafe {
-                set(
-                    val.map_or(core::ptr::null(), |v| v.as_ptr()),
-                    kp.raw_kernel_param(),
-                )
-            };
+            let res = unsafe { set(val.map_or(core::ptr::null(), |v| v.as_ptr()), kp.as_raw()) };
             if res < 0 {
-                return Err(LinuxError::try_from(-res).unwrap());
+                return Err(kmod_tools::errno_to_err(res));
             } else {
                 return Ok(());
             }
@@ -157,6 +158,12 @@ fn parse_one(
     Err(LinuxError::ENOENT)
 }
 
+/// The index of the parameter called `name`, treating `-` and `_` alike
+pub(crate) fn find_param(params: &[KernelParam], name: &str) -> Option<usize> {
+    let name = CString::new(name).ok()?;
+    params.iter().position(|kp| parameq(kp.raw_name(), &name))
+}
+
 pub(crate) fn parse_args(
     doing: &str,
     args: CString,
@@ -173,7 +180,7 @@ pub(crate) fn parse_args(
         return Ok(CString::new("").unwrap());
     }
 
-    while args[0] != b'\0' {
+    while args.first().is_some_and(|&b| b != b'\0') {
         let (param, val, new_args) = next_arg(args)?;
         args = new_args;
         // Stop at --
@@ -220,6 +227,38 @@ pub(crate) fn parse_args(
     Ok(CString::new("").unwrap())
 }
 
+/// Highest initcall level a parameter can be bound to (`late`).
+const MAX_INITCALL_LEVEL: i16 = 7;
+
+/// Parse the args in several passes so that parameters are applied in
+/// ascending level order: plain module parameters (negative levels) first,
+/// then each initcall level from 0 to 7, and finally anything above.
+///
+/// See <https://elixir.bootlin.com/linux/v6.6/source/init/main.c#L1307>
+pub(crate) fn parse_args_leveled(
+    doing: &str,
+    args: CString,
+    params: &mut [KernelParam],
+) -> Result<CString> {
+    parse_args(doing, args.clone(), params, i16::MIN, -1)?;
+    for level in 0..=MAX_INITCALL_LEVEL {
+        parse_args(doing, args.clone(), params, level, level)?;
+    }
+    parse_args(doing, args, params, MAX_INITCALL_LEVEL + 1, i16::MAX)
+}
+
+/// Release any resources held by the parameters, e.g. the strings of
+/// `charp` parameters.
+///
+/// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/params.c#L741>
+pub(crate) fn destroy_params(params: &mut [KernelParam]) {
+    for kp in params.iter_mut() {
+        if let Some(free) = kp.ops().free {
+            unsafe { free(kp.arg_ptr()) };
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::{borrow::ToOwned, boxed::Box};
@@ -253,7 +292,46 @@ mod tests {
         let (param, val, rest) = next_arg(rest).expect("Failed to parse arg3");
         assert_eq!(param, c"param3");
         assert_eq!(val, Some(c"val3"));
-        assert_eq!(rest, b"\0");
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_next_arg_quoted_equals() {
+        let mut args = b"foo=\"a=b\" bar=\"x y=z\"\0".to_owned();
+        let (param, val, rest) = next_arg(args.as_mut_slice()).expect("Failed to parse arg1");
+        assert_eq!(param, c"foo");
+        assert_eq!(val, Some(c"a=b"));
+        let (param, val, rest) = next_arg(rest).expect("Failed to parse arg2");
+        assert_eq!(param, c"bar");
+        assert_eq!(val, Some(c"x y=z"));
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_next_arg_quoted_pair() {
+        let mut args = b"\"foo=a b\"\0".to_owned();
+        let (param, val, rest) = next_arg(args.as_mut_slice()).expect("Failed to parse arg");
+        assert_eq!(param, c"foo");
+        assert_eq!(val, Some(c"a b"));
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_next_arg_disjoint() {
+        // The returned strings must stay readable after the rest is written
+        let mut args = b"a=\"x\" b=y\0".to_owned();
+        let (param, val, rest) = next_arg(args.as_mut_slice()).unwrap();
+        rest.fill(b'\0');
+        assert_eq!(param, c"a");
+        assert_eq!(val, Some(c"x"));
+    }
+
+    #[test]
+    fn test_next_arg_unterminated() {
+        let mut empty = [0u8; 0];
+        assert_eq!(next_arg(&mut empty).err(), Some(LinuxError::EINVAL));
+        let mut no_nul = b"x".to_owned();
+        assert_eq!(next_arg(&mut no_nul).err(), Some(LinuxError::EINVAL));
     }
 
     #[test]
@@ -273,66 +351,15 @@ mod tests {
     // Helper function to create test kernel params
     // Note: This is a simplified approach that uses unsafe code to create mock KernelParam structures for testing
     fn create_test_param_int(name: &'static CStr, value_ptr: *mut c_int) -> KernelParam {
-        // Use mem::transmute to bypass the type system for testing
-        // This is safe in test context as we control all the types
-        let param_raw: kmod::kernel_param = unsafe {
-            let mut param = core::mem::MaybeUninit::<kmod::kernel_param>::zeroed();
-            let p = param.as_mut_ptr();
-            (*p).name = name.as_ptr() as *mut c_char;
-            (*p).mod_ = core::ptr::null_mut();
-            (*p).ops = &param_ops_int;
-            (*p).perm = 0;
-            (*p).level = 0;
-            (*p).flags = 0;
-            // Set the union field arg
-            core::ptr::write(
-                &mut (*p).__bindgen_anon_1 as *mut _ as *mut *mut core::ffi::c_void,
-                value_ptr as *mut core::ffi::c_void,
-            );
-            param.assume_init()
-        };
-
-        KernelParam::from_raw(param_raw)
+        KernelParam::new(name, &param_ops_int, value_ptr as _, 0, 0)
     }
 
     fn create_test_param_bool(name: &'static CStr, value_ptr: *mut bool) -> KernelParam {
-        let param_raw: kmod::kernel_param = unsafe {
-            let mut param = core::mem::MaybeUninit::<kmod::kernel_param>::zeroed();
-            let p = param.as_mut_ptr();
-            (*p).name = name.as_ptr() as *mut c_char;
-            (*p).mod_ = core::ptr::null_mut();
-            (*p).ops = &kapi::param::param_ops_bool;
-            (*p).perm = 0;
-            (*p).level = 0;
-            (*p).flags = 0;
-            core::ptr::write(
-                &mut (*p).__bindgen_anon_1 as *mut _ as *mut *mut core::ffi::c_void,
-                value_ptr as *mut core::ffi::c_void,
-            );
-            param.assume_init()
-        };
-
-        KernelParam::from_raw(param_raw)
+        KernelParam::new(name, &kapi::param::param_ops_bool, value_ptr as _, 0, 0)
     }
 
     fn create_test_param_charp(name: &'static CStr, value_ptr: *mut *mut c_char) -> KernelParam {
-        let param_raw: kmod::kernel_param = unsafe {
-            let mut param = core::mem::MaybeUninit::<kmod::kernel_param>::zeroed();
-            let p = param.as_mut_ptr();
-            (*p).name = name.as_ptr() as *mut c_char;
-            (*p).mod_ = core::ptr::null_mut();
-            (*p).ops = &kapi::param::param_ops_charp;
-            (*p).perm = 0;
-            (*p).level = 0;
-            (*p).flags = 0;
-            core::ptr::write(
-                &mut (*p).__bindgen_anon_1 as *mut _ as *mut *mut core::ffi::c_void,
-                value_ptr as *mut core::ffi::c_void,
-            );
-            param.assume_init()
-        };
-
-        KernelParam::from_raw(param_raw)
+        KernelParam::new(name, &kapi::param::param_ops_charp, value_ptr as _, 0, 0)
     }
 
     fn create_test_params() -> alloc::vec::Vec<KernelParam> {
@@ -341,7 +368,7 @@ mod tests {
         // Create test variables to hold parameter values
 
         let test_int = Box::leak(Box::new(0 as c_int));
-        let test_bool = Box::leak(Box::new(false as bool));
+        let test_bool = Box::leak(Box::new(false));
         let test_str = Box::leak(Box::new(core::ptr::null_mut() as *mut c_char));
 
         // Reset variables before each test
@@ -389,7 +416,7 @@ mod tests {
         // Verify bool value
         let bool_ptr = unsafe { params[1].raw_kernel_param().__bindgen_anon_1.arg };
         let bool_val = unsafe { *(bool_ptr as *const bool) };
-        assert_eq!(bool_val, true);
+        assert!(bool_val);
 
         // Verify string value
         let str_ptr = unsafe { params[2].raw_kernel_param().__bindgen_anon_1.arg };
@@ -422,7 +449,7 @@ mod tests {
 
         let bool_ptr = unsafe { params[1].raw_kernel_param().__bindgen_anon_1.arg };
         let bool_val = unsafe { *(bool_ptr as *const bool) };
-        assert_eq!(bool_val, true);
+        assert!(bool_val);
     }
 
     #[test]
@@ -506,6 +533,192 @@ mod tests {
 
         let bool_ptr = unsafe { params[1].raw_kernel_param().__bindgen_anon_1.arg };
         let bool_val = unsafe { *(bool_ptr as *const bool) };
-        assert_eq!(bool_val, false);
+        assert!(!bool_val);
+    }
+
+    static FREE_COUNT: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+    unsafe extern "C" fn counting_free(_arg: *mut core::ffi::c_void) {
+        FREE_COUNT.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+    }
+
+    static COUNTING_OPS: kmod_tools::kernel_param_ops = kmod_tools::kernel_param_ops {
+        flags: 0,
+        set: None,
+        get: None,
+        free: Some(counting_free),
+    };
+
+    #[test]
+    fn test_destroy_params_calls_free() {
+        let mut params = create_test_params();
+        let args = CString::new("test_str=hello").unwrap();
+        let result = parse_args("test", args, &mut params, i16::MIN, i16::MAX);
+        assert!(result.is_ok());
+
+        let counted = Box::leak(Box::new(0 as c_int));
+        params.push(KernelParam::new(
+            c"test_counted",
+            &COUNTING_OPS,
+            counted as *mut c_int as _,
+            0,
+            0,
+        ));
+
+        destroy_params(&mut params);
+        assert_eq!(FREE_COUNT.load(core::sync::atomic::Ordering::SeqCst), 1);
+
+        // param_free_charp released the string and cleared the slot
+        let str_ptr = unsafe { params[2].raw_kernel_param().__bindgen_anon_1.arg };
+        let str_val = unsafe { *(str_ptr as *const *mut c_char) };
+        assert!(str_val.is_null());
+    }
+
+    static SET_SEQ: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+    /// Stores the order in which it was called into the parameter slot.
+    unsafe extern "C" fn record_order_set(
+        _val: *const c_char,
+        kp: *const kmod_tools::kernel_param,
+    ) -> c_int {
+        let seq = SET_SEQ.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+        unsafe { *((*kp).__bindgen_anon_1.arg as *mut c_int) = seq as c_int };
+        0
+    }
+
+    static RECORD_ORDER_OPS: kmod_tools::kernel_param_ops = kmod_tools::kernel_param_ops {
+        flags: 0,
+        set: Some(record_order_set),
+        get: None,
+        free: None,
+    };
+
+    fn create_test_param_leveled(
+        name: &'static CStr,
+        value_ptr: *mut c_int,
+        level: i8,
+    ) -> KernelParam {
+        KernelParam::new(name, &RECORD_ORDER_OPS, value_ptr as _, 0, level)
+    }
+
+    #[test]
+    fn test_parse_args_leveled_order() {
+        let late = Box::leak(Box::new(-1 as c_int));
+        let early = Box::leak(Box::new(-1 as c_int));
+        let mut params = alloc::vec![
+            create_test_param_leveled(c"late", late, 3),
+            create_test_param_leveled(c"early", early, 0),
+        ];
+        let args = CString::new("late=1 early=1 -- rest").unwrap();
+        let after_dashes = parse_args_leveled("test", args, &mut params).unwrap();
+        assert_eq!(after_dashes.to_str().unwrap(), "rest");
+        assert_eq!(*early, 0);
+        assert_eq!(*late, 1);
+    }
+
+    mod declared {
+        use kmod_tools::{exit_fn, init_fn, module};
+
+        #[init_fn]
+        fn init() -> i32 {
+            0
+        }
+
+        #[exit_fn]
+        fn exit() {}
+
+        module!(
+            name: "param_test",
+            license: "GPL",
+            description: "Module parameters declared through module!",
+            version: "0.1.0",
+            params: {
+                count: int {
+                    default: 1,
+                    permissions: 0o644,
+                    description: "Number of greetings",
+                },
+                verbose: bool {
+                    default: false,
+                    description: "Print more",
+                },
+            },
+        );
+
+        pub(super) fn values() -> (core::ffi::c_int, bool) {
+            unsafe { (count, verbose) }
+        }
+    }
+
+    #[test]
+    fn test_module_declared_params() {
+        extern crate std;
+
+        unsafe extern "C" {
+            static mut __start___param: u8;
+            static mut __stop___param: u8;
+        }
+        let start = &raw mut __start___param;
+        let size = &raw mut __stop___param as usize - start as usize;
+        let params = unsafe { KernelParam::slice_from_section(start, size) }.unwrap();
+        let mut names: alloc::vec::Vec<_> = params.iter().map(|kp| kp.name()).collect();
+        names.sort();
+        assert_eq!(names, ["count", "verbose"]);
+        assert!(params.iter().all(|kp| kp.level() == -1));
+
+        assert_eq!(declared::values(), (1, false));
+        let args = CString::new("count=7 verbose").unwrap();
+        parse_args("param_test", args, params, i16::MIN, i16::MAX).unwrap();
+        assert_eq!(declared::values(), (7, true));
+
+        // The matching `.modinfo` strings
+        let exe = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let elf = goblin::elf::Elf::parse(&exe).unwrap();
+        let modinfo = elf
+            .section_headers
+            .iter()
+            .find(|shdr| elf.shdr_strtab.get_at(shdr.sh_name) == Some(".modinfo"))
+            .unwrap();
+        let data = &exe[modinfo.sh_offset as usize..][..modinfo.sh_size as usize];
+        let entries: alloc::vec::Vec<_> = data.split(|&b| b == 0).collect();
+        for entry in [
+            "parmtype=count:int",
+            "parm=count:Number of greetings",
+            "parmtype=verbose:bool",
+            "parm=verbose:Print more",
+        ] {
+            assert!(entries.contains(&entry.as_bytes()), "missing {}", entry);
+        }
+    }
+
+    unsafe extern "C" fn set_unknown_errno(
+        _val: *const c_char,
+        _kp: *const kmod_tools::kernel_param,
+    ) -> c_int {
+        -4095
+    }
+
+    static PARAM_OPS_UNKNOWN_ERRNO: kmod_tools::kernel_param_ops = kmod_tools::kernel_param_ops {
+        flags: 0,
+        set: Some(set_unknown_errno),
+        get: None,
+        free: None,
+    };
+
+    #[test]
+    fn test_parse_args_unknown_errno() {
+        let mut value = 0 as c_int;
+        let mut params = [KernelParam::new(
+            c"odd",
+            &PARAM_OPS_UNKNOWN_ERRNO,
+            &raw mut value as _,
+            0,
+            0,
+        )];
+        let args = CString::new("odd=1").unwrap();
+        assert_eq!(
+            parse_args("test", args, &mut params, i16::MIN, i16::MAX),
+            Err(LinuxError::EINVAL)
+        );
     }
 }
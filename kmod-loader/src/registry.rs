@@ -0,0 +1,206 @@
+use alloc::{ffi::CString, string::String, vec::Vec};
+
+use crate::{
+    ModuleErr, Result,
+    loader::{KernelModuleHelper, ModuleLoader, ModuleOwner},
+};
+
+/// A set of loaded modules, keyed by module name.
+pub struct ModuleRegistry<H: KernelModuleHelper> {
+    modules: Vec<ModuleOwner<H>>,
+}
+
+impl<H: KernelModuleHelper> Default for ModuleRegistry<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: KernelModuleHelper> ModuleRegistry<H> {
+    pub const fn new() -> Self {
+        ModuleRegistry {
+            modules: Vec::new(),
+        }
+    }
+
+    /// Get a loaded module by name
+    pub fn get(&self, name: &str) -> Option<&ModuleOwner<H>> {
+        self.modules.iter().find(|m| m.name() == name)
+    }
+
+    /// Get a loaded module by name
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut ModuleOwner<H>> {
+        self.modules.iter_mut().find(|m| m.name() == name)
+    }
+
+    /// Iterate over the loaded modules in load order
+    pub fn iter(&self) -> impl Iterator<Item = &ModuleOwner<H>> {
+        self.modules.iter()
+    }
+
+    /// Load a batch of modules, given as `(elf_data, args)` pairs, so that
+    /// every module is loaded after the modules it declares in `depends=` or
+    /// `softdep=pre:`.
+    ///
+    /// Dependencies that are neither in the batch nor already loaded are left
+    /// to symbol resolution to report. A dependency cycle fails with `ELOOP`
+    /// before anything is loaded.
+    pub fn load_ordered(&mut self, modules: Vec<(&[u8], CString)>) -> Result<()> {
+        let mut loaders = Vec::with_capacity(modules.len());
+        let mut graph = Vec::with_capacity(modules.len());
+        for (elf_data, args) in modules {
+            let loader = ModuleLoader::<H>::new(elf_data)?;
+            let info = loader.module_info()?;
+            let name = String::from(info.get("name").unwrap_or_default());
+            let deps = info
+                .dependencies()
+                .into_iter()
+                .filter(|dep| self.get(dep).is_none())
+                .collect();
+            graph.push((name, deps));
+            loaders.push(Some((loader, args)));
+        }
+
+        for idx in sort_by_dependencies(&graph)? {
+            let (loader, args) = loaders[idx].take().unwrap();
+            let owner = loader.load_module(args)?;
+            self.modules.push(owner);
+        }
+        Ok(())
+    }
+}
+
+/// Order `modules`, given as `(name, dependencies)`, so that each module comes
+/// after its dependencies. Returns the indices into `modules` in load order.
+fn sort_by_dependencies(modules: &[(String, Vec<String>)]) -> Result<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        None,
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        idx: usize,
+        modules: &[(String, Vec<String>)],
+        marks: &mut [Mark],
+        order: &mut Vec<usize>,
+    ) -> Result<()> {
+        match marks[idx] {
+            Mark::Done => return Ok(()),
+            Mark::Visiting => {
+                log::error!("Module dependency cycle through '{}'", modules[idx].0);
+                return Err(ModuleErr::ELOOP);
+            }
+            Mark::None => {}
+        }
+        marks[idx] = Mark::Visiting;
+        for dep in &modules[idx].1 {
+            match modules.iter().position(|(name, _)| name == dep) {
+                Some(dep_idx) => visit(dep_idx, modules, marks, order)?,
+                None => log::warn!(
+                    "Module '{}' depends on '{}', which is not available",
+                    modules[idx].0,
+                    dep
+                ),
+            }
+        }
+        marks[idx] = Mark::Done;
+        order.push(idx);
+        Ok(())
+    }
+
+    let mut marks = alloc::vec![Mark::None; modules.len()];
+    let mut order = Vec::with_capacity(modules.len());
+    for idx in 0..modules.len() {
+        visit(idx, modules, &mut marks, &mut order)?;
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "x86_64")]
+    use alloc::{boxed::Box, format};
+    use alloc::{string::ToString, vec};
+
+    use super::*;
+    #[cfg(target_arch = "x86_64")]
+    use crate::testing::{Aligned, HELLO, HookHelper, Hooks};
+
+    fn module(name: &str, deps: &[&str]) -> (String, Vec<String>) {
+        (
+            name.to_string(),
+            deps.iter().map(|d| d.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn test_sort_reverse_order() {
+        let modules = vec![module("b", &["a"]), module("a", &[])];
+        assert_eq!(sort_by_dependencies(&modules).unwrap(), [1, 0]);
+    }
+
+    #[test]
+    fn test_sort_chain_and_missing() {
+        let modules = vec![
+            module("c", &["b", "external"]),
+            module("b", &["a"]),
+            module("a", &[]),
+        ];
+        assert_eq!(sort_by_dependencies(&modules).unwrap(), [2, 1, 0]);
+    }
+
+    #[test]
+    fn test_sort_cycle() {
+        let modules = vec![module("a", &["b"]), module("b", &["a"])];
+        assert_eq!(sort_by_dependencies(&modules), Err(ModuleErr::ELOOP));
+    }
+
+    /// A copy of [`HELLO`] whose `.modinfo` names it `name` and makes it
+    /// depend on `depends`
+    #[cfg(target_arch = "x86_64")]
+    fn hello_as(name: &str, depends: &str) -> Box<Aligned<[u8; HELLO.len()]>> {
+        let elf = goblin::elf::Elf::parse(HELLO).unwrap();
+        let modinfo = elf
+            .section_headers
+            .iter()
+            .find(|shdr| elf.shdr_strtab.get_at(shdr.sh_name) == Some(".modinfo"))
+            .unwrap();
+        let info = format!("name={name}\0depends={depends}\0license=GPL\0");
+        let start = modinfo.sh_offset as usize;
+        let section = start..start + modinfo.sh_size as usize;
+        assert!(info.len() <= section.len());
+
+        let mut data = Box::new(Aligned([0u8; HELLO.len()]));
+        data.0.copy_from_slice(HELLO);
+        data.0[section.clone()].fill(0);
+        data.0[section.start..section.start + info.len()].copy_from_slice(info.as_bytes());
+        data
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_load_ordered_dependency_first() {
+        Hooks {
+            resolve_symbol: Some(|_| {
+                extern "C" fn stub() {}
+                Some(stub as *const () as usize)
+            }),
+            ..Default::default()
+        }
+        .install();
+
+        let a = hello_as("a", "");
+        let b = hello_as("b", "a");
+        let mut registry = ModuleRegistry::<HookHelper>::new();
+        registry
+            .load_ordered(vec![
+                (&b.0[..], CString::default()),
+                (&a.0[..], CString::default()),
+            ])
+            .unwrap();
+        let names: Vec<_> = registry.iter().map(|owner| owner.name()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Appended module signatures, as produced by `scripts/sign-file`
+//!
+//! A signed module is laid out as
+//! `[module][PKCS#7 signature][struct module_signature][MODULE_SIG_STRING]`.
+//!
+//! See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module_signature.c>
+
+use core::mem::size_of;
+
+use crate::{ModuleErr, Result};
+
+/// The marker at the very end of a signed module
+pub(crate) const MODULE_SIG_STRING: &[u8] = b"~Module signature appended~\n";
+
+/// `PKEY_ID_PKCS7`, the only signature type modules are signed with
+const PKEY_ID_PKCS7: u8 = 2;
+
+/// `struct module_signature`, which sits between the signature and the marker.
+/// `sig_len` is stored big-endian.
+#[repr(C)]
+struct ModuleSignature {
+    algo: u8,
+    hash: u8,
+    id_type: u8,
+    signer_len: u8,
+    key_id_len: u8,
+    pad: [u8; 3],
+    sig_len: u32,
+}
+
+impl ModuleSignature {
+    fn from_bytes(bytes: &[u8; size_of::<ModuleSignature>()]) -> Self {
+        ModuleSignature {
+            algo: bytes[0],
+            hash: bytes[1],
+            id_type: bytes[2],
+            signer_len: bytes[3],
+            key_id_len: bytes[4],
+            pad: [bytes[5], bytes[6], bytes[7]],
+            sig_len: u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+        }
+    }
+
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module_signature.c#L19>
+    fn check(&self, file_len: usize) -> Result<()> {
+        if self.sig_len as usize >= file_len - size_of::<ModuleSignature>() {
+            return Err(ModuleErr::EBADMSG);
+        }
+        if self.id_type != PKEY_ID_PKCS7 {
+            log::error!("module: not signed with expected PKCS#7 message");
+            return Err(ModuleErr::ENOPKG);
+        }
+        if self.algo != 0
+            || self.hash != 0
+            || self.signer_len != 0
+            || self.key_id_len != 0
+            || self.pad != [0; 3]
+        {
+            log::error!("module: PKCS#7 signature info has unexpected non-zero params");
+            return Err(ModuleErr::EBADMSG);
+        }
+        Ok(())
+    }
+}
+
+/// Split a module image into the module itself and its appended signature.
+///
+/// Returns `ENODATA` if the image carries no signature marker, `ENOPKG` if it
+/// is not signed with PKCS#7 and `EBADMSG` if the trailer is malformed.
+///
+/// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/signing.c#L40>
+pub(crate) fn split_signature(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    let Some(data) = data.strip_suffix(MODULE_SIG_STRING) else {
+        return Err(ModuleErr::ENODATA);
+    };
+    let Some((rest, ms)) = data.split_last_chunk::<{ size_of::<ModuleSignature>() }>() else {
+        return Err(ModuleErr::EBADMSG);
+    };
+    let ms = ModuleSignature::from_bytes(ms);
+    ms.check(data.len())?;
+    Ok(rest.split_at(rest.len() - ms.sig_len as usize))
+}
+
+/// Append a signature trailer to `data`, like `scripts/sign-file` does
+#[cfg(test)]
+pub(crate) fn append_signature(data: &[u8], sig: &[u8]) -> alloc::vec::Vec<u8> {
+    let mut out = data.to_vec();
+    out.extend_from_slice(sig);
+    out.extend_from_slice(&[0, 0, PKEY_ID_PKCS7, 0, 0, 0, 0, 0]);
+    out.extend_from_slice(&(sig.len() as u32).to_be_bytes());
+    out.extend_from_slice(MODULE_SIG_STRING);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_signature() {
+        let signed = append_signature(b"\x7fELF module", b"pkcs7");
+        assert_eq!(
+            split_signature(&signed),
+            Ok((&b"\x7fELF module"[..], &b"pkcs7"[..]))
+        );
+        assert_eq!(split_signature(b"\x7fELF module"), Err(ModuleErr::ENODATA));
+    }
+
+    #[test]
+    fn test_malformed_signature() {
+        let signed = append_signature(b"\x7fELF module", b"pkcs7");
+        let info = signed.len() - MODULE_SIG_STRING.len() - size_of::<ModuleSignature>();
+
+        // Just the marker, without a `struct module_signature`
+        assert_eq!(split_signature(MODULE_SIG_STRING), Err(ModuleErr::EBADMSG));
+
+        // Signature longer than the module
+        let mut bad = signed.clone();
+        bad[info + 8..info + 12].copy_from_slice(&0x1000u32.to_be_bytes());
+        assert_eq!(split_signature(&bad), Err(ModuleErr::EBADMSG));
+
+        // Not PKCS#7
+        let mut bad = signed.clone();
+        bad[info + 2] = 1;
+        assert_eq!(split_signature(&bad), Err(ModuleErr::ENOPKG));
+
+        // Unexpected non-zero params
+        let mut bad = signed;
+        bad[info] = 1;
+        assert_eq!(split_signature(&bad), Err(ModuleErr::EBADMSG));
+    }
+}
@@ -0,0 +1,32 @@
+//! The process-wide symbol map behind the host-side helpers
+
+extern crate std;
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+use std::sync::Mutex;
+
+static SYMBOLS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+static DEFAULT_SYMBOL: Mutex<Option<usize>> = Mutex::new(None);
+
+/// Make `name` resolve to `addr`
+pub(crate) fn register(name: &str, addr: usize) {
+    SYMBOLS.lock().unwrap().insert(name.to_string(), addr);
+}
+
+/// Address that symbols missing from the map resolve to, if any
+pub(crate) fn set_default(addr: Option<usize>) {
+    *DEFAULT_SYMBOL.lock().unwrap() = addr;
+}
+
+/// The address registered for `name`, or the default one
+pub(crate) fn resolve(name: &str) -> Option<usize> {
+    SYMBOLS
+        .lock()
+        .unwrap()
+        .get(name)
+        .copied()
+        .or(*DEFAULT_SYMBOL.lock().unwrap())
+}
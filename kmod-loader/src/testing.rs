@@ -0,0 +1,689 @@
+//! An in-memory [`KernelModuleHelper`] for tests and host-side tools
+
+extern crate std;
+
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use std::sync::Mutex;
+
+use crate::{KernelModuleHelper, SectionMemOps, SectionPerm, symbol_map};
+#[cfg(test)]
+use crate::{ResolvedSymbol, SymbolLicense};
+
+const PAGE_SIZE: usize = 4096;
+
+static PERMS: Mutex<BTreeMap<usize, SectionPerm>> = Mutex::new(BTreeMap::new());
+
+/// A [`KernelModuleHelper`] backed by heap memory and a process-wide symbol
+/// map.
+///
+/// Sections are allocated as page-aligned `Vec`s. The memory is never made
+/// executable, so a module loaded with it can be inspected but its init and
+/// exit functions must not be called.
+pub struct TestHelper;
+
+impl TestHelper {
+    /// Make `name` resolve to `addr`
+    pub fn register_symbol(name: &str, addr: usize) {
+        symbol_map::register(name, addr);
+    }
+
+    /// Address that symbols missing from the map resolve to. With `None`
+    /// (the default) they fail to resolve.
+    pub fn set_default_symbol(addr: Option<usize>) {
+        symbol_map::set_default(addr);
+    }
+
+    /// Permissions last set on the live allocation starting at `addr`
+    pub fn perms(addr: *const u8) -> Option<SectionPerm> {
+        PERMS.lock().unwrap().get(&(addr as usize)).copied()
+    }
+}
+
+impl KernelModuleHelper for TestHelper {
+    fn vmalloc(size: usize) -> Option<Box<dyn SectionMemOps>> {
+        let mem = TestMem(alloc::vec![Page([0; PAGE_SIZE]); size.div_ceil(PAGE_SIZE)]);
+        PERMS
+            .lock()
+            .unwrap()
+            .insert(mem.as_ptr() as usize, SectionPerm::empty());
+        Some(Box::new(mem))
+    }
+
+    fn resolve_symbol(name: &str) -> Option<usize> {
+        symbol_map::resolve(name)
+    }
+}
+
+#[repr(C, align(4096))]
+#[derive(Clone)]
+struct Page([u8; PAGE_SIZE]);
+
+struct TestMem(Vec<Page>);
+
+impl SectionMemOps for TestMem {
+    fn as_ptr(&self) -> *const u8 {
+        self.0.as_ptr() as *const u8
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.0.as_mut_ptr() as *mut u8
+    }
+
+    fn len(&self) -> usize {
+        self.0.len() * PAGE_SIZE
+    }
+
+    fn change_perms(&mut self, perms: SectionPerm) -> bool {
+        PERMS.lock().unwrap().insert(self.as_ptr() as usize, perms);
+        true
+    }
+}
+
+/// `modules/hello` built for the host with `-C relocation-model=pic` and
+/// linked with `ld -r -T linker.ld` as in `build_module.sh`
+#[cfg(all(test, target_arch = "x86_64"))]
+pub(crate) static HELLO: &[u8] = &Aligned(*include_bytes!("../testdata/hello-x86_64.ko")).0;
+
+/// Relocation tables are read in place, so the image must be aligned
+#[cfg(all(test, target_arch = "x86_64"))]
+#[repr(C, align(8))]
+pub(crate) struct Aligned<T>(pub T);
+
+impl Drop for TestMem {
+    fn drop(&mut self) {
+        PERMS.lock().unwrap().remove(&(self.as_ptr() as usize));
+    }
+}
+
+/// The hooks a [`HookHelper`] runs on the current thread. Unset ones behave
+/// as the defaults of [`KernelModuleHelper`], except that sections are
+/// allocated by [`TestHelper`] and symbols do not resolve.
+#[cfg(test)]
+#[derive(Clone, Copy, Default)]
+#[allow(clippy::type_complexity)]
+pub(crate) struct Hooks {
+    pub vmalloc: Option<fn(usize) -> Option<Box<dyn SectionMemOps>>>,
+    pub vmalloc_at: Option<fn(usize, usize) -> Option<Box<dyn SectionMemOps>>>,
+    pub resolve_symbol: Option<fn(&str) -> Option<usize>>,
+    pub resolve_symbol_ex: Option<fn(&str) -> Option<(usize, SymbolLicense)>>,
+    pub resolve_symbol_full: Option<fn(&str) -> Option<ResolvedSymbol>>,
+    pub symbol_crc: Option<fn(&str) -> Option<u32>>,
+    pub verify_module: Option<fn(&[(*const u8, usize, SectionPerm)]) -> Result<(), ()>>,
+    pub verify_signature: Option<fn(&[u8], &[u8]) -> bool>,
+    pub sig_enforce: bool,
+    pub strict_osabi: bool,
+    pub allow_common_symbols: bool,
+    pub weak_default: Option<usize>,
+    pub best_effort_relocations: bool,
+    pub apply_alternatives: Option<fn(&str, &[u8])>,
+    pub patch_jump_entry: Option<fn(&kmod_tools::kbindings::jump_entry)>,
+}
+
+#[cfg(test)]
+std::thread_local! {
+    static HOOKS: core::cell::Cell<Hooks> = core::cell::Cell::new(Hooks::default());
+}
+
+#[cfg(test)]
+impl Hooks {
+    /// Make [`HookHelper`] run these hooks on the current thread, which is
+    /// the test's own
+    pub(crate) fn install(self) {
+        HOOKS.set(self);
+    }
+}
+
+/// A [`KernelModuleHelper`] for tests that need a hook of their own, set
+/// with [`Hooks::install`]
+#[cfg(test)]
+pub(crate) struct HookHelper<const PAGE_SIZE: usize = 4096>;
+
+#[cfg(test)]
+impl<const PAGE: usize> KernelModuleHelper for HookHelper<PAGE> {
+    const PAGE_SIZE: usize = PAGE;
+
+    fn vmalloc(size: usize) -> Option<Box<dyn SectionMemOps>> {
+        match HOOKS.get().vmalloc {
+            Some(vmalloc) => vmalloc(size),
+            None => TestHelper::vmalloc(size),
+        }
+    }
+
+    fn vmalloc_at(hint: usize, size: usize) -> Option<Box<dyn SectionMemOps>> {
+        match HOOKS.get().vmalloc_at {
+            Some(vmalloc_at) => vmalloc_at(hint, size),
+            None => Self::vmalloc(size),
+        }
+    }
+
+    fn resolve_symbol(name: &str) -> Option<usize> {
+        HOOKS.get().resolve_symbol.and_then(|resolve| resolve(name))
+    }
+
+    fn resolve_symbol_ex(name: &str) -> Option<(usize, SymbolLicense)> {
+        match HOOKS.get().resolve_symbol_ex {
+            Some(resolve) => resolve(name),
+            None => Self::resolve_symbol(name).map(|addr| (addr, SymbolLicense::Any)),
+        }
+    }
+
+    fn resolve_symbol_full(name: &str) -> Option<ResolvedSymbol> {
+        match HOOKS.get().resolve_symbol_full {
+            Some(resolve) => resolve(name),
+            None => Self::resolve_symbol_ex(name).map(|(addr, license)| ResolvedSymbol {
+                addr,
+                license,
+                crc: Self::symbol_crc(name),
+            }),
+        }
+    }
+
+    fn symbol_crc(name: &str) -> Option<u32> {
+        HOOKS.get().symbol_crc.and_then(|crc| crc(name))
+    }
+
+    fn verify_module(regions: &[(*const u8, usize, SectionPerm)]) -> Result<(), ()> {
+        HOOKS
+            .get()
+            .verify_module
+            .map_or(Ok(()), |verify| verify(regions))
+    }
+
+    fn verify_signature(data: &[u8], sig: &[u8]) -> bool {
+        HOOKS
+            .get()
+            .verify_signature
+            .is_some_and(|verify| verify(data, sig))
+    }
+
+    fn sig_enforce() -> bool {
+        HOOKS.get().sig_enforce
+    }
+
+    fn strict_osabi() -> bool {
+        HOOKS.get().strict_osabi
+    }
+
+    fn allow_common_symbols() -> bool {
+        HOOKS.get().allow_common_symbols
+    }
+
+    fn weak_default() -> Option<usize> {
+        HOOKS.get().weak_default
+    }
+
+    fn best_effort_relocations() -> bool {
+        HOOKS.get().best_effort_relocations
+    }
+
+    fn apply_alternatives(name: &str, section: &[u8]) {
+        if let Some(apply) = HOOKS.get().apply_alternatives {
+            apply(name, section);
+        }
+    }
+
+    fn patch_jump_entry(entry: &kmod_tools::kbindings::jump_entry) {
+        if let Some(patch) = HOOKS.get().patch_jump_entry {
+            patch(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{ffi::CString, string::ToString};
+
+    use super::*;
+    use crate::{ModuleErr, ModuleLoader, ModuleOwner};
+
+    #[test]
+    fn test_helper_symbols_and_perms() {
+        TestHelper::register_symbol("test_helper_symbol", 0x1234);
+        assert_eq!(
+            TestHelper::resolve_symbol("test_helper_symbol"),
+            Some(0x1234)
+        );
+
+        let mut mem = TestHelper::vmalloc(PAGE_SIZE + 1).unwrap();
+        let ptr = mem.as_ptr();
+        assert_eq!(ptr as usize % PAGE_SIZE, 0);
+        assert_eq!(TestHelper::perms(ptr), Some(SectionPerm::empty()));
+        assert!(mem.change_perms(SectionPerm::READ));
+        assert_eq!(TestHelper::perms(ptr), Some(SectionPerm::READ));
+        drop(mem);
+        assert_eq!(TestHelper::perms(ptr), None);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_load_hello_module() {
+        extern "C" fn stub() {}
+
+        let elf = goblin::elf::Elf::parse(HELLO).unwrap();
+        for sym in elf.syms.iter().filter(|sym| sym.is_import()) {
+            let name = elf.strtab.get_at(sym.st_name).unwrap();
+            TestHelper::register_symbol(name, stub as *const () as usize);
+        }
+
+        let owner = ModuleLoader::<TestHelper>::new(HELLO)
+            .unwrap()
+            .load_module(CString::default())
+            .unwrap();
+        assert_eq!(owner.name(), "hello");
+        assert_eq!(owner.module().name(), owner.name());
+        assert_eq!(owner.state(), kmod_tools::ModuleState::Coming);
+
+        let perms: Vec<_> = PERMS.lock().unwrap().values().copied().collect();
+        assert!(perms.contains(&(SectionPerm::READ | SectionPerm::EXECUTE)));
+        assert!(perms.contains(&(SectionPerm::READ | SectionPerm::WRITE)));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_load_without_modinfo() {
+        extern "C" fn stub() {}
+
+        // Rename `.modinfo` to `.comment`, which is as long
+        let elf = goblin::elf::Elf::parse(HELLO).unwrap();
+        let shstrtab = elf.section_headers[elf.header.e_shstrndx as usize].sh_offset as usize;
+        let modinfo = elf
+            .section_headers
+            .iter()
+            .find(|shdr| elf.shdr_strtab.get_at(shdr.sh_name) == Some(".modinfo"))
+            .unwrap();
+        let mut data = Aligned([0u8; HELLO.len()]);
+        data.0.copy_from_slice(HELLO);
+        let name = shstrtab + modinfo.sh_name;
+        data.0[name..name + 8].copy_from_slice(b".comment");
+
+        for sym in elf.syms.iter().filter(|sym| sym.is_import()) {
+            let name = elf.strtab.get_at(sym.st_name).unwrap();
+            TestHelper::register_symbol(name, stub as *const () as usize);
+        }
+        let loader = ModuleLoader::<TestHelper>::new(&data.0).unwrap();
+        let info = loader.module_info().unwrap();
+        assert_eq!(info.get("name"), Some("hello"));
+        assert_eq!(info.get("license"), None);
+
+        let owner = loader.load_module(CString::default()).unwrap();
+        assert_eq!(owner.name(), "hello");
+        assert_eq!(owner.module().name(), owner.name());
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_verify_module_rejects() {
+        static VERIFIED: Mutex<usize> = Mutex::new(0);
+
+        // Reject every module after checking that no permissions have been
+        // applied yet
+        Hooks {
+            resolve_symbol: Some(stub_symbol),
+            verify_module: Some(|regions| {
+                assert!(
+                    regions
+                        .iter()
+                        .any(|(_, _, perms)| perms.contains(SectionPerm::EXECUTE))
+                );
+                for &(start, _, _) in regions {
+                    assert_eq!(TestHelper::perms(start), Some(SectionPerm::empty()));
+                }
+                *VERIFIED.lock().unwrap() += 1;
+                Err(())
+            }),
+            ..Default::default()
+        }
+        .install();
+
+        let err = ModuleLoader::<HookHelper>::new(HELLO)
+            .unwrap()
+            .load_module(CString::default())
+            .err();
+        assert_eq!(err, Some(ax_errno::LinuxError::EACCES));
+        assert_eq!(*VERIFIED.lock().unwrap(), 1);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_load_module_at_passes_hint() {
+        const BASE: usize = 0xffff_ffff_c000_0000;
+
+        static HINTS: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+        // Record the hints and sizes asked for
+        Hooks {
+            vmalloc: Some(|_| panic!("load_module_at must allocate through vmalloc_at")),
+            vmalloc_at: Some(|hint, size| {
+                HINTS.lock().unwrap().push((hint, size));
+                TestHelper::vmalloc(size)
+            }),
+            resolve_symbol: Some(stub_symbol),
+            ..Default::default()
+        }
+        .install();
+
+        ModuleLoader::<HookHelper>::new(HELLO)
+            .unwrap()
+            .load_module_at(CString::default(), BASE)
+            .unwrap();
+
+        // The sections are laid out back to back from the hint
+        let hints = HINTS.lock().unwrap();
+        assert!(hints.len() > 1);
+        let mut next = BASE;
+        for &(hint, size) in hints.iter() {
+            assert_eq!(hint, next);
+            next += size;
+        }
+    }
+
+    /// Resolve every symbol to a stub, without touching the global map
+    #[cfg(target_arch = "x86_64")]
+    fn stub_symbol(_name: &str) -> Option<usize> {
+        extern "C" fn stub() {}
+        Some(stub as *const () as usize)
+    }
+
+    /// Resolves every symbol with [`stub_symbol`]
+    #[cfg(target_arch = "x86_64")]
+    struct StubHelper;
+
+    #[cfg(target_arch = "x86_64")]
+    impl KernelModuleHelper for StubHelper {
+        fn vmalloc(size: usize) -> Option<Box<dyn SectionMemOps>> {
+            TestHelper::vmalloc(size)
+        }
+
+        fn resolve_symbol(name: &str) -> Option<usize> {
+            stub_symbol(name)
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_load_pipeline_phases() {
+        use crate::LoadPhase;
+
+        let stub = StubHelper::resolve_symbol("write_char").unwrap();
+        let pipeline = ModuleLoader::<StubHelper>::new(HELLO)
+            .unwrap()
+            .pipeline()
+            .unwrap();
+        assert_eq!(pipeline.phase(), LoadPhase::Validated);
+        assert_eq!(pipeline.owner().name(), "hello");
+        assert!(pipeline.load_info().is_none());
+
+        let pipeline = pipeline.layout_and_allocate(None).unwrap();
+        let text = pipeline
+            .owner()
+            .memory_map()
+            .into_iter()
+            .find(|entry| entry.name == ".text")
+            .unwrap();
+        let text_bytes =
+            || unsafe { core::slice::from_raw_parts(text.base as *const u8, text.size) }.to_vec();
+        let unrelocated = text_bytes();
+
+        // Imports are resolved before anything is relocated
+        let pipeline = pipeline.simplify_symbols().unwrap();
+        assert_eq!(pipeline.phase(), LoadPhase::SymbolsResolved);
+        let (write_char, _) = pipeline
+            .load_info()
+            .unwrap()
+            .syms
+            .iter()
+            .find(|(_, name)| name == "write_char")
+            .unwrap();
+        assert_eq!(write_char.st_value as usize, stub);
+        assert_eq!(text_bytes(), unrelocated);
+
+        let pipeline = pipeline.apply_relocations().unwrap();
+        assert_ne!(text_bytes(), unrelocated);
+        assert_eq!(pipeline.owner().module().name(), "");
+
+        // `struct module` is read from the relocated image, before the
+        // permissions are applied
+        let pipeline = pipeline.read_this_module().unwrap();
+        assert_eq!(pipeline.owner().module().name(), "hello");
+        assert_eq!(
+            TestHelper::perms(text.base as *const u8),
+            Some(SectionPerm::empty())
+        );
+
+        let pipeline = pipeline.complete_formation().unwrap();
+        assert_eq!(pipeline.phase(), LoadPhase::Formed);
+        assert_eq!(TestHelper::perms(text.base as *const u8), Some(text.perms));
+
+        let owner = pipeline.finish(CString::default()).unwrap();
+        assert_eq!(owner.state(), kmod_tools::ModuleState::Coming);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_load_pipeline_order() {
+        let pipeline = || {
+            ModuleLoader::<StubHelper>::new(HELLO)
+                .unwrap()
+                .pipeline()
+                .unwrap()
+        };
+        assert_eq!(pipeline().simplify_symbols().err(), Some(ModuleErr::EINVAL));
+        let allocated = pipeline().layout_and_allocate(None).unwrap();
+        assert_eq!(allocated.read_this_module().err(), Some(ModuleErr::EINVAL));
+
+        // finish() runs whatever steps are left
+        let owner = pipeline()
+            .layout_and_allocate(None)
+            .unwrap()
+            .finish(CString::default())
+            .unwrap();
+        assert_eq!(owner.module().name(), "hello");
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_layout_is_reproducible() {
+        let load = || {
+            let owner = ModuleLoader::<StubHelper>::new(HELLO)
+                .unwrap()
+                .load_module(CString::default())
+                .unwrap();
+            owner
+                .layout()
+                .map(|(name, offset, size)| (name.to_string(), offset, size))
+                .collect::<Vec<_>>()
+        };
+        let first = load();
+        assert_eq!(first, load());
+
+        // Laid out back to back, in section order
+        let mut next = 0;
+        for (_, offset, size) in &first {
+            assert_eq!(*offset, next);
+            next += size;
+        }
+        let elf = goblin::elf::Elf::parse(HELLO).unwrap();
+        let index = |name: &str| {
+            elf.section_headers
+                .iter()
+                .position(|shdr| elf.shdr_strtab.get_at(shdr.sh_name) == Some(name))
+        };
+        assert!(first.windows(2).all(|w| index(&w[0].0) < index(&w[1].0)));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_memory_map() {
+        let owner = ModuleLoader::<StubHelper>::new(HELLO)
+            .unwrap()
+            .load_module(CString::default())
+            .unwrap();
+        let map = owner.memory_map();
+        let text = map.iter().find(|entry| entry.name == ".text").unwrap();
+        assert_eq!(text.perms, SectionPerm::READ | SectionPerm::EXECUTE);
+        assert!(map.iter().any(|entry| entry.name == ".data"));
+        for entry in &map {
+            assert_ne!(entry.base, 0);
+            assert_ne!(entry.size, 0);
+            assert!(entry.to_string().ends_with(&entry.name));
+        }
+        assert_eq!(map.len(), owner.layout().count());
+    }
+
+    /// `init_module` in `.text` calls `hello_init`, which `#[init_fn]` puts in
+    /// `.text.init` (linked into `.init.text`), through a GOT slot
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_text_calls_into_init_section() {
+        let elf = goblin::elf::Elf::parse(HELLO).unwrap();
+        let (sym_idx, init_fn) = elf
+            .syms
+            .iter()
+            .enumerate()
+            .find(|(_, sym)| {
+                elf.strtab
+                    .get_at(sym.st_name)
+                    .is_some_and(|name| crate::demangle_sym(name) == "hello::hello_init")
+            })
+            .unwrap();
+        let init_section = elf
+            .shdr_strtab
+            .get_at(elf.section_headers[init_fn.st_shndx].sh_name)
+            .unwrap();
+        assert_eq!(init_section, ".init.text");
+
+        let loader = ModuleLoader::<StubHelper>::new(HELLO).unwrap();
+        let rela = loader
+            .reloc_sections()
+            .filter(|(target, _)| *target == ".text")
+            .flat_map(|(_, relas)| relas)
+            .find(|rela| (rela.r_info >> 32) as usize == sym_idx)
+            .copied()
+            .unwrap();
+
+        let owner = loader.load_module(CString::default()).unwrap();
+        let map = owner.memory_map();
+        let base = |name: &str| map.iter().find(|entry| entry.name == name).unwrap().base;
+
+        // GOTPCREL: disp = G + A - P
+        let location = base(".text") + rela.r_offset as usize;
+        let disp = unsafe { (location as *const i32).read_unaligned() };
+        let slot = (location as i64 + disp as i64 - rela.r_addend) as *const u64;
+        let target = base(init_section) + init_fn.st_value as usize;
+        assert_eq!(unsafe { slot.read_unaligned() }, target as u64);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_apply_relocations_for_one_section() {
+        let mut loader = ModuleLoader::<StubHelper>::new(HELLO).unwrap();
+        let (mut owner, load_info) = loader.allocate().unwrap();
+        let targets = loader
+            .reloc_sections()
+            .map(|(target, _)| target.to_string())
+            .collect::<Vec<_>>();
+        assert!(targets.iter().any(|target| target == ".text"));
+        assert!(targets.iter().any(|target| target != ".text"));
+
+        let elf = goblin::elf::Elf::parse(HELLO).unwrap();
+        let file_data = |name: &str| {
+            let shdr = elf
+                .section_headers
+                .iter()
+                .find(|shdr| elf.shdr_strtab.get_at(shdr.sh_name) == Some(name))
+                .unwrap();
+            &HELLO[shdr.sh_offset as usize..][..shdr.sh_size as usize]
+        };
+        let memory = |owner: &ModuleOwner<StubHelper>, name: &str| {
+            let entry = owner
+                .memory_map()
+                .into_iter()
+                .find(|entry| entry.name == name)
+                .unwrap();
+            let len = file_data(name).len();
+            unsafe { core::slice::from_raw_parts(entry.base as *const u8, len) }.to_vec()
+        };
+
+        loader
+            .apply_relocations_for(".text", &load_info, &mut owner)
+            .unwrap();
+        assert_ne!(memory(&owner, ".text"), file_data(".text"));
+        for target in targets.iter().filter(|target| *target != ".text") {
+            assert_eq!(memory(&owner, target), file_data(target), "{target}");
+        }
+
+        assert_eq!(
+            loader.apply_relocations_for(".no_such_section", &load_info, &mut owner),
+            Err(ModuleErr::ENOENT)
+        );
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_module_signature() {
+        use crate::signature::append_signature;
+
+        /// Load `data` trusting only `b"trusted"`
+        fn load(data: &[u8], enforce: bool) -> crate::Result<crate::ModuleOwner<HookHelper>> {
+            Hooks {
+                resolve_symbol: Some(stub_symbol),
+                verify_signature: Some(|data, sig| {
+                    assert_eq!(data, HELLO);
+                    sig == b"trusted"
+                }),
+                sig_enforce: enforce,
+                ..Default::default()
+            }
+            .install();
+            ModuleLoader::new(data)?.load_module(CString::default())
+        }
+
+        let signed = append_signature(HELLO, b"trusted");
+        let owner = load(&signed, true).unwrap();
+        assert!(owner.sig_ok());
+        assert_eq!(owner.name(), "hello");
+
+        let forged = append_signature(HELLO, b"forged");
+        assert_eq!(
+            load(&forged, true).err(),
+            Some(ax_errno::LinuxError::EKEYREJECTED)
+        );
+        assert_eq!(
+            load(HELLO, true).err(),
+            Some(ax_errno::LinuxError::EKEYREJECTED)
+        );
+
+        // Allocating for on-demand relocation is checked the same way
+        assert_eq!(
+            ModuleLoader::<HookHelper>::new(HELLO)
+                .unwrap()
+                .allocate()
+                .err(),
+            Some(ax_errno::LinuxError::EKEYREJECTED)
+        );
+        let mut loader = ModuleLoader::<HookHelper>::new(&signed).unwrap();
+        assert!(loader.allocate().unwrap().0.sig_ok());
+
+        // Without enforcement an unverified module still loads, unflagged
+        assert!(load(&forged, false).is_ok_and(|owner| !owner.sig_ok()));
+        assert!(load(HELLO, false).is_ok_and(|owner| !owner.sig_ok()));
+
+        // A rejected signature can be told from a module rejected once
+        // relocated
+        let unsigned = load(HELLO, true).err();
+        Hooks {
+            resolve_symbol: Some(stub_symbol),
+            verify_signature: Some(|_, _| true),
+            sig_enforce: true,
+            verify_module: Some(|_| Err(())),
+            ..Default::default()
+        }
+        .install();
+        let err = ModuleLoader::<HookHelper>::new(&signed)
+            .unwrap()
+            .load_module(CString::default())
+            .err();
+        assert_eq!(err, Some(ax_errno::LinuxError::EACCES));
+        assert_ne!(err, unsigned);
+    }
+}
@@ -0,0 +1,106 @@
+use core::{
+    ffi::{CStr, c_ulong},
+    mem::{offset_of, size_of},
+};
+
+use kmod_tools::kbindings::modversion_info;
+
+/// The `__versions` section of a module: one `modversion_info` (CRC and
+/// symbol name) per imported symbol, as generated by modpost.
+pub(crate) struct ModVersions<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ModVersions<'a> {
+    /// Wrap the raw section contents. The data is read unaligned, so it may
+    /// point straight into the ELF file.
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        ModVersions { data }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&'a CStr, u32)> {
+        self.data
+            .chunks_exact(size_of::<modversion_info>())
+            .filter_map(|entry| {
+                let crc_offset = offset_of!(modversion_info, crc);
+                let crc = unsafe {
+                    core::ptr::read_unaligned(entry[crc_offset..].as_ptr() as *const c_ulong)
+                };
+                let name = CStr::from_bytes_until_nul(&entry[offset_of!(modversion_info, name)..]);
+                name.ok().map(|name| (name, crc as u32))
+            })
+    }
+
+    /// Check the CRC the module recorded for `symname` against the one the
+    /// kernel exports, if any.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/module/version.c#L13>
+    pub(crate) fn check(&self, module_name: &str, symname: &str, crc: Option<u32>) -> bool {
+        // Exporting module didn't supply crcs? OK, nothing to compare against.
+        let Some(crc) = crc else {
+            return true;
+        };
+        for (name, module_crc) in self.entries() {
+            if name.to_bytes() != symname.as_bytes() {
+                continue;
+            }
+            if module_crc == crc {
+                return true;
+            }
+            log::debug!("Found checksum {:X} vs module {:X}", crc, module_crc);
+            log::warn!(
+                "{}: disagrees about version of symbol {}",
+                module_name,
+                symname
+            );
+            return false;
+        }
+        // Broken toolchain. Warn, then let it go..
+        log::warn!("{}: no symbol version for {}", module_name, symname);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn versions_section(entries: &[(&str, u32)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (name, crc) in entries {
+            let mut entry = [0u8; size_of::<modversion_info>()];
+            let crc_offset = offset_of!(modversion_info, crc);
+            entry[crc_offset..crc_offset + size_of::<c_ulong>()]
+                .copy_from_slice(&(*crc as c_ulong).to_ne_bytes());
+            let name_offset = offset_of!(modversion_info, name);
+            entry[name_offset..name_offset + name.len()].copy_from_slice(name.as_bytes());
+            data.extend_from_slice(&entry);
+        }
+        data
+    }
+
+    #[test]
+    fn test_check_version() {
+        let data = versions_section(&[("printk", 0x1234_5678), ("kmalloc", 0xdead_beef)]);
+        // Misalign the section to make sure it is read unaligned.
+        let mut shifted = alloc::vec![0u8];
+        shifted.extend_from_slice(&data);
+        let versions = ModVersions::new(&shifted[1..]);
+
+        assert!(versions.check("test", "printk", Some(0x1234_5678)));
+        assert!(versions.check("test", "kmalloc", Some(0xdead_beef)));
+        assert!(!versions.check("test", "printk", Some(0x8765_4321)));
+    }
+
+    #[test]
+    fn test_check_version_missing() {
+        let data = versions_section(&[("printk", 0x1234_5678)]);
+        let versions = ModVersions::new(&data);
+        // The kernel has no CRC for the symbol
+        assert!(versions.check("test", "printk", None));
+        // The module has no CRC for the symbol
+        assert!(versions.check("test", "kfree", Some(1)));
+    }
+}
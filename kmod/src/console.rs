@@ -0,0 +1,88 @@
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// A console sink receiving the text printed by a module.
+pub type ConsoleSink = fn(&str);
+
+static CONSOLE_SINK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Register the sink that [`KernelWriter`], [`print!`](crate::print) and
+/// [`println!`](crate::println) forward to. Until one is registered, output is
+/// discarded.
+pub fn set_console_sink(sink: ConsoleSink) {
+    CONSOLE_SINK.store(sink as *mut (), Ordering::Release);
+}
+
+fn console_sink() -> Option<ConsoleSink> {
+    let sink = CONSOLE_SINK.load(Ordering::Acquire);
+    if sink.is_null() {
+        None
+    } else {
+        Some(unsafe { core::mem::transmute::<*mut (), ConsoleSink>(sink) })
+    }
+}
+
+/// A [`core::fmt::Write`] implementation writing to the registered console
+/// sink.
+pub struct KernelWriter;
+
+impl core::fmt::Write for KernelWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if let Some(sink) = console_sink() {
+            sink(s);
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(args: core::fmt::Arguments) {
+    let _ = core::fmt::Write::write_fmt(&mut KernelWriter, args);
+}
+
+/// Prints to the registered console sink.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::_print(format_args!($($arg)*))
+    };
+}
+
+/// Prints to the registered console sink, with a newline.
+#[macro_export]
+macro_rules! println {
+    () => {
+        $crate::print!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::_print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU8, AtomicUsize};
+
+    use super::*;
+
+    static CAPTURED_LEN: AtomicUsize = AtomicUsize::new(0);
+    static CAPTURED: [AtomicU8; 64] = [const { AtomicU8::new(0) }; 64];
+
+    fn capture_sink(s: &str) {
+        for &b in s.as_bytes() {
+            let idx = CAPTURED_LEN.fetch_add(1, Ordering::SeqCst);
+            CAPTURED[idx].store(b, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_print_to_sink() {
+        set_console_sink(capture_sink);
+        crate::print!("answer={}", 42);
+        crate::println!(", ok");
+        crate::println!();
+
+        let len = CAPTURED_LEN.load(Ordering::SeqCst);
+        let captured: [u8; 64] = core::array::from_fn(|i| CAPTURED[i].load(Ordering::SeqCst));
+        assert_eq!(&captured[..len], b"answer=42, ok\n\n");
+    }
+}
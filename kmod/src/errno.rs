@@ -0,0 +1,27 @@
+use ax_errno::LinuxError;
+
+/// Convert the negative errno returned by C code, such as an operation of a
+/// parameter, into a [`LinuxError`]. Codes that are not a known
+/// [`LinuxError`] become `EINVAL`. The reverse direction is
+/// [`LinuxError::code`].
+pub fn errno_to_err(ret: core::ffi::c_int) -> LinuxError {
+    LinuxError::try_from(ret.wrapping_neg()).unwrap_or(LinuxError::EINVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errno_round_trip() {
+        for err in [LinuxError::ENOENT, LinuxError::EINVAL, LinuxError::ENOSPC] {
+            assert_eq!(errno_to_err(-err.code()), err);
+        }
+    }
+
+    #[test]
+    fn test_unknown_errno() {
+        assert_eq!(errno_to_err(-4095), LinuxError::EINVAL);
+        assert_eq!(errno_to_err(core::ffi::c_int::MIN), LinuxError::EINVAL);
+    }
+}
@@ -1,8 +1,61 @@
 #![no_std]
 #![allow(unsafe_op_in_unsafe_fn)]
+extern crate alloc;
+#[cfg(test)]
+extern crate self as kmod_tools;
+mod console;
+mod errno;
 mod module;
+mod panic;
 mod param;
+mod symbol;
+pub use console::{_print, ConsoleSink, KernelWriter, set_console_sink};
+pub use errno::errno_to_err;
 pub use kbindings;
 pub use kmacro_tools::*;
-pub use module::Module;
+pub use module::{Module, ModuleState};
+pub use panic::_panic;
 pub use param::*;
+pub use symbol::KernelSymbol;
+
+// Test builds link std, which already provides a panic handler.
+#[cfg(all(feature = "provide-panic-handler", not(test)))]
+default_panic_handler!();
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use goblin::elf::Elf;
+
+    #[kmod_tools::section(".kmod_test.text")]
+    #[inline(never)]
+    fn section_probe() -> usize {
+        42
+    }
+
+    #[kmod_tools::section(".kmod_test.data")]
+    static SECTION_PROBE_DATA: usize = 42;
+
+    #[test]
+    fn test_section_attribute() {
+        assert_eq!(section_probe(), 42);
+        let exe = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let elf = Elf::parse(&exe).unwrap();
+        let section_of = |name: &str| {
+            let sym = elf
+                .syms
+                .iter()
+                .find(|sym| {
+                    elf.strtab
+                        .get_at(sym.st_name)
+                        .is_some_and(|sym_name| sym_name.contains(name))
+                })
+                .unwrap();
+            let shdr = &elf.section_headers[sym.st_shndx];
+            elf.shdr_strtab.get_at(shdr.sh_name).unwrap()
+        };
+        assert_eq!(section_of("section_probe"), ".kmod_test.text");
+        assert_eq!(section_of("SECTION_PROBE_DATA"), ".kmod_test.data");
+    }
+}
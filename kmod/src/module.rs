@@ -1,5 +1,21 @@
 use crate::KernelParam;
 
+/// The lifecycle state of a [`Module`]
+///
+/// See <https://elixir.bootlin.com/linux/v6.6/source/include/linux/module.h#L315>
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleState {
+    /// Normal state
+    Live = kbindings::module_state_MODULE_STATE_LIVE,
+    /// Fully formed, running module_init
+    Coming = kbindings::module_state_MODULE_STATE_COMING,
+    /// Going away
+    Going = kbindings::module_state_MODULE_STATE_GOING,
+    /// Still setting it up
+    Unformed = kbindings::module_state_MODULE_STATE_UNFORMED,
+}
+
 /// The `Module` struct represents a kernel module.
 ///
 /// See <https://elixir.bootlin.com/linux/v6.6/source/include/linux/module.h#L402>
@@ -26,6 +42,59 @@ impl Module {
         Module(module)
     }
 
+    /// Set the name stored in the module struct, as modpost does with
+    /// `KBUILD_MODNAME`. The name must leave room for a terminating NUL.
+    ///
+    /// [`module!`](crate::module) uses this, and rejects names of 56 bytes or
+    /// more at expansion time:
+    /// ```compile_fail
+    /// use kmod_tools::{exit_fn, init_fn, module};
+    ///
+    /// #[init_fn]
+    /// fn init() -> i32 { 0 }
+    /// #[exit_fn]
+    /// fn exit() {}
+    ///
+    /// // 60 bytes
+    /// module!(
+    ///     name: "a_module_name_that_is_much_too_long_for_struct_module_xxxxxx",
+    ///     license: "GPL",
+    ///     description: "",
+    ///     version: "0.1.0",
+    /// );
+    /// # fn main() {}
+    /// ```
+    ///
+    /// 55 bytes still fit:
+    /// ```
+    /// use kmod_tools::{exit_fn, init_fn, module};
+    ///
+    /// #[init_fn]
+    /// fn init() -> i32 { 0 }
+    /// #[exit_fn]
+    /// fn exit() {}
+    ///
+    /// module!(
+    ///     name: "a_module_name_that_just_fits_in_struct_module_xxxxxxxxx",
+    ///     license: "GPL",
+    ///     description: "",
+    ///     version: "0.1.0",
+    /// );
+    /// # fn main() {
+    /// #     assert_eq!(__this_module.name().len(), 55);
+    /// # }
+    /// ```
+    pub const fn with_name(mut self, name: &str) -> Self {
+        let bytes = name.as_bytes();
+        assert!(bytes.len() < self.0.name.len(), "module name is too long");
+        let mut i = 0;
+        while i < bytes.len() {
+            self.0.name[i] = bytes[i] as core::ffi::c_char;
+            i += 1;
+        }
+        self
+    }
+
     pub fn init_fn(&self) -> Option<unsafe extern "C" fn() -> core::ffi::c_int> {
         self.0.init
     }
@@ -42,16 +111,110 @@ impl Module {
         self.0.exit.take()
     }
 
+    /// The lifecycle state. A value that is not a known state reads as
+    /// [`ModuleState::Unformed`].
+    pub fn state(&self) -> ModuleState {
+        match self.0.state {
+            kbindings::module_state_MODULE_STATE_LIVE => ModuleState::Live,
+            kbindings::module_state_MODULE_STATE_COMING => ModuleState::Coming,
+            kbindings::module_state_MODULE_STATE_GOING => ModuleState::Going,
+            _ => ModuleState::Unformed,
+        }
+    }
+
+    /// Move the module to `state`, as the loader does while loading,
+    /// initializing and unloading it
+    pub fn set_state(&mut self, state: ModuleState) {
+        self.0.state = state as kbindings::module_state;
+    }
+
+    /// The name in the fixed-size `name` array, up to the first NUL or the
+    /// end of the array. Reads as "unknown" if it is not UTF-8.
     pub fn name(&self) -> &str {
-        let c_str = unsafe { core::ffi::CStr::from_ptr(self.0.name.as_ptr()) };
-        c_str.to_str().unwrap_or("unknown")
+        let name = &self.0.name;
+        let bytes = unsafe { core::slice::from_raw_parts(name.as_ptr() as *const u8, name.len()) };
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        core::str::from_utf8(&bytes[..len]).unwrap_or("unknown")
     }
 
     pub fn raw_mod(&mut self) -> &mut kbindings::module {
         &mut self.0
     }
 
+    pub fn params(&self) -> &[KernelParam] {
+        if self.0.kp.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.0.kp as _, self.0.num_kp as usize) }
+    }
+
+    /// The module's exception table (`__ex_table`), whose entries hold
+    /// offsets relative to their own fields
+    pub fn exception_table(&self) -> &[kbindings::exception_table_entry] {
+        if self.0.extable.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.0.extable, self.0.num_exentries as usize) }
+    }
+
+    /// The module's bug table (`__bug_table`), whose entries hold offsets
+    /// relative to their own fields
+    pub fn bug_table(&self) -> &[kbindings::bug_entry] {
+        if self.0.bug_table.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.0.bug_table, self.0.num_bugs as usize) }
+    }
+
+    /// The module's static key jump table (`__jump_table`), whose entries
+    /// hold offsets relative to their own fields
+    pub fn jump_entries(&self) -> &[kbindings::jump_entry] {
+        if self.0.jump_entries.is_null() {
+            return &[];
+        }
+        unsafe {
+            core::slice::from_raw_parts(self.0.jump_entries, self.0.num_jump_entries as usize)
+        }
+    }
+
     pub fn params_mut(&mut self) -> &mut [KernelParam] {
+        if self.0.kp.is_null() {
+            return &mut [];
+        }
         unsafe { core::slice::from_raw_parts_mut(self.0.kp as _, self.0.num_kp as usize) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_state() {
+        let mut module = Module::new(None, None);
+        assert_eq!(module.state(), ModuleState::Live);
+        for state in [
+            ModuleState::Unformed,
+            ModuleState::Coming,
+            ModuleState::Live,
+            ModuleState::Going,
+        ] {
+            module.set_state(state);
+            assert_eq!(module.state(), state);
+        }
+        module.raw_mod().state = 42;
+        assert_eq!(module.state(), ModuleState::Unformed);
+    }
+
+    #[test]
+    fn test_module_name() {
+        const MODULE: Module = Module::new(None, None).with_name("hello");
+        assert_eq!(MODULE.name(), "hello");
+
+        let mut module = Module::new(None, None);
+        assert_eq!(module.name(), "");
+        // Without a NUL the name ends with the array
+        module.raw_mod().name.fill(b'a' as _);
+        assert_eq!(module.name().len(), module.raw_mod().name.len());
+    }
+}
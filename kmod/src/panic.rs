@@ -0,0 +1,65 @@
+/// Report a panic on the registered console sink and halt.
+#[doc(hidden)]
+pub fn _panic(info: &core::panic::PanicInfo) -> ! {
+    crate::println!("{}", info);
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Define a `#[panic_handler]` that prints the panic message through
+/// [`KernelWriter`](crate::KernelWriter) and then halts.
+///
+/// [`module!`](crate::module) never emits a panic handler, since the kernel a
+/// module links into usually provides one. Modules that are built standalone
+/// and need one can opt in with this macro, or define their own. Enabling the
+/// `provide-panic-handler` feature of this crate defines it for them.
+/// # Example:
+/// ```ignore
+/// kmod_tools::default_panic_handler!();
+/// ```
+#[macro_export]
+macro_rules! default_panic_handler {
+    () => {
+        #[panic_handler]
+        fn panic(info: &core::panic::PanicInfo) -> ! {
+            $crate::_panic(info)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    mod own_panic_handler {
+        use crate::{exit_fn, init_fn, module};
+
+        #[init_fn]
+        fn init() -> i32 {
+            0
+        }
+
+        #[exit_fn]
+        fn exit() {}
+
+        // Stands in for the module's own `#[panic_handler]` (tests link std,
+        // which already has one): `module!` must not define anything that
+        // collides with it.
+        #[allow(dead_code)]
+        fn panic(info: &core::panic::PanicInfo) -> ! {
+            crate::_panic(info)
+        }
+
+        module!(
+            name: "own_panic",
+            license: "GPL",
+            description: "A module with its own panic handler",
+            version: "0.1.0",
+        );
+
+        #[test]
+        fn test_module_with_own_panic_handler() {
+            assert_eq!(unsafe { init_module() }, 0);
+            assert_eq!(__this_module.name(), "own_panic");
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use alloc::{string::String, vec::Vec};
 use core::ffi::CStr;
 
+use ax_errno::LinuxError;
 pub use kbindings::{kernel_param, kernel_param_ops};
 /// The `KernelParam` struct represents a kernel module parameter.
 ///
@@ -7,6 +9,9 @@ pub use kbindings::{kernel_param, kernel_param_ops};
 #[repr(transparent)]
 pub struct KernelParam(kbindings::kernel_param);
 
+unsafe impl Send for KernelParam {}
+unsafe impl Sync for KernelParam {}
+
 impl Default for KernelParam {
     fn default() -> Self {
         let mut param = core::mem::MaybeUninit::<kbindings::kernel_param>::uninit();
@@ -19,6 +24,48 @@ impl Default for KernelParam {
 }
 
 impl KernelParam {
+    /// Create a new parameter named `name`, handled by `ops` and backed by the
+    /// value that `arg` points to.
+    pub const fn new(
+        name: &'static CStr,
+        ops: &'static kbindings::kernel_param_ops,
+        arg: *mut core::ffi::c_void,
+        perm: u16,
+        level: i8,
+    ) -> Self {
+        KernelParam(kbindings::kernel_param {
+            name: name.as_ptr(),
+            mod_: core::ptr::null_mut(),
+            ops,
+            perm,
+            level,
+            flags: 0,
+            __bindgen_anon_1: kbindings::kernel_param__bindgen_ty_1 { arg },
+        })
+    }
+
+    /// View the contents of a `__param` section as a slice of parameters.
+    ///
+    /// Returns `None` if `size` is not a multiple of the size of
+    /// `kernel_param` or `base` is not suitably aligned.
+    ///
+    /// # Safety
+    /// `base` must point to `size` bytes of initialized `kernel_param`s that
+    /// stay valid and unaliased for `'a`.
+    pub unsafe fn slice_from_section<'a>(base: *mut u8, size: usize) -> Option<&'a mut [Self]> {
+        let elem_size = core::mem::size_of::<kbindings::kernel_param>();
+        if !size.is_multiple_of(elem_size) {
+            return None;
+        }
+        if size == 0 {
+            return Some(&mut []);
+        }
+        if base.is_null() || !base.cast::<Self>().is_aligned() {
+            return None;
+        }
+        Some(unsafe { core::slice::from_raw_parts_mut(base.cast(), size / elem_size) })
+    }
+
     pub fn name(&self) -> &str {
         unsafe {
             let c_str = core::ffi::CStr::from_ptr(self.0.name);
@@ -39,16 +86,32 @@ impl KernelParam {
         &self.0
     }
 
+    /// Returns a raw pointer to the underlying `kernel_param`, e.g. for the
+    /// `set`/`get` callbacks.
+    ///
+    /// The pointer is valid for as long as `self` is alive and not moved. It
+    /// must not be written through; use [`KernelParam::as_raw_mut`] for that.
+    pub fn as_raw(&self) -> *const kbindings::kernel_param {
+        &self.0
+    }
+
+    /// Returns a mutable raw pointer to the underlying `kernel_param`.
+    ///
+    /// The pointer is valid for as long as `self` is alive and not moved, and
+    /// no other reference to `self` may be used while writing through it.
+    pub fn as_raw_mut(&mut self) -> *mut kbindings::kernel_param {
+        &mut self.0
+    }
+
     pub fn raw_name(&self) -> &CStr {
         unsafe { CStr::from_ptr(self.0.name) }
     }
 
     /// Returns a pointer to the argument value.
     ///
-    /// # Safety
-    /// This function is unsafe because it accesses an union field. User
-    /// must ensure that the correct field is accessed based on the context.
-    pub unsafe fn arg_ptr(&self) -> *mut core::ffi::c_void {
+    /// Every variant of the union is a pointer, so reading it as `arg` is
+    /// always sound; dereferencing the result requires knowing its real type.
+    pub fn arg_ptr(&self) -> *mut core::ffi::c_void {
         unsafe { self.0.__bindgen_anon_1.arg }
     }
 
@@ -69,4 +132,186 @@ impl KernelParam {
     pub fn ops(&self) -> &kbindings::kernel_param_ops {
         unsafe { self.0.ops.as_ref().unwrap() }
     }
+
+    /// Set the parameter from `value` through its `set` operation.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/params.c#L563>
+    pub fn set(&mut self, value: &str) -> Result<(), LinuxError> {
+        let set = self.ops().set.ok_or(LinuxError::EPERM)?;
+        if value.contains('\0') {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut val = Vec::with_capacity(value.len() + 1);
+        val.extend_from_slice(value.as_bytes());
+        val.push(0);
+        let ret = unsafe { set(val.as_ptr().cast(), self.as_raw()) };
+        errno(ret).map(|_| ())
+    }
+
+    /// Format the parameter through its `get` operation, as it would be
+    /// shown in `/sys/module/<module>/parameters/<name>`. The operation
+    /// writes into a buffer of the kernel's `PAGE_SIZE`.
+    ///
+    /// See <https://elixir.bootlin.com/linux/v6.6/source/kernel/params.c#L544>
+    pub fn get(&self) -> Result<String, LinuxError> {
+        let get = self.ops().get.ok_or(LinuxError::EPERM)?;
+        let mut buf = alloc::vec![0u8; kbindings::PAGE_SIZE];
+        let len = errno(unsafe { get(buf.as_mut_ptr().cast(), self.as_raw()) })?;
+        buf.truncate(len.min(kbindings::PAGE_SIZE));
+        String::from_utf8(buf).map_err(|_| LinuxError::EINVAL)
+    }
+}
+
+/// Map the return value of a parameter operation: a negative errno or a
+/// non-negative length.
+fn errno(ret: core::ffi::c_int) -> Result<usize, LinuxError> {
+    if ret >= 0 {
+        return Ok(ret as usize);
+    }
+    Err(crate::errno_to_err(ret))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ffi::{c_char, c_int, c_void};
+
+    use super::*;
+
+    static TEST_OPS: kernel_param_ops = kernel_param_ops {
+        flags: 0,
+        set: None,
+        get: None,
+        free: None,
+    };
+
+    #[test]
+    fn test_new_int_param() {
+        let mut value: c_int = 7;
+        let arg = &mut value as *mut c_int as *mut c_void;
+        let kp = KernelParam::new(c"count", &TEST_OPS, arg, 0o644, -1);
+        assert_eq!(kp.name(), "count");
+        assert_eq!(kp.raw_name(), c"count");
+        assert_eq!(kp.raw_kernel_param().perm, 0o644);
+        assert_eq!(kp.level(), -1);
+        assert!(core::ptr::eq(kp.ops(), &TEST_OPS));
+        assert_eq!(kp.arg_ptr(), arg);
+        assert_eq!(unsafe { *(kp.arg_ptr() as *const c_int) }, 7);
+    }
+
+    #[test]
+    fn test_new_bool_param() {
+        let mut value = true;
+        let arg = &mut value as *mut bool as *mut c_void;
+        let kp = KernelParam::new(c"enable", &TEST_OPS, arg, 0o444, 3);
+        assert_eq!(kp.name(), "enable");
+        assert_eq!(kp.level(), 3);
+        assert!(unsafe { *(kp.arg_ptr() as *const bool) });
+    }
+
+    #[test]
+    fn test_new_charp_param() {
+        let mut value: *mut c_char = c"hello".as_ptr() as *mut c_char;
+        let arg = &mut value as *mut *mut c_char as *mut c_void;
+        let kp = KernelParam::new(c"greeting", &TEST_OPS, arg, 0, 0);
+        assert_eq!(kp.name(), "greeting");
+        let s = unsafe { CStr::from_ptr(*(kp.arg_ptr() as *const *mut c_char)) };
+        assert_eq!(s, c"hello");
+        assert!(kp.raw_kernel_param().mod_.is_null());
+    }
+
+    #[test]
+    fn test_raw_accessors() {
+        let mut value: c_int = 1;
+        let mut other: c_int = 2;
+        let mut kp = KernelParam::new(
+            c"raw",
+            &TEST_OPS,
+            &mut value as *mut c_int as *mut c_void,
+            0,
+            0,
+        );
+
+        let raw = kp.as_raw();
+        assert!(core::ptr::eq(raw, kp.raw_kernel_param()));
+        assert_eq!(unsafe { (*raw).name }, c"raw".as_ptr());
+
+        let raw_mut = kp.as_raw_mut();
+        unsafe {
+            (*raw_mut).perm = 0o600;
+            (*raw_mut).__bindgen_anon_1.arg = &mut other as *mut c_int as *mut c_void;
+        }
+        assert_eq!(kp.raw_kernel_param().perm, 0o600);
+        assert_eq!(unsafe { *(kp.arg_ptr() as *const c_int) }, 2);
+    }
+
+    unsafe extern "C" fn set_int(val: *const c_char, kp: *const kernel_param) -> c_int {
+        let val = unsafe { CStr::from_ptr(val) }.to_str().unwrap();
+        let Ok(v) = val.parse::<c_int>() else {
+            return -(LinuxError::EINVAL as c_int);
+        };
+        unsafe { *((*kp).__bindgen_anon_1.arg as *mut c_int) = v };
+        0
+    }
+
+    unsafe extern "C" fn get_int(buffer: *mut c_char, kp: *const kernel_param) -> c_int {
+        let v = unsafe { *((*kp).__bindgen_anon_1.arg as *const c_int) };
+        let s = alloc::format!("{}\n", v);
+        unsafe { core::ptr::copy_nonoverlapping(s.as_ptr(), buffer.cast(), s.len()) };
+        s.len() as c_int
+    }
+
+    static INT_OPS: kernel_param_ops = kernel_param_ops {
+        flags: 0,
+        set: Some(set_int),
+        get: Some(get_int),
+        free: None,
+    };
+
+    #[test]
+    fn test_set_get_int_param() {
+        let mut value: c_int = 7;
+        let arg = &mut value as *mut c_int as *mut c_void;
+        let mut kp = KernelParam::new(c"count", &INT_OPS, arg, 0o644, -1);
+        assert_eq!(kp.get().as_deref(), Ok("7\n"));
+
+        assert_eq!(kp.set("-42"), Ok(()));
+        assert_eq!(kp.get().as_deref(), Ok("-42\n"));
+        assert_eq!(kp.set("forty-two"), Err(LinuxError::EINVAL));
+        assert_eq!(kp.set("4\x002"), Err(LinuxError::EINVAL));
+        assert_eq!(kp.get().as_deref(), Ok("-42\n"));
+        assert_eq!(value, -42);
+    }
+
+    #[test]
+    fn test_set_get_without_ops() {
+        let mut value: c_int = 0;
+        let arg = &mut value as *mut c_int as *mut c_void;
+        let mut kp = KernelParam::new(c"count", &TEST_OPS, arg, 0, 0);
+        assert_eq!(kp.set("1"), Err(LinuxError::EPERM));
+        assert_eq!(kp.get(), Err(LinuxError::EPERM));
+    }
+
+    #[test]
+    fn test_slice_from_section() {
+        let mut a: c_int = 1;
+        let mut b: c_int = 2;
+        let mut section = [
+            KernelParam::new(c"a", &TEST_OPS, &mut a as *mut c_int as *mut c_void, 0, 0),
+            KernelParam::new(c"b", &TEST_OPS, &mut b as *mut c_int as *mut c_void, 0, 1),
+        ];
+        let base = section.as_mut_ptr() as *mut u8;
+        let size = core::mem::size_of_val(&section);
+
+        let params = unsafe { KernelParam::slice_from_section(base, size) }.unwrap();
+        assert_eq!(params.len(), 2);
+        let names: [&str; 2] = [params[0].name(), params[1].name()];
+        assert_eq!(names, ["a", "b"]);
+        for (kp, expected) in params.iter().zip([1, 2]) {
+            assert_eq!(unsafe { *(kp.arg_ptr() as *const c_int) }, expected);
+        }
+
+        assert!(unsafe { KernelParam::slice_from_section(base, size - 1) }.is_none());
+        let empty = unsafe { KernelParam::slice_from_section(core::ptr::null_mut(), 0) };
+        assert_eq!(empty.map(|p| p.len()), Some(0));
+    }
 }
@@ -0,0 +1,27 @@
+/// A symbol exported with `#[capi_fn]` or `#[cdata]`.
+///
+/// Every exported item gets one entry in the `kmod_symtab` section, so the
+/// whole table can be walked between `__start_kmod_symtab` and
+/// `__stop_kmod_symtab` to resolve the symbols a loaded module imports.
+#[repr(C)]
+pub struct KernelSymbol {
+    name: &'static str,
+    addr: *const (),
+}
+
+unsafe impl Send for KernelSymbol {}
+unsafe impl Sync for KernelSymbol {}
+
+impl KernelSymbol {
+    pub const fn new(name: &'static str, addr: *const ()) -> Self {
+        KernelSymbol { name, addr }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn addr(&self) -> usize {
+        self.addr as usize
+    }
+}
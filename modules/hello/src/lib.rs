@@ -3,36 +3,30 @@ extern crate alloc;
 
 use alloc::vec;
 
-use kmod_tools::{exit_fn, init_fn, module};
+use kmod_tools::{exit_fn, init_fn, module, println, set_console_sink};
 
 unsafe extern "C" {
     fn write_char(c: u8);
 }
 
-struct Writer;
-
-impl core::fmt::Write for Writer {
-    fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for &b in s.as_bytes() {
-            unsafe { write_char(b) };
-        }
-        Ok(())
+fn console_write(s: &str) {
+    for &b in s.as_bytes() {
+        unsafe { write_char(b) };
     }
 }
 
 #[init_fn]
 pub fn hello_init() -> i32 {
-    let mut writer = Writer;
-    core::fmt::write(&mut writer, format_args!("Hello, Kernel Module!\n")).unwrap();
+    set_console_sink(console_write);
+    println!("Hello, Kernel Module!");
     let v = vec![1, 2, 3, 4, 5];
-    core::fmt::write(&mut writer, format_args!("Vector contents: {:?}\n", v)).unwrap();
+    println!("Vector contents: {:?}", v);
     0
 }
 
 #[exit_fn]
 fn hello_exit() {
-    let mut writer = Writer;
-    core::fmt::write(&mut writer, format_args!("Goodbye, Kernel Module!\n")).unwrap();
+    println!("Goodbye, Kernel Module!");
 }
 
 module!(